@@ -1,15 +1,4 @@
-use std::collections::HashMap;
-
-use valence::{
-    entity::{
-        block_display::{self, BlockDisplayEntityBundle},
-        display,
-    },
-    layer::chunk::IntoBlock,
-    prelude::*,
-};
-
-use crate::utils::*;
+use valence::prelude::*;
 
 /// An `AltBlock` is a block that changes under certain conditions.
 #[derive(Debug, Clone, PartialEq)]
@@ -57,6 +46,15 @@ impl AltBlock {
             }
         }
     }
+
+    /// Returns the block of the first `AltBlockState`.
+    pub fn first_block(&self) -> Option<BlockState> {
+        match self {
+            AltBlock::Tick(blocks, _) => blocks.first().map(|(state, _)| match state {
+                AltBlockState::Block(block) | AltBlockState::SmallBlock(block) => *block,
+            }),
+        }
+    }
 }
 
 /// The `AltBlockParams` struct represents the parameters of the current tick.
@@ -79,56 +77,3 @@ pub enum AltBlockState {
     /// A smaller, non-solid block. I.e., a `BlockDisplay` with a smaller size.
     SmallBlock(BlockState),
 }
-
-impl AltBlockState {
-    /// Sets the block in the world.
-    pub fn set_block(
-        &self,
-        pos: BlockPos,
-        alt_block_entities: &mut HashMap<BlockPos, Entity>,
-        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
-        commands: &mut Commands,
-        world: &mut ChunkLayer,
-        layer: &EntityLayerId,
-    ) {
-        if prev_alt_block_states.contains_key(&pos) && *self == prev_alt_block_states[&pos] {
-            return;
-        }
-
-        prev_alt_block_states.insert(pos, *self);
-
-        match self {
-            AltBlockState::Block(block) => {
-                if alt_block_entities.contains_key(&pos) {
-                    if let Some(mut entity) = commands.get_entity(alt_block_entities[&pos]) {
-                        entity.insert(Despawned);
-                    }
-                    alt_block_entities.remove(&pos);
-                }
-                world.set_block(pos, block.into_block());
-            }
-            AltBlockState::SmallBlock(block) => {
-                if alt_block_entities.contains_key(&pos) {
-                    if let Some(mut entity) = commands.get_entity(alt_block_entities[&pos]) {
-                        entity.insert(Despawned);
-                    }
-                }
-
-                world.set_block(pos, BlockState::AIR.into_block());
-
-                let display = BlockDisplayEntityBundle {
-                    position: Position(pos.to_vec3().as_dvec3()),
-                    layer: *layer,
-                    block_display_block_state: block_display::BlockState(*block),
-                    display_scale: display::Scale(Vec3::new(0.5, 0.5, 0.5)),
-                    display_translation: display::Translation(Vec3::new(0.25, 0.25, 0.25)),
-                    ..Default::default()
-                };
-
-                let entity = commands.spawn(display).id();
-
-                alt_block_entities.insert(pos, entity);
-            }
-        }
-    }
-}
@@ -0,0 +1,519 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use valence::{
+    abilities::{PlayerAbilitiesFlags, PlayerStartFlyingEvent},
+    action::DiggingEvent,
+    event_loop::PacketEvent,
+    interact_block::InteractBlockEvent,
+    math::DVec2,
+    movement::MovementEvent,
+    prelude::*,
+    protocol::{
+        packets::play::{BlockUpdateS2c, CreativeInventoryActionC2s},
+        WritePacket,
+    },
+};
+
+use crate::{
+    config::{AntiCheatConfig, ServerConfig},
+    game_state::GameState,
+    prediction::prediction_state::PredictionState,
+    random,
+};
+
+/// Every flag is appended to this file, so the evidence is kept after the run
+/// ends and the server restarts.
+pub const FLAG_LOG_PATH: &str = "data/flags.log";
+
+/// How many ticks movement isn't checked after the server moved a player.
+const TELEPORT_GRACE_TICKS: u32 = 20;
+/// The furthest a player can move horizontally in one tick while on the
+/// ground. Lag can make several ticks of movement arrive at once.
+const MAX_TICK_DISTANCE: f64 = 5.0;
+/// Room given on top of the highest possible jump.
+const HEIGHT_TOLERANCE: f64 = 0.5;
+/// Room given on top of the furthest possible jump. The prediction assumes an
+/// average running speed, so it is also scaled by `DISTANCE_SCALE`.
+const DISTANCE_TOLERANCE: f64 = 1.5;
+const DISTANCE_SCALE: f64 = 1.25;
+/// How many ticks a player can keep rising or hovering after the highest
+/// point of a jump.
+const MAX_HOVER_TICKS: u32 = 10;
+/// How many ticks the horizontal speed is averaged over.
+const SPEED_WINDOW: usize = 20;
+/// The highest average horizontal speed in blocks per tick. Sprint jumping
+/// averages about 0.4.
+const MAX_AVERAGE_SPEED: f64 = 0.55;
+/// The highest a player can walk up without jumping.
+const STEP_HEIGHT: f64 = 0.6;
+/// How many more movement packets than ticks a player can send before being
+/// flagged. Vanilla clients send at most one per tick.
+const TIMER_TOLERANCE: i32 = 10;
+/// How many ticks of missing packets are remembered. Lagging players send
+/// the missed packets all at once afterwards.
+const MAX_PACKET_DEFICIT: i32 = 60;
+
+/// The kinds of cheating the anti-cheat detects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagKind {
+    Speed,
+    Fly,
+    StepUp,
+    Timer,
+    Teleport,
+    Creative,
+}
+
+impl FlagKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FlagKind::Speed => "speed",
+            FlagKind::Fly => "fly",
+            FlagKind::StepUp => "step-up",
+            FlagKind::Timer => "timer",
+            FlagKind::Teleport => "teleport",
+            FlagKind::Creative => "creative inventory",
+        }
+    }
+}
+
+/// The `Flag` struct represents a single time a player was caught cheating.
+///
+/// Properties:
+///
+/// * `kind`: What the player was caught doing.
+/// * `evidence`: A description of the movement that got the player flagged.
+/// * `tick`: The tick of the run the player was flagged at.
+#[derive(Clone, Debug)]
+pub struct Flag {
+    pub kind: FlagKind,
+    pub evidence: String,
+    pub tick: usize,
+}
+
+/// The `MovementCheck` component keeps track of a player's movement to detect
+/// movement that isn't possible in vanilla.
+#[derive(Component)]
+pub struct MovementCheck {
+    /// Where the player last stood on a block. Players are put back here when
+    /// they are flagged.
+    last_ground: DVec3,
+    jump: Option<Jump>,
+    grace_ticks: u32,
+    /// The horizontal speed of the player during the last `SPEED_WINDOW`
+    /// ticks.
+    speeds: VecDeque<f64>,
+    /// Movement packets received minus ticks passed.
+    packet_balance: i32,
+}
+
+/// A jump that is being checked.
+///
+/// Properties:
+///
+/// * `start`: Where the player left the ground.
+/// * `prediction`: The fastest and highest jump possible from `start`.
+/// * `max_y`: The highest point `prediction` has reached so far.
+/// * `hover_ticks`: How many ticks the player hasn't fallen since the
+/// prediction started falling.
+struct Jump {
+    start: DVec3,
+    prediction: PredictionState,
+    max_y: f64,
+    hover_ticks: u32,
+}
+
+impl Jump {
+    fn new(start: DVec3) -> Self {
+        Self {
+            start,
+            prediction: PredictionState::running_jump_vec(start, 0.0),
+            max_y: start.y,
+            hover_ticks: 0,
+        }
+    }
+}
+
+impl MovementCheck {
+    pub fn new(pos: DVec3) -> Self {
+        Self {
+            last_ground: pos,
+            jump: None,
+            grace_ticks: TELEPORT_GRACE_TICKS,
+            speeds: VecDeque::new(),
+            packet_balance: 0,
+        }
+    }
+
+    /// Has to be called whenever the server moves the player so it isn't
+    /// flagged as a teleport.
+    pub fn teleported(&mut self, pos: DVec3) {
+        self.last_ground = pos;
+        self.jump = None;
+        self.grace_ticks = TELEPORT_GRACE_TICKS;
+        self.speeds.clear();
+        self.packet_balance = 0;
+    }
+
+    /// Checks the movement since the last tick. `vel` is how far the player
+    /// moved since then.
+    fn check_tick(
+        &mut self,
+        pos: DVec3,
+        vel: DVec3,
+        layer: &ChunkLayer,
+    ) -> Option<(FlagKind, String)> {
+        let prev_pos = pos - vel;
+
+        self.packet_balance = (self.packet_balance - 1).max(-MAX_PACKET_DEFICIT);
+
+        if self.grace_ticks > 0 {
+            self.grace_ticks -= 1;
+            self.jump = None;
+            return None;
+        }
+
+        let speed = horizontal_distance(pos, prev_pos);
+
+        self.speeds.push_back(speed);
+        if self.speeds.len() > SPEED_WINDOW {
+            self.speeds.pop_front();
+        }
+
+        if self.speeds.len() == SPEED_WINDOW {
+            let average = self.speeds.iter().sum::<f64>() / SPEED_WINDOW as f64;
+            if average > MAX_AVERAGE_SPEED {
+                self.speeds.clear();
+                return Some((
+                    FlagKind::Speed,
+                    format!(
+                        "averaged {:.2} blocks per tick over {} ticks",
+                        average, SPEED_WINDOW
+                    ),
+                ));
+            }
+        }
+
+        if is_on_ground(pos, layer) {
+            self.jump = None;
+            self.last_ground = pos;
+
+            if speed > MAX_TICK_DISTANCE {
+                return Some((
+                    FlagKind::Teleport,
+                    format!("moved {:.1} blocks in one tick", speed),
+                ));
+            }
+
+            return None;
+        }
+
+        let jump = self.jump.get_or_insert_with(|| Jump::new(prev_pos));
+
+        jump.prediction.tick();
+        jump.max_y = jump.max_y.max(jump.prediction.pos.y);
+
+        if pos.y > jump.max_y + HEIGHT_TOLERANCE {
+            return Some((
+                FlagKind::Fly,
+                format!(
+                    "jumped {:.2} blocks high (at most {:.2} expected)",
+                    pos.y - jump.start.y,
+                    jump.max_y - jump.start.y
+                ),
+            ));
+        }
+
+        let dist = horizontal_distance(pos, jump.start);
+        let max_dist = horizontal_distance(jump.prediction.pos, jump.start);
+        if dist > max_dist * DISTANCE_SCALE + DISTANCE_TOLERANCE {
+            return Some((
+                FlagKind::Teleport,
+                format!(
+                    "jumped {:.1} blocks far (at most {:.1} expected)",
+                    dist, max_dist
+                ),
+            ));
+        }
+
+        if jump.prediction.vel.y < 0.0 && vel != DVec3::ZERO && vel.y >= 0.0 {
+            jump.hover_ticks += 1;
+            if jump.hover_ticks > MAX_HOVER_TICKS {
+                return Some((
+                    FlagKind::Fly,
+                    format!("didn't fall for {} ticks", jump.hover_ticks),
+                ));
+            }
+        }
+
+        None
+    }
+
+    /// Checks a single movement packet. Unlike `check_tick`, this is exactly
+    /// one tick of the client's movement, even if the player is lagging.
+    fn check_packet(
+        &mut self,
+        pos: DVec3,
+        old_pos: DVec3,
+        layer: &ChunkLayer,
+    ) -> Option<(FlagKind, String)> {
+        self.packet_balance += 1;
+
+        if self.grace_ticks > 0 {
+            return None;
+        }
+
+        if self.packet_balance > TIMER_TOLERANCE {
+            let balance = self.packet_balance;
+            self.packet_balance = 0;
+            return Some((
+                FlagKind::Timer,
+                format!("sent {} more movement packets than ticks passed", balance),
+            ));
+        }
+
+        let step = pos.y - old_pos.y;
+        if step > STEP_HEIGHT && is_on_ground(old_pos, layer) && is_on_ground(pos, layer) {
+            return Some((
+                FlagKind::StepUp,
+                format!("stepped up {:.2} blocks without jumping", step),
+            ));
+        }
+
+        None
+    }
+}
+
+/// Logs the flag with its evidence and adds it to the current run, which marks
+/// the run as flagged. Admins can look at the flags of a run with
+/// `/admin flags`.
+fn flag(kind: FlagKind, evidence: String, state: &mut GameState) {
+    let tick = state.recording.ticks;
+    let line = format!(
+        "[anti-cheat] {} flagged for {}: {} (pos: {:.2?}, vel: {:.2?}, seed: {}, tick: {})",
+        state.recording.username,
+        kind.name(),
+        evidence,
+        state.test_state.pos,
+        state.test_state.vel,
+        state.seed,
+        tick
+    );
+    println!("{}", line);
+    if let Err(e) = append_to_log(&line) {
+        println!("Failed to write to {}: {}", FLAG_LOG_PATH, e);
+    }
+
+    state.flags.push(Flag {
+        kind,
+        evidence,
+        tick,
+    });
+}
+
+fn append_to_log(line: &str) -> std::io::Result<()> {
+    if let Some(parent) = Path::new(FLAG_LOG_PATH).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(FLAG_LOG_PATH)?;
+    writeln!(file, "{}", line)
+}
+
+fn horizontal_distance(a: DVec3, b: DVec3) -> f64 {
+    DVec2::new(a.x - b.x, a.z - b.z).length()
+}
+
+/// Whether there is a block below or inside the player's feet.
+pub fn is_on_ground(pos: DVec3, layer: &ChunkLayer) -> bool {
+    let is_solid = |x: f64, y: f64, z: f64| {
+        layer
+            .block(BlockPos::new(
+                x.floor() as i32,
+                y.floor() as i32,
+                z.floor() as i32,
+            ))
+            .map_or(false, |b| !b.state.is_air())
+    };
+
+    const HALF_WIDTH: f64 = 0.3;
+
+    if is_solid(pos.x, pos.y, pos.z) {
+        return true;
+    }
+
+    [-HALF_WIDTH, HALF_WIDTH].iter().any(|dx| {
+        [-HALF_WIDTH, HALF_WIDTH]
+            .iter()
+            .any(|dz| is_solid(pos.x + dx, pos.y - 0.05, pos.z + dz))
+    })
+}
+
+/// Applies the configured consequences of a flag.
+fn punish(
+    client: &mut Client,
+    pos: &mut Position,
+    state: &mut GameState,
+    check: &mut MovementCheck,
+    config: &AntiCheatConfig,
+) {
+    client.send_chat_message(
+        "Impossible movement detected. This run won't count for the leaderboard.".color(Color::RED),
+    );
+
+    if config.reset_on_flag {
+        state.pending_restart = Some(random::random_seed());
+    } else if config.setback {
+        let setback = check.last_ground;
+        pos.set(setback);
+        check.teleported(setback);
+    }
+}
+
+/// Checks every player's movement during the last tick using the velocity
+/// computed in `reset_clients`.
+pub fn check_movement(
+    mut clients: Query<(
+        &mut Client,
+        &mut Position,
+        &mut GameState,
+        &mut MovementCheck,
+        &ChunkLayer,
+    )>,
+    config: Res<ServerConfig>,
+) {
+    for (mut client, mut pos, mut state, mut check, layer) in clients.iter_mut() {
+        if let Some((kind, evidence)) = check.check_tick(pos.0, state.test_state.vel, layer) {
+            flag(kind, evidence, &mut state);
+            punish(
+                &mut client,
+                &mut pos,
+                &mut state,
+                &mut check,
+                &config.anti_cheat,
+            );
+        }
+    }
+}
+
+/// Checks every movement packet for step-up and timer hacks.
+pub fn check_movement_packets(
+    mut events: EventReader<MovementEvent>,
+    mut clients: Query<(
+        &mut Client,
+        &mut Position,
+        &mut GameState,
+        &mut MovementCheck,
+        &ChunkLayer,
+    )>,
+    config: Res<ServerConfig>,
+) {
+    for event in events.read() {
+        let Ok((mut client, mut pos, mut state, mut check, layer)) = clients.get_mut(event.client)
+        else {
+            continue;
+        };
+
+        if let Some((kind, evidence)) =
+            check.check_packet(event.position, event.old_position, layer)
+        {
+            flag(kind, evidence, &mut state);
+            punish(
+                &mut client,
+                &mut pos,
+                &mut state,
+                &mut check,
+                &config.anti_cheat,
+            );
+        }
+    }
+}
+
+/// Sends the real block back to players that try to break one, since the
+/// course can't be changed.
+pub fn deny_digging(
+    mut events: EventReader<DiggingEvent>,
+    mut clients: Query<(&mut Client, &ChunkLayer)>,
+) {
+    for event in events.read() {
+        if let Ok((mut client, layer)) = clients.get_mut(event.client) {
+            resend_block(&mut client, layer, event.position);
+        }
+    }
+}
+
+/// Sends the real blocks back to players that try to place one.
+pub fn deny_placing(
+    mut events: EventReader<InteractBlockEvent>,
+    mut clients: Query<(&mut Client, &ChunkLayer)>,
+) {
+    for event in events.read() {
+        if let Ok((mut client, layer)) = clients.get_mut(event.client) {
+            resend_block(&mut client, layer, event.position);
+            resend_block(
+                &mut client,
+                layer,
+                event.position.get_in_direction(event.face),
+            );
+        }
+    }
+}
+
+fn resend_block(client: &mut Client, layer: &ChunkLayer, pos: BlockPos) {
+    let state = layer.block(pos).map_or(BlockState::AIR, |b| b.state);
+
+    client.write_packet(&BlockUpdateS2c {
+        position: pos,
+        block_id: state,
+    });
+}
+
+/// Stops players from flying when their game mode doesn't allow it.
+pub fn deny_flying(
+    mut events: EventReader<PlayerStartFlyingEvent>,
+    mut clients: Query<(&mut PlayerAbilitiesFlags, &mut GameState)>,
+) {
+    for event in events.read() {
+        if let Ok((mut flags, mut state)) = clients.get_mut(event.client) {
+            if !flags.allow_flying() {
+                flags.set_flying(false);
+                flag(
+                    FlagKind::Fly,
+                    "started flying without being allowed to".to_owned(),
+                    &mut state,
+                );
+            }
+        }
+    }
+}
+
+/// Valence already ignores creative inventory actions from players that
+/// aren't in creative mode. They can only come from modified clients, so they
+/// are flagged.
+pub fn deny_creative_actions(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<(&GameMode, &mut GameState)>,
+) {
+    for packet in packets.read() {
+        let Some(action) = packet.decode::<CreativeInventoryActionC2s>() else {
+            continue;
+        };
+
+        if let Ok((game_mode, mut state)) = clients.get_mut(packet.client) {
+            if *game_mode != GameMode::Creative {
+                flag(
+                    FlagKind::Creative,
+                    format!("tried to put an item in slot {}", action.slot),
+                    &mut state,
+                );
+            }
+        }
+    }
+}
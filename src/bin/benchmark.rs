@@ -0,0 +1,278 @@
+//! Times generating paths with the complex presets of the themes, with the
+//! shared tile set and with the way tiles used to be stored, where every
+//! generator had its own clones of the tiles.
+//!
+//! ```text
+//! cargo run --release --bin benchmark -- --theme parkour --iterations 50
+//! ```
+
+use std::{
+    collections::HashMap,
+    env,
+    hint::black_box,
+    process,
+    time::{Duration, Instant},
+};
+
+use mc_parkou_rs::{
+    generation::{
+        generator::GenerationType,
+        generators::{
+            ComplexGenError, ComplexGenerator, ComplexPreset, ComplexTile, Direction, DIRECTIONS,
+        },
+    },
+    themes::Themes,
+};
+use valence::BlockPos;
+
+const USAGE: &str = "Usage: benchmark [options]
+
+Options:
+  --theme <name>             Only benchmark the presets of this theme (default: every theme)
+  --iterations <iterations>  How many paths are generated each way (default: 20)";
+
+const DEFAULT_ITERATIONS: u32 = 20;
+/// How many times a path is searched for before an iteration counts as failed.
+const TRIES: usize = 100;
+
+/// The `Options` struct holds the command line options.
+///
+/// Properties:
+///
+/// * `theme`: The name of the theme, if not every theme.
+/// * `iterations`: How many paths are generated each way.
+struct Options {
+    theme: Option<String>,
+    iterations: u32,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Self {
+            theme: None,
+            iterations: DEFAULT_ITERATIONS,
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing a value for {}", arg))?;
+
+            match arg.as_str() {
+                "--theme" => options.theme = Some(value),
+                "--iterations" => {
+                    options.iterations = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|iterations| *iterations > 0)
+                        .ok_or_else(|| format!("{} has to be a positive number", arg))?
+                }
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// The `ClonedTiles` struct is how a `ComplexGenerator` used to store its
+/// tiles. Every tile was cloned into a list for each of its connections,
+/// the tiles that could follow were cloned whenever they were looked up, and
+/// every placed tile was cloned into the grid.
+///
+/// Properties:
+///
+/// * `tiles_by_connection`: Every tile with a connection, by its direction and
+/// name.
+/// * `tile_grid`: The placed tiles, by their position.
+#[derive(Clone)]
+struct ClonedTiles {
+    tiles_by_connection: HashMap<(Direction, String), Vec<ComplexTile>>,
+    tile_grid: HashMap<BlockPos, ComplexTile>,
+}
+
+impl ClonedTiles {
+    fn new(tiles: &[ComplexTile]) -> Self {
+        let mut tiles_by_connection: HashMap<_, Vec<_>> = HashMap::new();
+        for tile in tiles {
+            for direction in DIRECTIONS {
+                if let Some(connection) = tile.get_next(direction) {
+                    tiles_by_connection
+                        .entry((direction, connection.name.clone()))
+                        .or_default()
+                        .push(tile.clone());
+                }
+            }
+        }
+
+        Self {
+            tiles_by_connection,
+            tile_grid: HashMap::new(),
+        }
+    }
+
+    /// Clones the path of `gen` the way the old generator did while finding
+    /// and placing it. The tiles that could follow are only looked up once
+    /// for every placed tile, so this is less than the old generator cloned.
+    fn store_path(&mut self, gen: &ComplexGenerator) {
+        for (pos, index) in &gen.tile_grid {
+            let tile = &gen.tile_set.tiles[*index];
+            for direction in DIRECTIONS {
+                if let Some(connection) = tile.get_next(direction) {
+                    let key = (direction.get_opposite(), connection.name.clone());
+                    black_box(self.tiles_by_connection.get(&key).cloned());
+                }
+            }
+            self.tile_grid.insert(*pos, tile.clone());
+        }
+    }
+}
+
+/// The `Timing` struct holds how long generating paths took one way.
+///
+/// Properties:
+///
+/// * `elapsed`: How long every iteration took together.
+/// * `failed`: How many iterations didn't find a path.
+#[derive(Default)]
+struct Timing {
+    elapsed: Duration,
+    failed: u32,
+}
+
+impl Timing {
+    /// Times `iteration` `iterations` times. Failed iterations are counted
+    /// and timed like the others.
+    fn measure(
+        iterations: u32,
+        mut iteration: impl FnMut() -> Result<(), ComplexGenError>,
+    ) -> (Self, Option<ComplexGenError>) {
+        let mut timing = Self::default();
+        let mut last_error = None;
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            if let Err(e) = iteration() {
+                timing.failed += 1;
+                last_error = Some(e);
+            }
+        }
+        timing.elapsed = start.elapsed();
+
+        (timing, last_error)
+    }
+
+    fn per_iteration(&self, iterations: u32) -> Duration {
+        self.elapsed / iterations.max(1)
+    }
+}
+
+/// Generates a path the old way. The tile set is rebuilt for every generator,
+/// the tiles are cloned into its lists and the path is cloned into its grid.
+/// The old generator was also cloned for every segment of blocks it placed.
+fn cloned_iteration(preset: &ComplexPreset) -> Result<(), ComplexGenError> {
+    let mut gen = ComplexGenerator::new(
+        preset.tiles.to_vec(),
+        preset.tile_size,
+        preset.min_pos,
+        preset.max_pos,
+    )?
+    .with_length(preset.min_length, preset.max_length)?
+    .with_backend(preset.backend);
+    let mut cloned = ClonedTiles::new(&gen.tile_set.tiles);
+
+    gen.generate_path(TRIES)?;
+    cloned.store_path(&gen);
+
+    for _ in gen.get_block_segments()? {
+        black_box(cloned.clone());
+    }
+    Ok(())
+}
+
+/// Generates a path with the shared tile set of the preset.
+fn shared_iteration(preset: &ComplexPreset) -> Result<(), ComplexGenError> {
+    let mut gen = preset.build()?;
+    gen.generate_path(TRIES)?;
+    gen.get_block_segments()?;
+    Ok(())
+}
+
+fn main() {
+    let options = match Options::parse() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let themes = Themes::default();
+    if let Some(name) = &options.theme {
+        if themes.get(name).is_none() {
+            eprintln!(
+                "Unknown theme {}. Themes: {}",
+                name,
+                themes.names().join(", ")
+            );
+            process::exit(2);
+        }
+    }
+
+    let iterations = options.iterations;
+    let mut presets = 0;
+
+    for theme in &themes.themes {
+        if options
+            .theme
+            .as_ref()
+            .is_some_and(|name| *name != theme.name)
+        {
+            continue;
+        }
+
+        for (i, generation_type) in theme.generation_types.iter().enumerate() {
+            let GenerationType::ComplexCustom(preset) = generation_type else {
+                continue;
+            };
+            presets += 1;
+
+            // The shared tile set is built once, before it is timed.
+            let tiles = match preset.tile_set() {
+                Ok(tile_set) => tile_set.tiles.len(),
+                Err(e) => {
+                    println!("{} #{}: {}", theme.name, i, e);
+                    continue;
+                }
+            };
+
+            let (cloned, cloned_error) = Timing::measure(iterations, || cloned_iteration(preset));
+            let (shared, shared_error) = Timing::measure(iterations, || shared_iteration(preset));
+
+            println!(
+                "{} #{}: {} tiles, {:.2?} cloned ({} failed), {:.2?} shared ({} failed) per path ({:.1}x)",
+                theme.name,
+                i,
+                tiles,
+                cloned.per_iteration(iterations),
+                cloned.failed,
+                shared.per_iteration(iterations),
+                shared.failed,
+                cloned.elapsed.as_secs_f64() / shared.elapsed.as_secs_f64().max(f64::EPSILON),
+            );
+            if let Some(e) = shared_error.or(cloned_error) {
+                println!("  Last failure: {}", e);
+            }
+        }
+    }
+
+    if presets == 0 {
+        println!("No theme has a complex generation.");
+    }
+}
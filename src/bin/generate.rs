@@ -0,0 +1,343 @@
+//! Generates courses without starting the server, so themes can be tried out
+//! quickly. Prints statistics about the courses, and can write them as JSON.
+//!
+//! ```text
+//! cargo run --bin generate -- --theme parkour --seed 42 --count 100
+//! ```
+
+use std::{collections::BTreeMap, env, fs, process, time::Instant};
+
+use mc_parkou_rs::{
+    generation::{
+        course, difficulty::Difficulty, generation::Generation, schematic::SchematicBlock,
+        theme::GenerationTheme,
+    },
+    random,
+    themes::Themes,
+};
+use serde::Serialize;
+use valence::BlockPos;
+
+const USAGE: &str = "Usage: generate [options]
+
+Options:
+  --theme <name>           The theme to generate (default: the first theme)
+  --seed <seed>            The seed of the first course (default: random)
+  --count <generations>    How many generations follow the start (default: 50)
+  --courses <courses>      How many courses to generate, on consecutive seeds (default: 1)
+  --difficulty <difficulty> easy, normal or hard (default: normal)
+  --json <path>            Write the courses as JSON, or to stdout with -";
+
+const DEFAULT_COUNT: u32 = 50;
+
+/// The `Options` struct holds the command line options.
+///
+/// Properties:
+///
+/// * `theme`: The name of the theme, if not the first one.
+/// * `seed`: The seed of the first course, if not random.
+/// * `count`: How many generations follow the start of each course.
+/// * `courses`: How many courses are generated.
+/// * `difficulty`: The difficulty of every course.
+/// * `json`: Where the courses are written as JSON, if anywhere.
+struct Options {
+    theme: Option<String>,
+    seed: Option<u64>,
+    count: u32,
+    courses: u64,
+    difficulty: Difficulty,
+    json: Option<String>,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Self {
+            theme: None,
+            seed: None,
+            count: DEFAULT_COUNT,
+            courses: 1,
+            difficulty: Difficulty::Normal,
+            json: None,
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing a value for {}", arg))?;
+            let number = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("{} has to be a positive number", arg))
+            };
+            let small_number = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("{} has to be a positive number up to {}", arg, u32::MAX))
+            };
+
+            match arg.as_str() {
+                "--theme" => options.theme = Some(value),
+                "--seed" => options.seed = Some(number(&value)?),
+                "--count" => options.count = small_number(&value)?,
+                "--courses" => options.courses = number(&value)?,
+                "--difficulty" => {
+                    options.difficulty = Difficulty::from_name(&value)
+                        .ok_or_else(|| format!("Unknown difficulty {}", value))?
+                }
+                "--json" => options.json = Some(value),
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// The `CourseJson` struct is how a course is written as JSON.
+///
+/// Properties:
+///
+/// * `seed`: The seed the course was generated from.
+/// * `theme`: The name of the theme.
+/// * `difficulty`: The difficulty of the course.
+/// * `generations`: Every generation, starting with the start.
+#[derive(Serialize)]
+struct CourseJson {
+    seed: u64,
+    theme: String,
+    difficulty: Difficulty,
+    generations: Vec<GenerationJson>,
+}
+
+/// The `GenerationJson` struct is how a generation is written as JSON.
+/// Positions are `[x, y, z]` arrays in the world.
+///
+/// Properties:
+///
+/// * `kind`: The name of the generation type.
+/// * `offset`: Where the generation was placed.
+/// * `end`: The block the player jumps off of to the next generation.
+/// * `yaw`: The direction the player jumps to the next generation in.
+/// * `children`: How many child generations there are.
+/// * `collectibles`: How many collectibles there are.
+/// * `teleport`: Where the teleport pad sends the player, if there is one.
+/// * `blocks`: Every block with its block state, like
+/// `minecraft:oak_stairs[facing=north]`.
+#[derive(Serialize)]
+struct GenerationJson {
+    kind: &'static str,
+    offset: [i32; 3],
+    end: [i32; 3],
+    yaw: f32,
+    children: usize,
+    collectibles: usize,
+    teleport: Option<[i32; 3]>,
+    blocks: Vec<([i32; 3], String)>,
+}
+
+impl GenerationJson {
+    fn new(generation: &Generation) -> Self {
+        let pos = |pos: BlockPos| [pos.x, pos.y, pos.z];
+
+        let mut blocks: Vec<_> = generation
+            .world_blocks()
+            .into_iter()
+            .map(|(p, block)| (pos(p), SchematicBlock::from_state(block).to_state_string()))
+            .collect();
+        blocks.sort();
+
+        Self {
+            kind: generation.kind,
+            offset: pos(generation.offset),
+            end: pos(generation.end_state.get_block_pos()),
+            yaw: generation.end_state.yaw,
+            children: generation.children.len(),
+            collectibles: generation.collectibles.len(),
+            teleport: generation.get_teleport_destination().map(pos),
+            blocks,
+        }
+    }
+}
+
+/// The `CourseStats` struct sums up a course.
+///
+/// Properties:
+///
+/// * `generations`: How many generations there are, including the start.
+/// * `blocks`: How many blocks the generations have.
+/// * `min_y`: The lowest block the player jumps off of.
+/// * `max_y`: The highest block the player jumps off of.
+/// * `distance`: How far the end is from the start horizontally.
+/// * `kinds`: How many generations of each type there are.
+struct CourseStats {
+    generations: usize,
+    blocks: usize,
+    min_y: i32,
+    max_y: i32,
+    distance: f64,
+    kinds: BTreeMap<&'static str, usize>,
+}
+
+impl CourseStats {
+    fn new(generations: &[Generation]) -> Self {
+        let ys = generations
+            .iter()
+            .map(|gen| gen.end_state.get_block_pos().y);
+        let start = generations.first().map(|gen| gen.end_state.pos);
+        let end = generations.last().map(|gen| gen.end_state.pos);
+
+        let mut kinds = BTreeMap::new();
+        for gen in generations {
+            *kinds.entry(gen.kind).or_insert(0) += 1;
+        }
+
+        Self {
+            generations: generations.len(),
+            blocks: generations.iter().map(|gen| gen.world_blocks().len()).sum(),
+            min_y: ys.clone().min().unwrap_or(0),
+            max_y: ys.max().unwrap_or(0),
+            distance: match (start, end) {
+                (Some(start), Some(end)) => (end.x - start.x).hypot(end.z - start.z),
+                _ => 0.0,
+            },
+            kinds,
+        }
+    }
+}
+
+/// Prints a line of the statistics. They go to stderr when the JSON is
+/// written to stdout, so the JSON can be piped somewhere else.
+fn report(options: &Options, line: String) {
+    if options.json.as_deref() == Some("-") {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+fn main() {
+    let options = match Options::parse() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let themes = Themes::default();
+    let theme: &GenerationTheme = match &options.theme {
+        Some(name) => match themes.get(name) {
+            Some(theme) => theme,
+            None => {
+                eprintln!(
+                    "Unknown theme {}. Themes: {}",
+                    name,
+                    themes.names().join(", ")
+                );
+                process::exit(2);
+            }
+        },
+        None => themes.default_theme(),
+    };
+
+    let first_seed = options.seed.unwrap_or_else(random::random_seed);
+    report(
+        &options,
+        format!(
+            "Theme {}, difficulty {}, {} generations per course",
+            theme.name,
+            options.difficulty.name(),
+            options.count
+        ),
+    );
+
+    let mut courses = Vec::new();
+    let mut kinds: BTreeMap<&'static str, usize> = BTreeMap::new();
+    let mut total_generations = 0;
+    let started = Instant::now();
+
+    for i in 0..options.courses {
+        let seed = first_seed.wrapping_add(i);
+
+        let start = Instant::now();
+        let generations = course::generate_course(theme, options.difficulty, seed, options.count);
+        let elapsed = start.elapsed();
+
+        let stats = CourseStats::new(&generations);
+        report(
+            &options,
+            format!(
+                "Seed {}: {} generations, {} blocks, y {} to {}, {:.1} blocks from the start, {:.2?}",
+                seed,
+                stats.generations,
+                stats.blocks,
+                stats.min_y,
+                stats.max_y,
+                stats.distance,
+                elapsed
+            ),
+        );
+
+        total_generations += stats.generations;
+        for (kind, count) in stats.kinds {
+            *kinds.entry(kind).or_insert(0) += count;
+        }
+
+        if options.json.is_some() {
+            courses.push(CourseJson {
+                seed,
+                theme: theme.name.clone(),
+                difficulty: options.difficulty,
+                generations: generations.iter().map(GenerationJson::new).collect(),
+            });
+        }
+    }
+
+    let elapsed = started.elapsed();
+    report(
+        &options,
+        format!(
+            "{} generations in {:.2?} ({:.2?} per generation)",
+            total_generations,
+            elapsed,
+            elapsed / total_generations.max(1) as u32
+        ),
+    );
+    report(&options, "Generation types:".to_owned());
+    let mut kinds: Vec<_> = kinds.into_iter().collect();
+    kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    for (kind, count) in kinds {
+        report(
+            &options,
+            format!(
+                "  {:<16} {:>6} {:>5.1}%",
+                kind,
+                count,
+                count as f64 * 100.0 / total_generations.max(1) as f64
+            ),
+        );
+    }
+
+    if let Some(path) = &options.json {
+        let json = match serde_json::to_string_pretty(&courses) {
+            Ok(json) => json,
+            Err(e) => {
+                eprintln!("Failed to write JSON: {}", e);
+                process::exit(1);
+            }
+        };
+
+        if path == "-" {
+            println!("{}", json);
+        } else if let Err(e) = fs::write(path, json) {
+            eprintln!("Failed to write {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+
+use valence::prelude::DVec3;
+
+use crate::{
+    game_state::GameState,
+    generation::{course::CourseState, fork::Fork, generation::Generation},
+};
+
+/// The `Checkpoint` struct represents the course the way it was when a player
+/// reached a checkpoint, so it can be rebuilt when they fall.
+///
+/// Everything the generation depends on is stored so the course continues the
+/// same way after respawning.
+///
+/// Properties:
+///
+/// * `score`: The score the player had at the checkpoint.
+/// * `coins`: How many collectibles the player had picked up at the
+/// checkpoint.
+/// * `generations`: The generations that were placed at the checkpoint. The
+/// first one is the one the checkpoint is on.
+/// * `course`: What the generation after the generations depends on.
+/// * `fork`: The fork after the generations, if there was one.
+/// * `recorded_generations`: How many generations the run's recording had.
+/// * `splits`: How many splits the run's timer had.
+/// * `spawn`: The position and yaw the player set the checkpoint at in
+/// practice mode. If `None`, the player respawns on the first generation.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub score: u32,
+    pub coins: u32,
+    pub generations: VecDeque<Generation>,
+    pub course: CourseState,
+    pub fork: Option<Fork>,
+    pub recorded_generations: usize,
+    pub splits: usize,
+    pub spawn: Option<(DVec3, f32)>,
+}
+
+impl Checkpoint {
+    pub fn new(state: &GameState, score: u32) -> Self {
+        Self {
+            score,
+            coins: state.coins,
+            generations: state.generations.clone(),
+            course: state.course.clone(),
+            fork: state.fork.clone(),
+            recorded_generations: state.recording.generations.len(),
+            splits: state.timer.splits.len(),
+            spawn: None,
+        }
+    }
+}
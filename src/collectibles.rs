@@ -0,0 +1,97 @@
+use valence::{
+    entity::{
+        block_display::{self, BlockDisplayEntityBundle},
+        display,
+    },
+    prelude::*,
+    protocol::sound::{Sound, SoundCategory},
+};
+
+use crate::game_state::GameState;
+
+/// How close the player's body has to be to a collectible to pick it up.
+const PICKUP_RADIUS: f64 = 1.0;
+/// How high above the player's feet their body is.
+const BODY_HEIGHT: f64 = 0.9;
+const SIZE: f32 = 0.3;
+
+/// Spawns the collectibles of the course and lets players pick them up.
+/// Collectibles are spawned on the player's own entity layer, so other players
+/// don't see them.
+pub fn update_collectibles(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &Position, &mut GameState)>,
+) {
+    for (entity, mut client, pos, mut state) in clients.iter_mut() {
+        let state = &mut *state;
+        let body = pos.0 + DVec3::new(0.0, BODY_HEIGHT, 0.0);
+        let mut score = 0;
+
+        for gen in state.generations.iter_mut() {
+            for collectible in gen.collectibles.iter_mut() {
+                if collectible.collected {
+                    continue;
+                }
+
+                let key = collectible.key();
+
+                if collectible.pos.distance(body) < PICKUP_RADIUS {
+                    collectible.collected = true;
+                    score += collectible.score;
+                    // Practice runs don't earn coins.
+                    if !state.practice {
+                        state.coins += 1;
+                    }
+
+                    if let Some(entity) = state.collectible_entities.remove(&key) {
+                        if let Some(mut entity) = commands.get_entity(entity) {
+                            entity.insert(Despawned);
+                        }
+                    }
+
+                    continue;
+                }
+
+                if state.collectible_entities.contains_key(&key) {
+                    continue;
+                }
+
+                let half = SIZE as f64 / 2.0;
+                let display = commands
+                    .spawn(BlockDisplayEntityBundle {
+                        position: Position(collectible.pos - DVec3::splat(half)),
+                        layer: EntityLayerId(entity),
+                        block_display_block_state: block_display::BlockState(
+                            BlockState::GOLD_BLOCK,
+                        ),
+                        display_scale: display::Scale(Vec3::splat(SIZE)),
+                        ..Default::default()
+                    })
+                    .id();
+
+                state.collectible_entities.insert(key, display);
+            }
+        }
+
+        if score == 0 {
+            continue;
+        }
+
+        client.play_sound(
+            Sound::EntityExperienceOrbPickup,
+            SoundCategory::Master,
+            pos.0,
+            1.0,
+            1.0,
+        );
+
+        // Practice runs aren't scored.
+        if state.practice {
+            continue;
+        }
+
+        state.score += score;
+
+        client.set_action_bar(format!("+{} bonus", score).color(Color::GOLD).bold());
+    }
+}
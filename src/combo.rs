@@ -0,0 +1,91 @@
+use valence::prelude::*;
+
+use crate::{config::ComboConfig, game_state::GameState};
+
+/// The `Combo` struct keeps track of how many points a player has gotten in a
+/// row without breaking the combo.
+///
+/// Properties:
+///
+/// * `count`: The points in the current combo.
+/// * `stopped_running`: Whether the player stopped sprinting since the last
+/// point.
+/// * `last_tick`: The tick the last point was gotten at, or the combo was
+/// reset at.
+/// * `remainder`: The fraction of a point left over from multiplying. It is
+/// added to the next points so no score is lost to rounding.
+#[derive(Clone, Debug, Default)]
+pub struct Combo {
+    pub count: u32,
+    pub stopped_running: bool,
+    pub last_tick: usize,
+    pub remainder: f32,
+}
+
+impl Combo {
+    pub fn new(tick: usize) -> Self {
+        Self {
+            last_tick: tick,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the combo has run out of time.
+    pub fn expired(&self, tick: usize, config: &ComboConfig) -> bool {
+        config
+            .window
+            .is_some_and(|window| tick - self.last_tick > window as usize)
+    }
+
+    /// Whether the combo will be reset when the player gets the next point.
+    pub fn broken(&self, tick: usize, config: &ComboConfig) -> bool {
+        (config.require_sprint && self.stopped_running) || self.expired(tick, config)
+    }
+
+    /// How much of the time to get the next point is left, from 0 to 1.
+    pub fn time_left(&self, tick: usize, config: &ComboConfig) -> f32 {
+        match config.window {
+            Some(window) if window > 0 => {
+                1.0 - ((tick - self.last_tick) as f32 / window as f32).min(1.0)
+            }
+            _ => 1.0,
+        }
+    }
+
+    pub fn multiplier(&self, config: &ComboConfig) -> f32 {
+        config.curve.multiplier(self.count)
+    }
+
+    /// Adds points to the combo. The combo is reset first if it was broken.
+    /// Returns the points multiplied by the combo multiplier.
+    pub fn add(&mut self, points: u32, tick: usize, config: &ComboConfig) -> u32 {
+        if self.broken(tick, config) {
+            self.count = 0;
+            self.remainder = 0.0;
+        }
+
+        self.stopped_running = false;
+        self.last_tick = tick;
+        self.count += points;
+
+        let points = points as f32 * self.multiplier(config) + self.remainder;
+        self.remainder = points.fract();
+        points.floor() as u32
+    }
+}
+
+/// Breaks the combo of players that stop sprinting.
+pub fn detect_stop_running(
+    mut events: EventReader<SprintEvent>,
+    mut clients: Query<&mut GameState>,
+) {
+    for event in events.read() {
+        if !matches!(event.state, SprintState::Stop) {
+            continue;
+        }
+
+        if let Ok(mut state) = clients.get_mut(event.client) {
+            state.combo.stopped_running = true;
+        }
+    }
+}
@@ -0,0 +1,721 @@
+use valence::{
+    command::{handler::CommandResultEvent, parsers::GreedyString, CommandRegistry},
+    command_macros::Command,
+    event_loop::PacketEvent,
+    prelude::*,
+    protocol::{
+        packets::play::{
+            command_suggestions_s2c::CommandSuggestionsMatch,
+            command_tree_s2c::{NodeData, Suggestion},
+            CommandSuggestionsS2c, RequestCommandCompletionsC2s,
+        },
+        VarInt, WritePacket,
+    },
+};
+
+use crate::{
+    game_state::GameState,
+    generation::{
+        course, difficulty::Difficulty, fork::Fork, generation::Generation, schematic::Schematic,
+        theme::CourseLength,
+    },
+    leaderboard::Leaderboard,
+    player_data::{self, PlayerData},
+    practice, random,
+    recording::{GhostSource, RunRecording},
+    themes::Themes,
+};
+
+/// Every player has this scope. It grants all `parkour.command.*` commands.
+pub const PLAYER_SCOPE: &str = "parkour.command";
+/// Players listed as admins in the config have this scope. It grants all
+/// `parkour.admin.*` commands and everything players can use.
+pub const ADMIN_SCOPE: &str = "parkour.admin";
+
+const TOP_COUNT: usize = 10;
+/// The directory exported courses are saved in.
+const EXPORTS_DIR: &str = "exports";
+/// How many generations are exported from a seed when no length is given
+/// and the theme's courses are infinite.
+const EXPORT_LENGTH: u32 = 50;
+/// The most generations that can be exported from a seed. Generating them
+/// blocks the server.
+const MAX_EXPORT_LENGTH: u32 = 500;
+/// What `/theme` is typed as, up to where the name of the theme starts.
+const THEME_PREFIX: &str = "/theme ";
+
+#[derive(Command, Debug, Clone)]
+#[paths("reset", "r")]
+#[scopes("parkour.command.reset")]
+pub struct ResetCommand;
+
+#[derive(Command, Debug, Clone)]
+#[paths("theme {theme?}")]
+#[scopes("parkour.command.theme")]
+pub struct ThemeCommand {
+    theme: Option<String>,
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("seed {seed?} {difficulty?}")]
+#[scopes("parkour.command.seed")]
+pub struct SeedCommand {
+    seed: Option<String>,
+    difficulty: Option<String>,
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("difficulty")]
+#[scopes("parkour.command.difficulty")]
+pub enum DifficultyCommand {
+    #[paths("easy")]
+    Easy,
+    #[paths("normal")]
+    Normal,
+    #[paths("hard")]
+    Hard,
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("lines")]
+#[scopes("parkour.command.lines")]
+pub enum LinesCommand {
+    #[paths("on")]
+    On,
+    #[paths("off")]
+    Off,
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("stats {player?}")]
+#[scopes("parkour.command.stats")]
+pub struct StatsCommand {
+    player: Option<String>,
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("top")]
+#[scopes("parkour.command.top")]
+pub struct TopCommand;
+
+#[derive(Command, Debug, Clone)]
+#[paths("ghost")]
+#[scopes("parkour.command.ghost")]
+pub enum GhostCommand {
+    #[paths("pb")]
+    PersonalBest,
+    #[paths("top")]
+    Leaderboard,
+    #[paths("off")]
+    Off,
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("practice")]
+#[scopes("parkour.command.practice")]
+pub enum PracticeCommand {
+    #[paths("on")]
+    On,
+    #[paths("off")]
+    Off,
+    #[paths("checkpoint")]
+    Checkpoint,
+    #[paths("regenerate")]
+    Regenerate,
+    /// Only generates the given generation type. `any` goes back to the
+    /// theme's usual generation types.
+    #[paths("type {kind}")]
+    Type { kind: String },
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("admin")]
+#[scopes("parkour.admin.command")]
+pub enum AdminCommand {
+    /// Ends a player's run and starts a new one.
+    #[paths("reset {player}")]
+    Reset { player: String },
+    #[paths("clearleaderboard")]
+    ClearLeaderboard,
+    #[paths("broadcast {message}")]
+    Broadcast { message: GreedyString },
+    /// Shows why the anti-cheat flagged a player's current run.
+    #[paths("flags {player?}")]
+    Flags { player: Option<String> },
+    /// Saves the generations a player currently has as a schematic.
+    #[paths("export course {player?}")]
+    ExportCourse { player: Option<String> },
+    /// Generates the course of a seed with the executor's theme and
+    /// difficulty and saves it as a schematic.
+    #[paths("export seed {seed} {length?}")]
+    ExportSeed { seed: String, length: Option<u32> },
+}
+
+fn highlight(text: impl Into<String>) -> Text {
+    let text: String = text.into();
+    text.color(Color::GOLD).bold().not_italic()
+}
+
+fn error(text: impl Into<String>) -> Text {
+    let text: String = text.into();
+    text.color(Color::RED).italic()
+}
+
+pub fn handle_reset_command(
+    mut events: EventReader<CommandResultEvent<ResetCommand>>,
+    mut clients: Query<(&mut Client, &mut GameState)>,
+) {
+    for event in events.read() {
+        if let Ok((mut client, mut state)) = clients.get_mut(event.executor) {
+            state.pending_restart = Some(random::random_seed());
+            client.send_chat_message("Starting a new run.".italic());
+        }
+    }
+}
+
+pub fn handle_theme_command(
+    mut events: EventReader<CommandResultEvent<ThemeCommand>>,
+    mut clients: Query<(&mut Client, &mut GameState)>,
+    themes: Res<Themes>,
+) {
+    for event in events.read() {
+        let Ok((mut client, mut state)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        let Some(name) = &event.result.theme else {
+            client.send_chat_message(
+                "Current theme: ".italic()
+                    + highlight(state.theme.name.clone())
+                    + ". Available: ".italic()
+                    + themes.names().join(", ").not_italic(),
+            );
+            continue;
+        };
+
+        match themes.get(name) {
+            Some(theme) => {
+                state.theme = theme.clone();
+                state.pending_restart = Some(random::random_seed());
+                client.send_chat_message("Switched to theme ".italic() + highlight(name.clone()));
+            }
+            None => client.send_chat_message(error(format!(
+                "Unknown theme. Available: {}",
+                themes.names().join(", ")
+            ))),
+        }
+    }
+}
+
+/// Makes clients ask the server what to suggest for the theme of `/theme`,
+/// since the themes are only known once the server runs.
+pub fn suggest_theme_names(mut registry: ResMut<CommandRegistry>) {
+    for node in registry.graph.graph.node_weights_mut() {
+        if let NodeData::Argument {
+            name, suggestion, ..
+        } = &mut node.data
+        {
+            if name == "theme" {
+                *suggestion = Some(Suggestion::AskServer);
+            }
+        }
+    }
+}
+
+/// Suggests the names of the loaded themes that start with what the player
+/// typed after `/theme`.
+pub fn complete_theme_names(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<&mut Client>,
+    themes: Res<Themes>,
+) {
+    for packet in packets.read() {
+        let Some(request) = packet.decode::<RequestCommandCompletionsC2s>() else {
+            continue;
+        };
+        let Some(typed) = request.text.strip_prefix(THEME_PREFIX) else {
+            continue;
+        };
+        let Ok(mut client) = clients.get_mut(packet.client) else {
+            continue;
+        };
+
+        let matches = themes
+            .names()
+            .into_iter()
+            .filter(|name| name.starts_with(typed))
+            .map(|name| CommandSuggestionsMatch {
+                suggested_match: name,
+                tooltip: None,
+            })
+            .collect();
+
+        client.write_packet(&CommandSuggestionsS2c {
+            id: request.transaction_id,
+            start: VarInt(THEME_PREFIX.len() as i32),
+            length: VarInt(typed.len() as i32),
+            matches,
+        });
+    }
+}
+
+pub fn handle_seed_command(
+    mut events: EventReader<CommandResultEvent<SeedCommand>>,
+    mut clients: Query<(&mut Client, &mut GameState)>,
+) {
+    for event in events.read() {
+        let Ok((mut client, mut state)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        let Some(seed) = &event.result.seed else {
+            client.send_chat_message(
+                "Current seed: ".italic()
+                    + highlight(format!("{} {}", state.seed, state.difficulty.name())),
+            );
+            continue;
+        };
+
+        let Ok(seed) = seed.parse::<u64>() else {
+            client.send_chat_message(error("The seed has to be a positive number."));
+            continue;
+        };
+
+        // The same seed generates a different course on another difficulty,
+        // so a shared seed can come with the difficulty it was played on.
+        if let Some(difficulty) = &event.result.difficulty {
+            match Difficulty::from_name(difficulty) {
+                Some(difficulty) => state.difficulty = difficulty,
+                None => {
+                    client
+                        .send_chat_message(error("The difficulty has to be easy, normal or hard."));
+                    continue;
+                }
+            }
+        }
+
+        state.pending_restart = Some(seed);
+        client.send_chat_message(
+            "Starting a new run on seed ".italic()
+                + highlight(format!("{} {}", seed, state.difficulty.name())),
+        );
+    }
+}
+
+pub fn handle_difficulty_command(
+    mut events: EventReader<CommandResultEvent<DifficultyCommand>>,
+    mut clients: Query<(&mut Client, &mut GameState)>,
+) {
+    for event in events.read() {
+        if let Ok((mut client, mut state)) = clients.get_mut(event.executor) {
+            state.difficulty = match event.result {
+                DifficultyCommand::Easy => Difficulty::Easy,
+                DifficultyCommand::Normal => Difficulty::Normal,
+                DifficultyCommand::Hard => Difficulty::Hard,
+            };
+            state.pending_restart = Some(random::random_seed());
+            client.send_chat_message(
+                "Difficulty set to ".italic() + highlight(state.difficulty.name()),
+            );
+        }
+    }
+}
+
+pub fn handle_lines_command(
+    mut events: EventReader<CommandResultEvent<LinesCommand>>,
+    mut clients: Query<(&mut Client, &mut GameState)>,
+) {
+    for event in events.read() {
+        if let Ok((mut client, mut state)) = clients.get_mut(event.executor) {
+            state.show_lines = matches!(event.result, LinesCommand::On);
+            let text = if state.show_lines {
+                "Jump lines are now shown."
+            } else {
+                "Jump lines are now hidden."
+            };
+            client.send_chat_message(text.italic());
+        }
+    }
+}
+
+pub fn handle_stats_command(
+    mut events: EventReader<CommandResultEvent<StatsCommand>>,
+    mut clients: Query<(&mut Client, &GameState)>,
+) {
+    for event in events.read() {
+        let data = match &event.result.player {
+            Some(player) if !player_data::is_valid_username(player) => {
+                if let Ok((mut client, _)) = clients.get_mut(event.executor) {
+                    client.send_chat_message(error(format!("{} isn't a valid username.", player)));
+                }
+                continue;
+            }
+            Some(player) => PlayerData::load(player),
+            None => match clients.get(event.executor) {
+                Ok((_, state)) => state.player_data.clone(),
+                Err(_) => continue,
+            },
+        };
+
+        let Ok((mut client, _)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        if data.runs == 0 {
+            client.send_chat_message(error(format!(
+                "{} hasn't finished a run yet.",
+                data.username
+            )));
+            continue;
+        }
+
+        let minutes = data.play_ticks / 20 / 60;
+
+        client.send_chat_message("Stats of ".italic() + highlight(data.username.clone()));
+        client.send_chat_message("Runs: ".italic() + highlight(data.runs.to_string()));
+        client.send_chat_message("Best score: ".italic() + highlight(data.best_score.to_string()));
+        client.send_chat_message(
+            "Average score: ".italic()
+                + highlight(format!("{:.1}", data.total_score as f64 / data.runs as f64)),
+        );
+        client.send_chat_message(
+            "Time played: ".italic() + highlight(format!("{}h {}m", minutes / 60, minutes % 60)),
+        );
+
+        client.send_chat_message("Coins: ".italic() + highlight(data.coins.to_string()));
+
+        if !data.unlocks.is_empty() {
+            client.send_chat_message("Unlocks: ".italic() + highlight(data.unlocks.join(", ")));
+        }
+    }
+}
+
+/// Shows the leaderboard of the difficulty the player is playing on.
+pub fn handle_top_command(
+    mut events: EventReader<CommandResultEvent<TopCommand>>,
+    mut clients: Query<(&mut Client, &GameState)>,
+    leaderboard: Res<Leaderboard>,
+) {
+    for event in events.read() {
+        let Ok((mut client, state)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        let top = leaderboard.top(TOP_COUNT, state.difficulty);
+
+        if top.is_empty() {
+            client.send_chat_message(error(format!(
+                "Nobody has finished a run on {} yet.",
+                state.difficulty.name()
+            )));
+            continue;
+        }
+
+        client.send_chat_message(
+            format!("Leaderboard ({})", state.difficulty.name())
+                .color(Color::GOLD)
+                .bold(),
+        );
+
+        for (i, entry) in top.iter().enumerate() {
+            client.send_chat_message(
+                format!("{}. ", i + 1).color(Color::GRAY)
+                    + entry.username.clone().color(Color::WHITE)
+                    + " - ".color(Color::GRAY)
+                    + highlight(entry.score.to_string())
+                    + format!(" ({}, seed {})", entry.theme, entry.seed)
+                        .color(Color::GRAY)
+                        .italic(),
+            );
+        }
+    }
+}
+
+/// Starts a race against a ghost of the difficulty the player is playing on.
+/// The new run uses the seed, theme and difficulty of the ghost's run so both
+/// are on the same course.
+pub fn handle_ghost_command(
+    mut commands: Commands,
+    mut events: EventReader<CommandResultEvent<GhostCommand>>,
+    mut clients: Query<(&mut Client, &Username, &mut GameState)>,
+    leaderboard: Res<Leaderboard>,
+    themes: Res<Themes>,
+) {
+    for event in events.read() {
+        let Ok((mut client, username, mut state)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        let (source, entry) = match event.result {
+            GhostCommand::PersonalBest => (
+                GhostSource::PersonalBest,
+                leaderboard.best_for_player(&username.0, state.difficulty),
+            ),
+            GhostCommand::Leaderboard => {
+                (GhostSource::Leaderboard, leaderboard.best(state.difficulty))
+            }
+            GhostCommand::Off => {
+                match state.ghost.take() {
+                    Some(ghost) => {
+                        ghost.despawn(&mut commands);
+                        client.send_chat_message("Ghost removed.".italic());
+                    }
+                    None => client.send_chat_message(error("You aren't racing a ghost.")),
+                }
+                continue;
+            }
+        };
+
+        let Some(recording) =
+            entry.and_then(|e| RunRecording::load(&e.theme, e.seed, e.difficulty, &e.username))
+        else {
+            client.send_chat_message(error("There is no run to race against yet."));
+            continue;
+        };
+
+        // The course can only be generated again with the theme it was run on.
+        let Some(theme) = themes.get(&recording.theme) else {
+            client.send_chat_message(error(format!(
+                "The theme {} of that run isn't loaded anymore.",
+                recording.theme
+            )));
+            continue;
+        };
+
+        client.send_chat_message(
+            "Racing against a ghost with a score of ".italic()
+                + highlight(recording.score.to_string()),
+        );
+        state.theme = theme.clone();
+        state.difficulty = recording.difficulty;
+        state.pending_ghost = Some((source, recording));
+    }
+}
+
+pub fn handle_practice_command(
+    mut commands: Commands,
+    mut events: EventReader<CommandResultEvent<PracticeCommand>>,
+    mut clients: Query<(
+        &mut Client,
+        &mut Inventory,
+        &Position,
+        &Look,
+        &mut GameState,
+        &mut ChunkLayer,
+    )>,
+    themes: Res<Themes>,
+) {
+    for event in events.read() {
+        let Ok((mut client, mut inventory, pos, look, mut state, mut layer)) =
+            clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+
+        match &event.result {
+            PracticeCommand::On => {
+                state.practice = true;
+                state.pending_restart = Some(random::random_seed());
+                practice::give_items(&mut inventory);
+                client.send_chat_message("Practice mode on. Your runs won't be scored.".italic());
+                client.send_chat_message(
+                    "Use the lime dye to set a checkpoint and the clock to regenerate the course."
+                        .italic(),
+                );
+                continue;
+            }
+            PracticeCommand::Off => {
+                state.practice = false;
+                state.pending_restart = Some(random::random_seed());
+                if let Some(theme) = themes.get(&state.theme.name) {
+                    state.theme = theme.clone();
+                }
+                practice::take_items(&mut inventory);
+                client.send_chat_message("Practice mode off.".italic());
+                continue;
+            }
+            _ => {}
+        }
+
+        if !state.practice {
+            client.send_chat_message(error("You have to be in practice mode. Use /practice on."));
+            continue;
+        }
+
+        match &event.result {
+            PracticeCommand::Checkpoint => {
+                practice::set_checkpoint(&mut state, &mut client, pos.0, look.yaw, &layer);
+            }
+            PracticeCommand::Regenerate => {
+                practice::regenerate(&mut state, &mut client, &mut layer, &mut commands);
+            }
+            PracticeCommand::Type { kind } => {
+                let Some(theme) = themes.get(&state.theme.name) else {
+                    continue;
+                };
+
+                if kind == "any" {
+                    state.theme = theme.clone();
+                } else if let Some(only) = theme.only(kind) {
+                    state.theme = only;
+                } else {
+                    let mut kinds: Vec<&str> =
+                        theme.generation_types.iter().map(|t| t.name()).collect();
+                    kinds.sort_unstable();
+                    kinds.dedup();
+                    client.send_chat_message(error(format!(
+                        "Unknown generation type. Available: any, {}",
+                        kinds.join(", ")
+                    )));
+                    continue;
+                }
+
+                client.send_chat_message("Now practicing ".italic() + highlight(kind.clone()));
+                practice::regenerate(&mut state, &mut client, &mut layer, &mut commands);
+            }
+            PracticeCommand::On | PracticeCommand::Off => {}
+        }
+    }
+}
+
+pub fn handle_admin_command(
+    mut events: EventReader<CommandResultEvent<AdminCommand>>,
+    mut clients: Query<(&mut Client, &Username, &mut GameState)>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    for event in events.read() {
+        let feedback: Text = match &event.result {
+            AdminCommand::Reset { player } => {
+                match clients
+                    .iter_mut()
+                    .find(|(_, username, _)| username.0 == *player)
+                {
+                    Some((mut client, _, mut state)) => {
+                        state.pending_restart = Some(random::random_seed());
+                        client.send_chat_message("Your run was reset by an admin.".italic());
+                        "Reset the run of ".italic() + highlight(player.clone())
+                    }
+                    None => error(format!("{} isn't online.", player)),
+                }
+            }
+            AdminCommand::ClearLeaderboard => {
+                leaderboard.entries.clear();
+                match leaderboard.save() {
+                    Ok(()) => "Cleared the leaderboard.".italic(),
+                    Err(e) => error(format!("Failed to save leaderboard: {}", e)),
+                }
+            }
+            AdminCommand::Broadcast { message } => {
+                for (mut client, _, _) in clients.iter_mut() {
+                    client.send_chat_message(
+                        "[Broadcast] ".color(Color::GOLD).bold() + message.0.clone().not_bold(),
+                    );
+                }
+                continue;
+            }
+            AdminCommand::Flags { player } => {
+                let target = match player {
+                    Some(player) => clients
+                        .iter()
+                        .find(|(_, username, _)| username.0 == *player),
+                    None => clients.get(event.executor).ok(),
+                };
+                let lines: Vec<Text> = match target {
+                    Some((_, username, state)) if state.flags.is_empty() => {
+                        vec![
+                            "The current run of ".italic()
+                                + highlight(username.0.clone())
+                                + " hasn't been flagged.".italic(),
+                        ]
+                    }
+                    Some((_, username, state)) => {
+                        let mut lines = vec![
+                            "Flags of ".italic()
+                                + highlight(username.0.clone())
+                                + format!(" on seed {}", state.seed).italic(),
+                        ];
+                        for flag in &state.flags {
+                            lines.push(
+                                format!("Tick {}: ", flag.tick).color(Color::GRAY)
+                                    + highlight(flag.kind.name())
+                                    + format!(" - {}", flag.evidence).color(Color::GRAY),
+                            );
+                        }
+                        lines
+                    }
+                    None => vec![error("That player isn't online.")],
+                };
+
+                if let Ok((mut client, _, _)) = clients.get_mut(event.executor) {
+                    for line in lines {
+                        client.send_chat_message(line);
+                    }
+                }
+                continue;
+            }
+            AdminCommand::ExportCourse { player } => {
+                let target = match player {
+                    Some(player) => clients
+                        .iter()
+                        .find(|(_, username, _)| username.0 == *player),
+                    None => clients.get(event.executor).ok(),
+                };
+
+                match target {
+                    Some((_, username, state)) => {
+                        let fork = state.fork.iter().flat_map(Fork::generations);
+                        export_course(
+                            state.generations.iter().chain(fork),
+                            &format!("{}-{}", username.0, state.seed),
+                        )
+                    }
+                    None => error("That player isn't online."),
+                }
+            }
+            AdminCommand::ExportSeed { seed, length } => {
+                let Ok(seed) = seed.parse::<u64>() else {
+                    if let Ok((mut client, _, _)) = clients.get_mut(event.executor) {
+                        client.send_chat_message(error("The seed has to be a positive number."));
+                    }
+                    continue;
+                };
+                let Ok((_, _, state)) = clients.get(event.executor) else {
+                    continue;
+                };
+
+                // Finite courses are exported up to and including the finish.
+                let length = length
+                    .or(match state.theme.length {
+                        Some(CourseLength::Segments(segments)) => Some(segments + 1),
+                        _ => None,
+                    })
+                    .unwrap_or(EXPORT_LENGTH)
+                    .min(MAX_EXPORT_LENGTH);
+                let generations =
+                    course::generate_course(&state.theme, state.difficulty, seed, length);
+
+                export_course(
+                    &generations,
+                    &format!("seed-{}-{}-{}", seed, state.theme.name, length),
+                )
+            }
+        };
+
+        if let Ok((mut client, _, _)) = clients.get_mut(event.executor) {
+            client.send_chat_message(feedback);
+        }
+    }
+}
+
+/// Saves the blocks of the generations as a schematic named `name` in
+/// `EXPORTS_DIR`. Returns the message for the player who exported it.
+fn export_course<'a>(generations: impl IntoIterator<Item = &'a Generation>, name: &str) -> Text {
+    let path = format!("{}/{}.schem", EXPORTS_DIR, name);
+    let schematic =
+        Schematic::from_world_blocks(generations.into_iter().flat_map(Generation::world_blocks));
+
+    match schematic.save(&path) {
+        Ok(()) => "Saved the course to ".italic() + highlight(path),
+        Err(e) => error(format!("Failed to save {}: {}", path, e)),
+    }
+}
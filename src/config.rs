@@ -0,0 +1,371 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use valence::prelude::*;
+
+pub const CONFIG_PATH: &str = "config.json";
+
+/// The `ServerConfig` resource holds the settings read from `config.json`.
+/// Any missing field falls back to its default.
+///
+/// Properties:
+///
+/// * `admins`: The usernames of players that can use admin commands.
+/// * `anti_cheat`: What happens when a player is flagged for impossible
+/// movement.
+/// * `failure`: What happens when a player falls off the course.
+/// * `hud`: What is shown on the player's screen.
+/// * `combo`: How combos are kept and how they affect the score.
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub admins: Vec<String>,
+    pub anti_cheat: AntiCheatConfig,
+    pub failure: FailureModel,
+    pub hud: HudConfig,
+    pub combo: ComboConfig,
+}
+
+/// Properties:
+///
+/// * `setback`: Whether flagged players are put back to where they last stood
+/// on a block.
+/// * `reset_on_flag`: Whether flagged players have their run reset.
+///
+/// Flagged runs are never submitted to the leaderboard.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AntiCheatConfig {
+    pub setback: bool,
+    pub reset_on_flag: bool,
+}
+
+impl Default for AntiCheatConfig {
+    fn default() -> Self {
+        Self {
+            setback: true,
+            reset_on_flag: false,
+        }
+    }
+}
+
+/// Properties:
+///
+/// * `sidebar`: Whether the sidebar with the player's score is shown.
+/// * `boss_bar`: Whether the boss bar with the progress to the next milestone
+/// is shown.
+/// * `combo_bar`: Whether the boss bar with the player's combo is shown.
+/// * `score_every_point`: Whether the score is shown as a subtitle every time
+/// the player gets a point, instead of only at milestones.
+/// * `milestones`: The scores that are celebrated.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudConfig {
+    pub sidebar: bool,
+    pub boss_bar: bool,
+    pub combo_bar: bool,
+    pub score_every_point: bool,
+    pub milestones: Milestones,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        Self {
+            sidebar: true,
+            boss_bar: true,
+            combo_bar: true,
+            score_every_point: false,
+            milestones: Milestones::default(),
+        }
+    }
+}
+
+/// The milestones players are celebrated for reaching.
+///
+/// Properties:
+///
+/// * `table`: The milestones, in ascending order of score.
+/// * `repeat`: After the last milestone of `table`, this milestone is reached
+/// every `repeat.score` points.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Milestones {
+    pub table: Vec<Milestone>,
+    pub repeat: Option<Milestone>,
+}
+
+impl Default for Milestones {
+    fn default() -> Self {
+        let mut table: Vec<Milestone> = [10, 20, 30, 40, 75]
+            .into_iter()
+            .map(Milestone::new)
+            .collect();
+
+        table.push(Milestone {
+            sound: Some(MilestoneSound::LevelUp),
+            particles: Some(MilestoneParticles::Firework),
+            broadcast: Some("{player} reached {score} points!".to_owned()),
+            ..Milestone::new(100)
+        });
+
+        Self {
+            table,
+            repeat: Some(Milestone {
+                sound: Some(MilestoneSound::Pling),
+                ..Milestone::new(50)
+            }),
+        }
+    }
+}
+
+impl Milestones {
+    fn last_score(&self) -> u32 {
+        self.table.last().map_or(0, |m| m.score)
+    }
+
+    fn repeat_every(&self) -> Option<u32> {
+        self.repeat.as_ref().map(|m| m.score).filter(|s| *s > 0)
+    }
+
+    /// Gets the milestone at a score, if there is one. Repeated milestones
+    /// have their score set to the score they are reached at.
+    pub fn get(&self, score: u32) -> Option<Milestone> {
+        if let Some(milestone) = self.table.iter().find(|m| m.score == score) {
+            return Some(milestone.clone());
+        }
+
+        let last = self.last_score();
+        let every = self.repeat_every()?;
+
+        (score > last && (score - last) % every == 0).then(|| Milestone {
+            score,
+            ..self.repeat.clone().unwrap()
+        })
+    }
+
+    /// Gets the score of the first milestone above the score.
+    pub fn next(&self, score: u32) -> Option<u32> {
+        if let Some(milestone) = self.table.iter().find(|m| m.score > score) {
+            return Some(milestone.score);
+        }
+
+        let every = self.repeat_every()?;
+        let last = self.last_score();
+        Some(last + ((score - last) / every + 1) * every)
+    }
+
+    /// Gets the score of the last milestone at or below the score, or 0 if
+    /// there is none.
+    pub fn previous(&self, score: u32) -> u32 {
+        let last = self.last_score();
+
+        match self.repeat_every() {
+            Some(every) if score >= last => last + (score - last) / every * every,
+            _ => self
+                .table
+                .iter()
+                .rev()
+                .find(|m| m.score <= score)
+                .map_or(0, |m| m.score),
+        }
+    }
+
+    /// Gets the milestones at or below the score, in ascending order.
+    pub fn reached(&self, score: u32) -> Vec<Milestone> {
+        let mut milestones = Vec::new();
+        let mut next = self.next(0);
+
+        while let Some(s) = next.filter(|s| *s <= score) {
+            milestones.extend(self.get(s));
+            next = self.next(s);
+        }
+
+        milestones
+    }
+}
+
+/// The `Milestone` struct represents a score that is celebrated when a player
+/// reaches it. In all of the messages, `{score}` is replaced with the score
+/// and `{player}` with the player's name.
+///
+/// Properties:
+///
+/// * `score`: The score the milestone is reached at.
+/// * `title`: The title shown to the player.
+/// * `subtitle`: The subtitle shown to the player.
+/// * `sound`: The sound played to the player.
+/// * `particles`: The particles shown around the player.
+/// * `broadcast`: A message sent to every player.
+/// * `unlock`: A cosmetic the player unlocks. Unlocks are kept between
+/// sessions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Milestone {
+    pub score: u32,
+    pub title: String,
+    pub subtitle: String,
+    pub sound: Option<MilestoneSound>,
+    pub particles: Option<MilestoneParticles>,
+    pub broadcast: Option<String>,
+    pub unlock: Option<String>,
+}
+
+impl Default for Milestone {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Milestone {
+    /// Creates a milestone that only shows the score as a subtitle.
+    pub fn new(score: u32) -> Self {
+        Self {
+            score,
+            title: String::new(),
+            subtitle: "{score}".to_owned(),
+            sound: None,
+            particles: None,
+            broadcast: None,
+            unlock: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MilestoneSound {
+    LevelUp,
+    ChallengeComplete,
+    Firework,
+    Bell,
+    Pling,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MilestoneParticles {
+    Firework,
+    Totem,
+    HappyVillager,
+    Flame,
+}
+
+/// Properties:
+///
+/// * `require_sprint`: Whether the combo is broken when the player stops
+/// sprinting.
+/// * `window`: How many ticks the player has to get the next point in before
+/// the combo is broken. If `None`, there is no time limit.
+/// * `curve`: How the combo multiplies the points the player gets.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComboConfig {
+    pub require_sprint: bool,
+    pub window: Option<u32>,
+    pub curve: ComboCurve,
+}
+
+impl Default for ComboConfig {
+    fn default() -> Self {
+        Self {
+            require_sprint: true,
+            window: None,
+            curve: ComboCurve::default(),
+        }
+    }
+}
+
+/// How the combo multiplies the points the player gets.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ComboCurve {
+    /// The combo doesn't affect the score.
+    Flat,
+    /// Every point of combo adds `per_point` to the multiplier, up to `max`.
+    Linear { per_point: f32, max: f32 },
+    /// Every `every` points of combo add `bonus` to the multiplier, up to
+    /// `max`.
+    Steps { every: u32, bonus: f32, max: f32 },
+}
+
+impl Default for ComboCurve {
+    fn default() -> Self {
+        ComboCurve::Steps {
+            every: 10,
+            bonus: 0.25,
+            max: 2.0,
+        }
+    }
+}
+
+impl ComboCurve {
+    pub fn multiplier(&self, combo: u32) -> f32 {
+        match self {
+            ComboCurve::Flat => 1.0,
+            ComboCurve::Linear { per_point, max } => {
+                (1.0 + combo.saturating_sub(1) as f32 * per_point).min(*max)
+            }
+            ComboCurve::Steps { every, bonus, max } if *every > 0 => {
+                (1.0 + (combo / every) as f32 * bonus).min(*max)
+            }
+            ComboCurve::Steps { .. } => 1.0,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Loads the config from disk. Returns the default config if it doesn't
+    /// exist or can't be parsed.
+    pub fn load() -> Self {
+        match fs::read_to_string(CONFIG_PATH) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                println!("Failed to parse {}: {}", CONFIG_PATH, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.admins.iter().any(|a| a == username)
+    }
+}
+
+/// What happens when a player falls off the course.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FailureModel {
+    /// The run ends as soon as the player falls.
+    Instant,
+    /// The player is put back on the last generation they reached until they
+    /// run out of lives.
+    LastGeneration { lives: u32 },
+    /// A checkpoint is placed every `every` points. The player is put back on
+    /// the last one until they run out of lives.
+    Checkpoints { every: u32, lives: u32 },
+}
+
+impl Default for FailureModel {
+    fn default() -> Self {
+        FailureModel::LastGeneration { lives: 3 }
+    }
+}
+
+impl FailureModel {
+    /// How many times a player can fall before their run ends.
+    pub fn lives(&self) -> u32 {
+        match self {
+            FailureModel::Instant => 0,
+            FailureModel::LastGeneration { lives } => *lives,
+            FailureModel::Checkpoints { lives, .. } => *lives,
+        }
+    }
+
+    /// How many points apart checkpoints are placed, if they are.
+    pub fn checkpoint_interval(&self) -> Option<u32> {
+        match self {
+            FailureModel::Checkpoints { every, .. } if *every > 0 => Some(*every),
+            _ => None,
+        }
+    }
+}
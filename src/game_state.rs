@@ -4,26 +4,73 @@ use valence::prelude::*;
 
 use crate::{
     alt_block::AltBlockState,
-    generation::{generation::Generation, theme::GenerationTheme},
+    anti_cheat::Flag,
+    checkpoint::Checkpoint,
+    combo::Combo,
+    generation::{
+        course::CourseState, difficulty::Difficulty, fork::Fork, generation::Generation,
+        theme::GenerationTheme,
+    },
     line::Line3,
+    player_data::PlayerData,
     prediction::prediction_state::PredictionState,
-    utils::*,
+    recording::{Ghost, GhostSource, RunRecording},
+    timer::RunTimer,
 };
 
 #[derive(Component)]
 pub struct GameState {
     pub generations: VecDeque<Generation>,
-    pub target_y: i32,
-    pub direction: JumpDirection,
+    /// A fork after the last generation. No more generations are generated
+    /// until the player picks one of its branches.
+    pub fork: Option<Fork>,
+    /// Everything the generation of the current course depends on besides
+    /// the theme and difficulty.
+    pub course: CourseState,
     pub theme: GenerationTheme,
     pub score: u32,
-    pub combo: u32,
-    pub stopped_running: bool,
+    /// How many collectibles the player has picked up during the current run.
+    /// They are added to the player's coins when the run ends, so coins
+    /// collected again after respawning at a checkpoint only count once.
+    pub coins: u32,
+    pub combo: Combo,
     pub tick: usize,
     pub alt_block_entities: HashMap<BlockPos, Entity>,
     pub prev_alt_block_states: HashMap<BlockPos, AltBlockState>,
+    /// The entities of the collectibles on the course, keyed by the block
+    /// they are in.
+    pub collectible_entities: HashMap<BlockPos, Entity>,
     pub prev_pos: DVec3,
     pub test_state: PredictionState,
     pub line_entities: HashMap<Line3, Entity>,
     pub lines: HashSet<Line3>,
+    /// The seed the current course was generated from.
+    pub seed: u64,
+    pub recording: RunRecording,
+    pub ghost: Option<Ghost>,
+    /// A ghost to race against. The current run is ended and a new one is
+    /// started on the ghost's seed.
+    pub pending_ghost: Option<(GhostSource, RunRecording)>,
+    /// A seed to restart on. The current run is ended and a new one is started
+    /// on this seed.
+    pub pending_restart: Option<u64>,
+    pub difficulty: Difficulty,
+    /// Whether the jump lines are shown to the player.
+    pub show_lines: bool,
+    pub player_data: PlayerData,
+    /// Every time the anti-cheat flagged the player during the current run.
+    /// Flagged runs aren't saved or submitted to the leaderboard.
+    pub flags: Vec<Flag>,
+    /// How many more times the player can fall before the run ends.
+    pub lives: u32,
+    pub checkpoint: Option<Checkpoint>,
+    /// Whether the player is in practice mode. Practice runs aren't scored and
+    /// the player never runs out of lives.
+    pub practice: bool,
+    pub timer: RunTimer,
+    /// The velocity to give the player after they are teleported. It is sent
+    /// on the tick after the teleport, since teleporting resets it.
+    pub pending_velocity: Option<DVec3>,
+    /// The scores of the milestones celebrated during the current run.
+    pub milestones: HashSet<u32>,
 }
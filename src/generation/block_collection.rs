@@ -63,7 +63,10 @@ impl BlockCollectionMap {
 
     pub fn build(self) -> BuiltBlockCollectionMap {
         let mut collections = HashMap::new();
-        for (name, collection) in self.collections {
+        // Sorted so that a seeded RNG picks the same blocks every time.
+        let mut sorted = self.collections.into_iter().collect::<Vec<_>>();
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (name, collection) in sorted {
             let index = if collection.0.uniform {
                 collection
                     .0
@@ -119,7 +122,9 @@ impl BuiltBlockCollectionMap {
     /// Rebuids the `BuiltBlockCollectionMap`
     pub fn rebuild(&self) -> BuiltBlockCollectionMap {
         let mut collections = HashMap::new();
-        for (name, (collection, _)) in &self.collections {
+        let mut sorted = self.collections.iter().collect::<Vec<_>>();
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (name, (collection, _)) in sorted {
             let i = collection.0.blocks.get_random_index().unwrap();
             collections.insert(name.to_owned(), (collection.to_owned(), i));
         }
@@ -62,6 +62,16 @@ impl BlockGrid {
         Self { blocks }
     }
 
+    /// The blocks ordered by y, then z, then x. Picking random blocks from a
+    /// collection has to happen in this order, since the order of `blocks`
+    /// changes every time the server starts and a seed has to give the same
+    /// blocks.
+    pub fn sorted_blocks(&self) -> Vec<(&BlockPos, &BlockProperties)> {
+        let mut blocks: Vec<_> = self.blocks.iter().collect();
+        blocks.sort_by_key(|(pos, _)| (pos.y, pos.z, pos.x));
+        blocks
+    }
+
     /// Rotate the grid clockwise along the Y axis around a given point.
     pub fn rotate_cw(&self, origin: BlockPos) -> Self {
         let mut blocks = HashMap::new();
@@ -75,6 +85,21 @@ impl BlockGrid {
         Self { blocks }
     }
 
+    /// Moves every block with `pos` and changes it with `block`.
+    pub fn transform(
+        &self,
+        pos: impl Fn(BlockPos) -> BlockPos,
+        block: impl Fn(&BlockProperties) -> BlockProperties,
+    ) -> Self {
+        Self {
+            blocks: self
+                .blocks
+                .iter()
+                .map(|(p, b)| (pos(*p), block(b)))
+                .collect(),
+        }
+    }
+
     /// Flip the grid along the X axis around a given point.
     pub fn flip_x(&self, origin: BlockPos) -> Self {
         let mut blocks = HashMap::new();
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use valence::prelude::*;
+
+use crate::{random, utils::JumpDirection};
+
+use super::{
+    difficulty::Difficulty,
+    fork::Fork,
+    generation::Generation,
+    generator::Generator,
+    theme::{CourseLength, GenerationTheme},
+};
+
+/// Where every course starts.
+pub const START_POS: BlockPos = BlockPos::new(0, 100, 0);
+/// How far above or below the base height the course can go before it is
+/// steered back.
+const DIFF: i32 = 10;
+
+/// Picks the direction of the next generation. The course is steered back
+/// towards `base_y` when it gets too far from it, until it reaches
+/// `target_y`.
+///
+/// # Parameters
+///
+/// * `direction`: The direction of the last generation.
+/// * `target_y`: The height the course is being steered towards.
+/// * `base_y`: The height the course stays around.
+/// * `y`: The height of the end of the last generation.
+pub fn steer(direction: JumpDirection, target_y: &mut i32, base_y: i32, y: i32) -> JumpDirection {
+    if y < base_y - DIFF {
+        *target_y = base_y;
+        JumpDirection::Up
+    } else if y > base_y + DIFF {
+        *target_y = base_y;
+        JumpDirection::Down
+    } else {
+        match direction {
+            JumpDirection::Up if y >= *target_y => JumpDirection::DoesntMatter,
+            JumpDirection::Down if y <= *target_y => JumpDirection::DoesntMatter,
+            direction => direction,
+        }
+    }
+}
+
+/// The `CourseState` struct represents everything the generation of a course
+/// depends on besides its theme and difficulty. Runs keep it in their
+/// `GameState` and checkpoints clone it, so the course continues the same way
+/// after respawning.
+///
+/// Properties:
+///
+/// * `rng`: The RNG the course is generated with. Seeded with the course's
+/// seed.
+/// * `direction`: The direction of the last generation.
+/// * `target_y`: The height the course is being steered towards.
+/// * `base_y`: The height the course stays around. Changed by teleport pads.
+/// * `finish_at`: On courses that are `CourseLength::Points` long, how many
+/// generations follow the start before the finish. Set once the player has
+/// enough points.
+#[derive(Clone)]
+pub struct CourseState {
+    pub rng: StdRng,
+    pub direction: JumpDirection,
+    pub target_y: i32,
+    pub base_y: i32,
+    pub finish_at: Option<u32>,
+}
+
+impl CourseState {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: random::seeded_rng(seed),
+            direction: JumpDirection::DoesntMatter,
+            target_y: 0,
+            base_y: START_POS.y,
+            finish_at: None,
+        }
+    }
+
+    /// How many generations follow the start before the finish, if it is
+    /// known yet.
+    pub fn end(&self, theme: &GenerationTheme) -> Option<u32> {
+        match theme.length? {
+            CourseLength::Segments(segments) => Some(segments),
+            CourseLength::Points(_) => self.finish_at,
+        }
+    }
+
+    /// Generates the start of the course, which is the first generation.
+    pub fn first(&mut self, theme: &GenerationTheme) -> Generation {
+        random::with_rng(&mut self.rng, || {
+            Generator::first_in_generation(START_POS, theme)
+        })
+    }
+}
+
+/// Adds at most `count` generations to the end of `generations`, fewer if the
+/// theme's course ends sooner. `generated` is how many generations already
+/// follow the start, which can be more than `generations` holds if the ones
+/// the player passed were removed. `on_generation` is called with every new
+/// generation before it is added, so it can be placed.
+///
+/// Stops at a fork and returns it. No more generations are added until one of
+/// its branches is added to `generations`.
+pub fn extend_course(
+    course: &mut CourseState,
+    theme: &GenerationTheme,
+    difficulty: Difficulty,
+    generations: &mut VecDeque<Generation>,
+    generated: u32,
+    count: u32,
+    mut on_generation: impl FnMut(&Generation),
+) -> Option<Fork> {
+    for generated in generated..generated + count {
+        let end = course.end(theme);
+        if end.is_some_and(|end| generated > end) {
+            break;
+        }
+        let finish = end == Some(generated);
+
+        let prev_gen = generations.back()?;
+        course.direction = steer(
+            course.direction,
+            &mut course.target_y,
+            course.base_y,
+            prev_gen.end_state.get_block_pos().y,
+        );
+
+        let finish_theme;
+        let theme = if finish {
+            finish_theme = theme.finish();
+            &finish_theme
+        } else {
+            theme
+        };
+
+        // Forks would make finite courses longer than they should be.
+        if !finish && theme.length.is_none() && theme.fork_chance > 0.0 {
+            let fork = random::with_rng(&mut course.rng, || {
+                Fork::try_generate(course.direction, theme, prev_gen)
+            });
+
+            if fork.is_some() {
+                return fork;
+            }
+        }
+
+        let next_gen = random::with_rng(&mut course.rng, || {
+            Generator::next_in_generation(course.direction, difficulty, theme, prev_gen)
+        });
+
+        if let Some(destination) = next_gen.get_teleport_destination() {
+            course.base_y = destination.y;
+            course.target_y = destination.y;
+            course.direction = JumpDirection::DoesntMatter;
+        }
+
+        on_generation(&next_gen);
+        generations.push_back(next_gen);
+    }
+
+    None
+}
+
+/// Generates the course a run on `seed` gets, without a server. The first
+/// generation is the start. At most `length` generations follow it, fewer if
+/// the theme's course ends sooner. Courses that are `CourseLength::Points`
+/// long never end, since nobody scores points on them. The course takes the
+/// safe branch of every fork, and a fork counts as the generations of that
+/// branch.
+pub fn generate_course(
+    theme: &GenerationTheme,
+    difficulty: Difficulty,
+    seed: u64,
+    length: u32,
+) -> Vec<Generation> {
+    let mut course = CourseState::new(seed);
+    let mut generations = VecDeque::from([course.first(theme)]);
+
+    // The first generation is the start, which isn't part of the course.
+    while let Some(remaining) = (length + 1).checked_sub(generations.len() as u32) {
+        let generated = generations.len() as u32 - 1;
+        let fork = extend_course(
+            &mut course,
+            theme,
+            difficulty,
+            &mut generations,
+            generated,
+            remaining,
+            |_| {},
+        );
+
+        match fork {
+            Some(fork) => generations.extend(fork.safe),
+            None => break,
+        }
+    }
+
+    generations.into()
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+/// How hard the generated course is.
+///
+/// Harder difficulties make longer jumps more likely and pick generation
+/// types that need timing more often.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Finds the difficulty with the given name, like `hard`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    /// Added to the height the next generation is placed at. Landing higher
+    /// makes a jump shorter, landing lower makes it longer.
+    pub fn get_y_offset(self) -> i32 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Normal => 0,
+            Difficulty::Hard => -1,
+        }
+    }
+
+    /// What the weight of generation types that need timing is multiplied by.
+    pub fn timed_weight(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.25,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 2.0,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use rand::Rng;
+use valence::prelude::*;
+
+use crate::{prediction::prediction_state::PredictionState, random, utils::*};
+
+use super::{
+    difficulty::Difficulty, generation::Generation, generator::Generator, theme::GenerationTheme,
+};
+
+/// How many degrees to each side of the path the branches go.
+const FORK_ANGLE: f32 = 40.0;
+/// How many generations the safe branch has.
+const SAFE_LENGTH: usize = 3;
+/// How many generations the hard branch has.
+const HARD_LENGTH: usize = 1;
+/// The bonus points for taking the hard branch.
+pub const HARD_BONUS: u32 = 3;
+
+/// The two branches of a fork.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Branch {
+    Safe,
+    Hard,
+}
+
+impl Branch {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Branch::Safe => "Safe route",
+            Branch::Hard => "Hard route",
+        }
+    }
+}
+
+/// The `Fork` struct represents a point where the course splits into two
+/// branches. The course continues from whichever branch the player lands on,
+/// and the other one is removed.
+///
+/// Properties:
+///
+/// * `safe`: A long branch of easy generations.
+/// * `hard`: A short branch of hard generations that gives bonus points.
+#[derive(Clone, Debug)]
+pub struct Fork {
+    pub safe: Vec<Generation>,
+    pub hard: Vec<Generation>,
+}
+
+impl Fork {
+    /// Generates a fork after the generation with the theme's fork chance.
+    pub fn try_generate(
+        direction: JumpDirection,
+        theme: &GenerationTheme,
+        generation: &Generation,
+    ) -> Option<Self> {
+        random::rng()
+            .gen_bool(theme.fork_chance.clamp(0.0, 1.0))
+            .then(|| Self::generate(direction, theme, generation))
+    }
+
+    /// Generates a fork after the generation. The branches go off to either
+    /// side of the direction the player would jump in.
+    pub fn generate(
+        direction: JumpDirection,
+        theme: &GenerationTheme,
+        generation: &Generation,
+    ) -> Self {
+        let from = &generation.end_state;
+        let yaw = FORK_ANGLE.to_radians();
+        // Branches can't have teleport pads. The course after a fork goes on
+        // from the end of a branch, so it wouldn't follow a pad's destination.
+        let theme = &theme.without_teleports();
+
+        // Which side each branch is on is random, so the player can't tell
+        // them apart by that.
+        let side = if random::rng().gen_bool(0.5) {
+            1.0
+        } else {
+            -1.0
+        };
+
+        let safe = Self::branch(
+            direction,
+            Difficulty::Easy,
+            theme,
+            PredictionState::running_jump_vec(from.pos, from.yaw + side * yaw),
+            SAFE_LENGTH,
+        );
+        let hard = Self::branch(
+            direction,
+            Difficulty::Hard,
+            theme,
+            PredictionState::running_jump_vec(from.pos, from.yaw - side * yaw),
+            HARD_LENGTH,
+        );
+
+        Self { safe, hard }
+    }
+
+    fn branch(
+        direction: JumpDirection,
+        difficulty: Difficulty,
+        theme: &GenerationTheme,
+        mut from: PredictionState,
+        length: usize,
+    ) -> Vec<Generation> {
+        let mut generations = Vec::new();
+
+        for _ in 0..length {
+            let generation = Generator::from_state(direction, difficulty, theme, &from);
+            from = generation.end_state.clone();
+            generations.push(generation);
+        }
+
+        generations
+    }
+
+    pub fn branch_generations(&self, branch: Branch) -> &Vec<Generation> {
+        match branch {
+            Branch::Safe => &self.safe,
+            Branch::Hard => &self.hard,
+        }
+    }
+
+    /// Gets the branch the player has landed on, if any.
+    pub fn reached(&self, pos: Position) -> Option<Branch> {
+        [Branch::Safe, Branch::Hard].into_iter().find(|branch| {
+            self.branch_generations(*branch)
+                .iter()
+                .any(|gen| gen.has_reached(pos))
+        })
+    }
+
+    /// The first generation of each branch. These are the ones the player can
+    /// jump to.
+    pub fn first_generations(&self) -> impl Iterator<Item = &Generation> {
+        self.safe.first().into_iter().chain(self.hard.first())
+    }
+
+    pub fn generations(&self) -> impl Iterator<Item = &Generation> {
+        self.safe.iter().chain(self.hard.iter())
+    }
+}
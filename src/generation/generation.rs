@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
-use valence::{layer::chunk::IntoBlock, prelude::*};
+use valence::prelude::*;
 
 use crate::{alt_block::*, line::Line3, prediction::prediction_state::PredictionState, utils::*};
 
@@ -8,6 +8,8 @@ use crate::{alt_block::*, line::Line3, prediction::prediction_state::PredictionS
 ///
 /// Properties:
 ///
+/// * `kind`: The `kind` property is of type `&'static str`. It is the name of the
+/// `GenerationType` that generated this.
 /// * `blocks`: The `blocks` property is of type `HashMap<BlockPos, BlockState>`. It represents
 /// blocks that are generated.
 /// * `children`: The `children` property is of type `Vec<ChildGeneration>`. It represents
@@ -25,8 +27,13 @@ use crate::{alt_block::*, line::Line3, prediction::prediction_state::PredictionS
 /// the state to expect the player to be in at the end of the parkour generation.
 /// * `lines`: The `lines` property is of type `Vec<Line3>`. It represents the path the
 /// player takes through the parkour generation.
+/// * `collectibles`: The `collectibles` property is of type `Vec<Collectible>`. It
+/// represents the collectibles placed off the path on the way to the generation.
+/// * `teleport`: The `teleport` property is of type `Option<Teleport>`. It represents
+/// the teleport pad of the generation, if it has one.
 #[derive(Clone, Debug)]
 pub struct Generation {
+    pub kind: &'static str,
     pub blocks: HashMap<BlockPos, BlockState>,
     pub children: Vec<ChildGeneration>,
     pub alt_blocks: HashMap<BlockPos, AltBlock>,
@@ -34,88 +41,52 @@ pub struct Generation {
     pub offset: BlockPos,
     pub end_state: PredictionState,
     pub lines: Vec<Line3>,
+    pub collectibles: Vec<Collectible>,
+    pub teleport: Option<Teleport>,
 }
 
 impl Generation {
-    /// Places the blocks in the generation.
-    pub fn place(&self, world: &mut ChunkLayer) {
-        for (pos, block) in &self.blocks {
-            world.set_block(*pos + self.offset.as_ivec3(), *block);
-        }
-
-        for child in &self.children {
-            child.place(world, self.offset);
-        }
+    /// Gets the lowest point of the generation. This includes its blocks, its
+    /// children and the path the player is expected to take through it.
+    pub fn get_lowest_y(&self) -> f64 {
+        let block_ys = self
+            .blocks
+            .keys()
+            .chain(self.alt_blocks.keys())
+            .chain(self.children.iter().flat_map(|c| {
+                c.blocks
+                    .keys()
+                    .chain(c.alt_blocks.keys())
+                    .chain(c.check_blocks.iter())
+            }))
+            .map(|pos| (pos.y + self.offset.y) as f64);
+
+        let line_ys = self
+            .lines
+            .iter()
+            .flat_map(|line| [line.start.y as f64, line.end.y as f64]);
+
+        block_ys
+            .chain(line_ys)
+            .fold(self.end_state.pos.y - 1.0, f64::min)
     }
 
-    /// Removes the blocks in the generation.
-    pub fn remove(
-        &self,
-        world: &mut ChunkLayer,
-        alt_block_entities: &mut HashMap<BlockPos, Entity>,
-        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
-        commands: &mut Commands,
-    ) {
-        for (pos, _) in &self.blocks {
-            world.set_block(*pos + self.offset.as_ivec3(), BlockState::AIR.into_block());
-        }
-
-        for (pos, _) in &self.alt_blocks {
-            let pos = *pos + self.offset.as_ivec3();
-            if let Some(entity) = alt_block_entities.get_mut(&pos) {
-                if let Some(mut entity) = commands.get_entity(*entity) {
-                    entity.insert(Despawned);
-                }
-
-                alt_block_entities.remove(&pos);
-                prev_alt_block_states.remove(&pos);
-            }
-        }
-
-        for child in &self.children {
-            child.remove(
-                world,
-                alt_block_entities,
-                prev_alt_block_states,
-                commands,
-                self.offset,
-            );
-        }
-    }
-
-    /// Updates the alt blocks in the generation.
-    pub fn update_alt_blocks(
-        &self,
-        params: &AltBlockParams,
-        alt_block_entities: &mut HashMap<BlockPos, Entity>,
-        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
-        commands: &mut Commands,
-        world: &mut ChunkLayer,
-        layer: &EntityLayerId,
-    ) {
-        for (pos, block) in &self.alt_blocks {
-            let block = block.get_block(params);
-            block.set_block(
-                *pos + self.offset.as_ivec3(),
-                alt_block_entities,
-                prev_alt_block_states,
-                commands,
-                world,
-                layer,
-            )
-        }
-
-        for child in &self.children {
-            child.update_alt_blocks(
-                params,
-                alt_block_entities,
-                prev_alt_block_states,
-                commands,
-                world,
-                layer,
-                self.offset,
-            );
-        }
+    /// Gets every block of the generation and its children where they are in
+    /// the world. Alt blocks are given in their first state.
+    pub fn world_blocks(&self) -> Vec<(BlockPos, BlockState)> {
+        let alt_blocks = self
+            .alt_blocks
+            .iter()
+            .chain(self.children.iter().flat_map(|c| c.alt_blocks.iter()))
+            .filter_map(|(pos, block)| block.first_block().map(|block| (*pos, block)));
+
+        self.blocks
+            .iter()
+            .chain(self.children.iter().flat_map(|c| c.blocks.iter()))
+            .map(|(pos, block)| (*pos, *block))
+            .chain(alt_blocks)
+            .map(|(pos, block)| (pos + self.offset.as_ivec3(), block))
+            .collect()
     }
 
     /// Returns true if the player has reached any of the blocks.
@@ -171,6 +142,24 @@ impl Generation {
         }
     }
 
+    /// Gets how far the player has to be moved if they are standing on the
+    /// generation's teleport pad.
+    pub fn get_teleport(&self, pos: DVec3) -> Option<DVec3> {
+        let teleport = self.teleport?;
+        let floor = get_player_floor_blocks(pos - self.offset.to_vec3().as_dvec3());
+
+        floor
+            .contains(&teleport.pad)
+            .then(|| (teleport.destination.to_vec3() - teleport.pad.to_vec3()).as_dvec3())
+    }
+
+    /// Gets the block the generation's teleport pad sends the player to, if it
+    /// has one.
+    pub fn get_teleport_destination(&self) -> Option<BlockPos> {
+        self.teleport
+            .map(|teleport| teleport.destination + self.offset.as_ivec3())
+    }
+
     /// Returns the number of child generations that have been not been reached.
     pub fn get_unreached_child_count(&self) -> u32 {
         if self.ordered {
@@ -188,6 +177,56 @@ impl Generation {
     }
 }
 
+/// The `Teleport` struct represents a pad that teleports the player to another
+/// part of a generation.
+///
+/// Properties:
+///
+/// * `pad`: The block the player has to stand on to be teleported. Relative to
+/// the generation's offset.
+/// * `destination`: The block the player is teleported onto. Relative to the
+/// generation's offset.
+#[derive(Clone, Copy, Debug)]
+pub struct Teleport {
+    pub pad: BlockPos,
+    pub destination: BlockPos,
+}
+
+/// The `Collectible` struct represents a coin off the path that gives bonus
+/// points when the player picks it up.
+///
+/// Properties:
+///
+/// * `pos`: The position of the collectible. Unlike blocks, it isn't relative
+/// to the generation's offset.
+/// * `score`: The bonus points the collectible gives.
+/// * `collected`: Whether the player has picked the collectible up.
+#[derive(Clone, Debug)]
+pub struct Collectible {
+    pub pos: DVec3,
+    pub score: u32,
+    pub collected: bool,
+}
+
+impl Collectible {
+    pub fn new(pos: DVec3, score: u32) -> Self {
+        Self {
+            pos,
+            score,
+            collected: false,
+        }
+    }
+
+    /// The block the collectible is in. Used to keep track of its entity.
+    pub fn key(&self) -> BlockPos {
+        BlockPos::new(
+            self.pos.x.floor() as i32,
+            self.pos.y.floor() as i32,
+            self.pos.z.floor() as i32,
+        )
+    }
+}
+
 /// The `ChildGeneration` struct represents a child generation.
 ///
 /// Properties:
@@ -230,63 +269,6 @@ impl ChildGeneration {
         }
     }
 
-    /// Places the blocks in the generation.
-    pub fn place(&self, world: &mut ChunkLayer, offset: BlockPos) {
-        for (pos, block) in &self.blocks {
-            world.set_block(*pos + offset.as_ivec3(), *block);
-        }
-    }
-
-    /// Removes the blocks in the generation.
-    pub fn remove(
-        &self,
-        world: &mut ChunkLayer,
-        alt_block_entities: &mut HashMap<BlockPos, Entity>,
-        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
-        commands: &mut Commands,
-        offset: BlockPos,
-    ) {
-        for (pos, _) in &self.blocks {
-            world.set_block(*pos + offset.as_ivec3(), BlockState::AIR.into_block());
-        }
-
-        for (pos, _) in &self.alt_blocks {
-            let pos = *pos + offset.as_ivec3();
-            if let Some(entity) = alt_block_entities.get_mut(&pos) {
-                if let Some(mut entity) = commands.get_entity(*entity) {
-                    entity.insert(Despawned);
-                }
-
-                alt_block_entities.remove(&pos);
-                prev_alt_block_states.remove(&pos);
-            }
-        }
-    }
-
-    /// Updates the alt blocks in the generation.
-    pub fn update_alt_blocks(
-        &self,
-        params: &AltBlockParams,
-        alt_block_entities: &mut HashMap<BlockPos, Entity>,
-        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
-        commands: &mut Commands,
-        world: &mut ChunkLayer,
-        layer: &EntityLayerId,
-        offset: BlockPos,
-    ) {
-        for (pos, block) in &self.alt_blocks {
-            let block = block.get_block(params);
-            block.set_block(
-                *pos + offset.as_ivec3(),
-                alt_block_entities,
-                prev_alt_block_states,
-                commands,
-                world,
-                layer,
-            )
-        }
-    }
-
     /// Returns true if the player has reached any of the blocks.
     /// If so, the child generation will be marked as reached.
     pub fn has_reached(&mut self, pos: Position, offset: BlockPos) -> bool {
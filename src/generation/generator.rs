@@ -1,8 +1,13 @@
 use std::collections::HashMap;
 
-use crate::{alt_block::*, line::Line3, prediction::prediction_state::PredictionState, utils::*};
-
-use super::{block_collection::*, generation::*, generators::*, theme::GenerationTheme};
+use crate::{
+    alt_block::*, line::Line3, prediction::prediction_state::PredictionState, random, utils::*,
+};
+
+use super::{
+    block_collection::*, difficulty::Difficulty, generation::*, generators::*,
+    theme::GenerationTheme,
+};
 use rand::Rng;
 use valence::{math::IVec2, prelude::*};
 
@@ -32,6 +37,28 @@ impl GenerateResult {
     }
 }
 
+/// How far the finish platform reaches from its center.
+const FINISH_RADIUS: i32 = 2;
+/// How many degrees to the side of the path collectibles are placed at, at
+/// least and at most.
+const COLLECTIBLE_MIN_ANGLE: f32 = 25.0;
+const COLLECTIBLE_MAX_ANGLE: f32 = 45.0;
+/// The bonus points a collectible gives.
+const COLLECTIBLE_SCORE: u32 = 2;
+/// How far ahead teleport pads send the player.
+const TELEPORT_DISTANCE: f32 = 64.0;
+/// How far above or below the start height teleport pads send the player.
+const TELEPORT_HEIGHT: i32 = 24;
+/// How far from the start the course can get before teleport pads send the
+/// player back to it.
+const TELEPORT_RESET_DISTANCE: i32 = 256;
+/// How far from the start teleport pads that send the player back to it can
+/// be.
+const TELEPORT_SPREAD: i32 = 16;
+/// How many times a complex generation tries to find a path before the
+/// fallback is used.
+const COMPLEX_TRIES: usize = 100;
+
 /// The `GenerationType` enum represents the different types of parkour generations
 /// that can be used.
 ///
@@ -57,6 +84,8 @@ impl GenerateResult {
 /// * `ComplexCustom`: The `ComplexCustom` variant represents a custom parkour
 /// generation that is generated using a DFS algorithm. It produces a tile-based
 /// generation.
+/// * `Finish`: The `Finish` variant represents the platform at the end of a
+/// finite course.
 #[derive(Clone, Debug)]
 pub enum GenerationType {
     Single(String),
@@ -91,7 +120,44 @@ pub enum GenerationType {
     },
     SingleCustom(SingleCustomPreset),
     MultiCustom(MultiCustomPreset),
-    ComplexCustom(ComplexGenerator), // TODO: Make `ComplexPreset` instead and construct `ComplexGenerator` from it for better consistency
+    /// A path of tiles. A new `ComplexGenerator` is built from the preset for
+    /// every generation.
+    ComplexCustom(ComplexPreset),
+    Finish(String),
+    /// A pad that teleports the player to a new area. The course continues
+    /// from a pad there.
+    Teleport(String),
+}
+
+impl GenerationType {
+    /// Returns a short name for the type of generation. This is what gets
+    /// stored in recordings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GenerationType::Single(_) => "single",
+            GenerationType::Ramp(_) => "ramp",
+            GenerationType::Island { .. } => "island",
+            GenerationType::Indoor { .. } => "indoor",
+            GenerationType::Cave(_) => "cave",
+            GenerationType::Snake(_) => "snake",
+            GenerationType::BlinkBlocks { .. } => "blink_blocks",
+            GenerationType::SingleCustom(_) => "single_custom",
+            GenerationType::MultiCustom(_) => "multi_custom",
+            GenerationType::ComplexCustom(_) => "complex_custom",
+            GenerationType::Finish(_) => "finish",
+            GenerationType::Teleport(_) => "teleport",
+        }
+    }
+
+    /// Whether the generation needs good timing to get through.
+    pub fn is_timed(&self) -> bool {
+        matches!(
+            self,
+            GenerationType::Snake(_)
+                | GenerationType::BlinkBlocks { .. }
+                | GenerationType::ComplexCustom(_)
+        )
+    }
 }
 
 /// The `Generator` struct represents a parkour generator.
@@ -132,14 +198,34 @@ impl Generator {
 
     pub fn next_in_generation(
         direction: JumpDirection,
+        difficulty: Difficulty,
         theme: &GenerationTheme,
         generation: &Generation,
+    ) -> Generation {
+        let chance = theme.collectible_chance;
+        let mut next = Self::from_state(direction, difficulty, theme, &generation.end_state);
+
+        if chance > 0.0 && random::rng().gen_bool(chance.min(1.0)) {
+            next.collectibles
+                .extend(Self::collectible(&generation.end_state, &next));
+        }
+
+        next
+    }
+
+    /// Generates the generation the player lands on when jumping from `from`.
+    pub fn from_state(
+        direction: JumpDirection,
+        difficulty: Difficulty,
+        theme: &GenerationTheme,
+        from: &PredictionState,
     ) -> Generation {
         let theme = theme.clone();
-        let mut state = generation.end_state.clone();
+        let mut state = from.clone();
         let mut lines = Vec::new();
 
-        let target_y = (state.pos.y as i32 + direction.get_y_offset()) as f64;
+        let target_y =
+            (state.pos.y as i32 + direction.get_y_offset() + difficulty.get_y_offset()) as f64;
 
         let g = loop {
             let mut new_state = state.clone();
@@ -150,14 +236,29 @@ impl Generator {
                 state = new_state;
             } else {
                 break Self {
-                    generation_type: theme.get_random_generation_type(),
+                    generation_type: theme.get_random_generation_type(difficulty),
                     theme,
                     start: state.get_block_pos(),
                 };
             }
         };
 
-        g.generate(direction, generation.end_state.yaw, lines)
+        g.generate(direction, from.yaw, lines)
+    }
+
+    /// Creates a copy of the generator that generates the theme's fallback
+    /// type instead.
+    fn with_fallback(&self) -> Self {
+        let generation_type = match &self.theme.fallback {
+            // A fallback that can fail as well could fall back forever.
+            GenerationType::ComplexCustom(_) => GenerationType::Single("jump_block".to_owned()),
+            fallback => fallback.clone(),
+        };
+
+        Self {
+            generation_type,
+            ..self.clone()
+        }
     }
 
     pub fn generate(
@@ -171,6 +272,7 @@ impl Generator {
         let mut offset: BlockPos = self.start;
         let mut children = Vec::new();
         let mut ordered = true;
+        let mut teleport = None;
         let end_state: PredictionState;
 
         let params = BlockGenParams {
@@ -184,6 +286,33 @@ impl Generator {
 
                 end_state = PredictionState::running_jump_block(self.start, random_yaw());
             }
+            GenerationType::Finish(key) => {
+                for x in -FINISH_RADIUS..=FINISH_RADIUS {
+                    for z in -FINISH_RADIUS..=FINISH_RADIUS {
+                        blocks.insert(BlockPos::new(x, 0, z), params.block_map.get_block(key));
+                    }
+                }
+
+                // Nothing is generated after the finish.
+                end_state = PredictionState::running_jump_block(self.start, yaw);
+            }
+            GenerationType::Teleport(key) => {
+                let destination = teleport_destination(self.start, yaw);
+                let relative = BlockPos::new(
+                    destination.x - self.start.x,
+                    destination.y - self.start.y,
+                    destination.z - self.start.z,
+                );
+
+                blocks.insert(BlockPos::new(0, 0, 0), params.block_map.get_block(key));
+                blocks.insert(relative, params.block_map.get_block(key));
+
+                teleport = Some(Teleport {
+                    pad: BlockPos::new(0, 0, 0),
+                    destination: relative,
+                });
+                end_state = PredictionState::running_jump_block(destination, random_yaw());
+            }
             GenerationType::Ramp(key) => {
                 let new_yaw = random_yaw();
 
@@ -344,7 +473,7 @@ impl Generator {
             }
             GenerationType::Snake(block_name) => {
                 // TODO: Add more options
-                let mut rng = rand::thread_rng();
+                let mut rng = random::rng();
                 let mut snake = SnakeGenerator {
                     block_name: block_name.to_owned(),
                     snake_count: 1,
@@ -459,37 +588,30 @@ impl Generator {
                     lines.push(line + offset.to_vec3());
                 }
             }
-            GenerationType::ComplexCustom(gen) => {
-                let mut gen = gen.clone();
-
-                let mut tries = 0;
-
-                let end = loop {
-                    if let Some(t) = gen.generate_dfs()
-                    {
-                        break t;
-                    }
-                    tries += 1;
-
-                    if tries > 100 {
-                        panic!("Failed to generate complex custom generation. Tried 100 times.");
+            GenerationType::ComplexCustom(preset) => {
+                let (gen, exit) = match preset.generate(&params, COMPLEX_TRIES) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        // The course can't stop here, so the error is logged
+                        // and the fallback is generated instead.
+                        eprintln!(
+                            "Complex generation of theme {} failed, using the fallback: {}",
+                            self.theme.name, e
+                        );
+                        return self.with_fallback().generate(direction, yaw, lines);
                     }
-
-                    println!("Failed to generate complex custom generation. Retrying...");
                 };
 
-                let end = BlockPos::new(
-                    end.x * gen.tile_size.x,
-                    end.y * gen.tile_size.y,
-                    (end.z + 1) * gen.tile_size.z - 1,
-                );
-
-                let gen = gen.generate(&params);
-
                 offset = offset - gen.start.as_ivec3();
                 blocks = gen.blocks;
                 children = gen.children;
-                end_state = PredictionState::running_jump_block(offset + end.as_ivec3(), random_yaw_dist(30.));
+                // The next generation continues the way the path leaves.
+                end_state = PredictionState::running_jump_block(
+                    offset + exit.pos.as_ivec3(),
+                    exit.direction
+                        .to_yaw()
+                        .unwrap_or_else(|| random_yaw_dist(30.)),
+                );
 
                 for line in gen.lines {
                     lines.push(line + offset.to_vec3());
@@ -498,6 +620,7 @@ impl Generator {
         }
 
         Generation {
+            kind: self.generation_type.name(),
             blocks,
             children,
             alt_blocks,
@@ -505,8 +628,68 @@ impl Generator {
             offset,
             end_state,
             lines,
+            collectibles: Vec::new(),
+            teleport,
         }
     }
+
+    /// Tries to place a collectible to the side of the jump from `from` to
+    /// `generation`. It is placed on the path of a jump in a different
+    /// direction, so it can be reached but makes the jump riskier. Returns
+    /// `None` if the spot is inside the generation.
+    fn collectible(from: &PredictionState, generation: &Generation) -> Option<Collectible> {
+        let angle = random::rng().gen_range(COLLECTIBLE_MIN_ANGLE..COLLECTIBLE_MAX_ANGLE);
+        let side = if random::rng().gen_bool(0.5) {
+            1.0
+        } else {
+            -1.0
+        };
+
+        let mut state =
+            PredictionState::running_jump_vec(from.pos, from.yaw + side * angle.to_radians());
+
+        // Around the top of the jump.
+        for _ in 0..random::rng().gen_range(4..8) {
+            state.tick();
+        }
+
+        let collectible =
+            Collectible::new(state.pos + DVec3::new(0.0, 1.0, 0.0), COLLECTIBLE_SCORE);
+        let key = collectible.key() - generation.offset.as_ivec3();
+
+        for pos in [key, key - IVec3::new(0, 1, 0), key - IVec3::new(0, 2, 0)] {
+            if generation.blocks.contains_key(&pos) {
+                return None;
+            }
+        }
+
+        Some(collectible)
+    }
+}
+
+/// Picks where a teleport pad at `start` sends the player. Pads far from the
+/// start of the course send the player back near it, so the course doesn't
+/// drift away forever. Others send the player further ahead. Either way, the
+/// player ends up at a new height.
+fn teleport_destination(start: BlockPos, yaw: f32) -> BlockPos {
+    let home = super::course::START_POS;
+    let dx = start.x - home.x;
+    let dz = start.z - home.z;
+    let y = home.y + random::rng().gen_range(-TELEPORT_HEIGHT..=TELEPORT_HEIGHT);
+
+    if dx * dx + dz * dz > TELEPORT_RESET_DISTANCE * TELEPORT_RESET_DISTANCE {
+        BlockPos::new(
+            home.x + random::rng().gen_range(-TELEPORT_SPREAD..=TELEPORT_SPREAD),
+            y,
+            home.z + random::rng().gen_range(-TELEPORT_SPREAD..=TELEPORT_SPREAD),
+        )
+    } else {
+        BlockPos::new(
+            start.x + (-yaw.sin() * TELEPORT_DISTANCE) as i32,
+            y,
+            start.z + (yaw.cos() * TELEPORT_DISTANCE) as i32,
+        )
+    }
 }
 
 /// The `BlockGenerator` trait represents a block generator.
@@ -12,6 +12,7 @@ use crate::{
     },
     line::Line3,
     prediction::prediction_state::PredictionState,
+    random,
     utils::*,
 };
 
@@ -61,7 +62,7 @@ impl BlinkBlocksGenerator {
         pos: BlockPos,
         map: &BuiltBlockCollectionMap,
     ) -> (ChildGeneration, BlockPos) {
-        let off = rand::thread_rng().gen();
+        let off = random::rng().gen();
         let mut blocks = HashMap::new();
         let mut alt_blocks = HashMap::new();
 
@@ -99,12 +100,7 @@ impl BlinkBlocksGenerator {
             }
         }
 
-        let pos = pos
-            + IVec3::new(
-                if rand::thread_rng().gen() { o } else { 0 },
-                0,
-                self.size.y - 1,
-            );
+        let pos = pos + IVec3::new(if random::rng().gen() { o } else { 0 }, 0, self.size.y - 1);
 
         (ChildGeneration::blocks_alt_blocks(blocks, alt_blocks), pos)
     }
@@ -114,7 +110,7 @@ impl BlockGenerator for BlinkBlocksGenerator {
     fn generate(&self, params: &BlockGenParams) -> GenerateResult {
         let direction = params.direction;
         let map = &params.block_map;
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let mut children = Vec::new();
 
         let (mut g, mut pos) =
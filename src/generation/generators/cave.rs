@@ -11,6 +11,7 @@ use crate::{
     },
     line::Line3,
     prediction::prediction_state::PredictionState,
+    random,
     utils::*,
 };
 
@@ -52,7 +53,7 @@ impl CaveGenerator {
             return prev;
         }
 
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let mut blocks = HashMap::new();
 
@@ -214,7 +215,7 @@ impl BlockGenerator for CaveGenerator {
     fn generate(&self, params: &BlockGenParams) -> GenerateResult {
         let map = &params.block_map;
 
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let mut size: IVec3 = IVec3::new(
             rng.gen_range(10..=20),
@@ -1,23 +1,31 @@
 // I'm trying to somewhat copy TrackMania's grid system.
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+    sync::Arc,
+};
 
-use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
 use valence::{math::IVec3, prelude::*};
 
 use crate::{
     generation::{
         block_collection::BuiltBlockCollectionMap,
-        block_grid::BlockGrid,
+        block_grid::{BlockGrid, BlockProperties},
         generation::ChildGeneration,
-        generator::{BlockGenParams, BlockGenerator, GenerateResult},
+        generator::{BlockGenParams, GenerateResult},
     },
     utils::*,
+    weighted_vec::WeightedVec,
 };
 
+use super::complex_wfc::ComplexBackend;
+
 /// I require to create my own `Direction` instead of using `valence::Direction`
 /// because `valence::Direction` doesn't implement `Hash`.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Direction {
     #[default]
     North,
@@ -28,6 +36,16 @@ pub enum Direction {
     Down,
 }
 
+/// Every direction, in the order tiles are looked through.
+pub const DIRECTIONS: [Direction; 6] = [
+    Direction::North,
+    Direction::South,
+    Direction::West,
+    Direction::East,
+    Direction::Up,
+    Direction::Down,
+];
+
 impl Direction {
     pub fn get_opposite(&self) -> Direction {
         match self {
@@ -62,6 +80,12 @@ impl Direction {
         }
     }
 
+    /// Returns the direction turned around, or the same direction if it is
+    /// Up or Down.
+    pub fn rotate_180(&self) -> Direction {
+        self.get_right().get_right()
+    }
+
     pub fn mirror_horizontal(&self) -> Direction {
         match self {
             Direction::North => Direction::North,
@@ -98,6 +122,18 @@ impl Direction {
             _ => false,
         }
     }
+
+    /// Returns the yaw in radians of a player facing this direction, or
+    /// `None` for Up and Down.
+    pub fn to_yaw(&self) -> Option<f32> {
+        match self {
+            Direction::North => Some(PI),
+            Direction::South => Some(0.0),
+            Direction::West => Some(PI / 2.0),
+            Direction::East => Some(-PI / 2.0),
+            Direction::Up | Direction::Down => None,
+        }
+    }
 }
 
 impl ToBlockPos for Direction {
@@ -126,6 +162,100 @@ impl ToIVec3 for Direction {
     }
 }
 
+/// The `TileOrigin` struct represents the center of a tile position, which
+/// tiles are rotated and flipped around. The center is between two blocks if
+/// the tile size is even, so it is stored doubled.
+///
+/// A tile position spans `-(size.x / 2)` to `size.x - size.x / 2 - 1` on the
+/// x axis and `0` to `size.z - 1` on the z axis. Tiles that take up several
+/// tile positions continue in the positive directions.
+///
+/// Properties:
+///
+/// * `doubled`: Twice the position of the center. Only `x` and `z` are used.
+/// * `tile_size`: The size of a tile position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileOrigin {
+    pub doubled: BlockPos,
+    pub tile_size: BlockPos,
+}
+
+impl TileOrigin {
+    pub fn new(tile_size: BlockPos) -> Self {
+        Self {
+            doubled: BlockPos::new(tile_size.x - tile_size.x / 2 * 2 - 1, 0, tile_size.z - 1),
+            tile_size,
+        }
+    }
+
+    /// Rotates a position 90 degrees clockwise. The tile only stays in place
+    /// if it is square.
+    pub fn rotate_cw(&self, pos: BlockPos) -> BlockPos {
+        let (x, z) = (self.doubled.x, self.doubled.z);
+        BlockPos::new((x + z) / 2 - pos.z, pos.y, pos.x + (z - x) / 2)
+    }
+
+    pub fn rotate_180(&self, pos: BlockPos) -> BlockPos {
+        BlockPos::new(self.doubled.x - pos.x, pos.y, self.doubled.z - pos.z)
+    }
+
+    pub fn flip_x(&self, pos: BlockPos) -> BlockPos {
+        BlockPos::new(self.doubled.x - pos.x, pos.y, pos.z)
+    }
+
+    /// Splits a block of a tile into the tile position it is in, relative to
+    /// the first position of the tile, and the block within that position.
+    pub fn split(&self, pos: BlockPos) -> (BlockPos, BlockPos) {
+        let size = self.tile_size;
+        let cell = BlockPos::new(
+            (pos.x + size.x / 2).div_euclid(size.x),
+            pos.y.div_euclid(size.y),
+            pos.z.div_euclid(size.z),
+        );
+        (cell, pos - cell.mul_block_pos(size).as_ivec3())
+    }
+
+    /// Moves a block of a tile. The tile position the block is in is moved by
+    /// `cell`, and the block within that position by `block`.
+    fn transform_in(
+        &self,
+        pos: BlockPos,
+        cell: impl Fn(BlockPos) -> BlockPos,
+        block: impl Fn(BlockPos) -> BlockPos,
+    ) -> BlockPos {
+        let (c, local) = self.split(pos);
+        block(local) + cell(c).mul_block_pos(self.tile_size).as_ivec3()
+    }
+
+    /// Rotates a block of a tile that takes up `cells` tile positions 90
+    /// degrees clockwise. The tile positions have to be square.
+    pub fn rotate_cw_in(&self, cells: BlockPos, pos: BlockPos) -> BlockPos {
+        self.transform_in(pos, |c| rotate_cell_cw(cells, c), |p| self.rotate_cw(p))
+    }
+
+    pub fn rotate_180_in(&self, cells: BlockPos, pos: BlockPos) -> BlockPos {
+        self.transform_in(pos, |c| rotate_cell_180(cells, c), |p| self.rotate_180(p))
+    }
+
+    pub fn flip_x_in(&self, cells: BlockPos, pos: BlockPos) -> BlockPos {
+        self.transform_in(pos, |c| flip_cell_x(cells, c), |p| self.flip_x(p))
+    }
+}
+
+/// Rotates a tile position of a tile that takes up `cells` tile positions 90
+/// degrees clockwise, so that the tile still starts at 0, 0, 0.
+fn rotate_cell_cw(cells: BlockPos, cell: BlockPos) -> BlockPos {
+    BlockPos::new(cells.z - 1 - cell.z, cell.y, cell.x)
+}
+
+fn rotate_cell_180(cells: BlockPos, cell: BlockPos) -> BlockPos {
+    BlockPos::new(cells.x - 1 - cell.x, cell.y, cells.z - 1 - cell.z)
+}
+
+fn flip_cell_x(cells: BlockPos, cell: BlockPos) -> BlockPos {
+    BlockPos::new(cells.x - 1 - cell.x, cell.y, cell.z)
+}
+
 #[derive(Clone, Debug, Eq)]
 pub struct Connection {
     pub name: String,
@@ -142,6 +272,10 @@ pub struct Connection {
     /// Must be either North, South, West, or East and must be present in the
     /// if this connection is Up or Down.
     pub attach_direction: Option<Direction>,
+    /// The tile position of the tile the connection is on, relative to the
+    /// first position of the tile. Always 0, 0, 0 for tiles that take up a
+    /// single tile position.
+    pub cell: BlockPos,
 }
 
 impl PartialEq for Connection {
@@ -150,6 +284,7 @@ impl PartialEq for Connection {
             && self.next_direction == other.next_direction
             && self.can_next == other.can_next
             && self.can_start == other.can_start
+            && self.cell == other.cell
             && self.blocks.as_ref().map_or(0, |a| a.len())
                 == other.blocks.as_ref().map_or(0, |a| a.len()) // TODO: I might not need this
     }
@@ -161,6 +296,7 @@ impl std::hash::Hash for Connection {
         self.next_direction.hash(state);
         self.can_next.hash(state);
         self.can_start.hash(state);
+        self.cell.hash(state);
         self.blocks.as_ref().map_or(0, |a| a.len()).hash(state); // TODO: I might not need this
     }
 }
@@ -174,40 +310,59 @@ impl Default for Connection {
             can_start: true,
             blocks: None,
             attach_direction: None,
+            cell: BlockPos::new(0, 0, 0),
         }
     }
 }
 
 impl Connection {
-    pub fn rotate_cw(&self, origin: BlockPos) -> Connection {
+    /// Returns the connection with its blocks moved by `f`, its tile position
+    /// moved by `cell` and its directions changed by `direction`.
+    fn transform(
+        &self,
+        f: impl Fn(BlockPos) -> BlockPos,
+        cell: impl Fn(BlockPos) -> BlockPos,
+        direction: impl Fn(&Direction) -> Direction,
+    ) -> Connection {
         Connection {
-            next_direction: self.next_direction.get_right(),
+            next_direction: direction(&self.next_direction),
             blocks: self
                 .blocks
                 .as_ref()
-                .map(|blocks| rotate_block_set_cw(blocks, origin)),
-            attach_direction: self.attach_direction.as_ref().map(|d| d.get_right()),
+                .map(|blocks| blocks.iter().map(|pos| f(*pos)).collect()),
+            attach_direction: self.attach_direction.as_ref().map(direction),
+            cell: cell(self.cell),
             ..self.clone()
         }
     }
 
-    pub fn flip_x(&self, origin: BlockPos) -> Connection {
-        Connection {
-            next_direction: self.next_direction.mirror_horizontal(),
-            blocks: self
-                .blocks
-                .as_ref()
-                .map(|blocks| flip_block_set_x(blocks, origin)),
-            attach_direction: self
-                .attach_direction
-                .as_ref()
-                .map(|d| d.mirror_horizontal()),
-            ..self.clone()
-        }
+    /// Rotates the connection of a tile that takes up `cells` tile positions.
+    pub fn rotate_cw(&self, origin: TileOrigin, cells: BlockPos) -> Connection {
+        self.transform(
+            |pos| origin.rotate_cw_in(cells, pos),
+            |cell| rotate_cell_cw(cells, cell),
+            Direction::get_right,
+        )
+    }
+
+    pub fn rotate_180(&self, origin: TileOrigin, cells: BlockPos) -> Connection {
+        self.transform(
+            |pos| origin.rotate_180_in(cells, pos),
+            |cell| rotate_cell_180(cells, cell),
+            Direction::rotate_180,
+        )
+    }
+
+    pub fn flip_x(&self, origin: TileOrigin, cells: BlockPos) -> Connection {
+        self.transform(
+            |pos| origin.flip_x_in(cells, pos),
+            |cell| flip_cell_x(cells, cell),
+            Direction::mirror_horizontal,
+        )
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct ComplexTile {
     pub connection_north: Option<Connection>,
     pub connection_south: Option<Connection>,
@@ -215,8 +370,45 @@ pub struct ComplexTile {
     pub connection_east: Option<Connection>,
     pub connection_up: Option<Connection>,
     pub connection_down: Option<Connection>,
-    pub grid: BlockGrid,    // ignore this field in Hash and PartialEq
-    pub disable_flip: bool, // TODO: Figure out if I should add `disable_rotate` too
+    pub grid: BlockGrid, // ignore this field in Hash and PartialEq
+    /// How many tile positions the tile takes up on each axis. The blocks of
+    /// the other positions continue in the positive directions from the
+    /// first one.
+    pub cells: BlockPos,
+    pub disable_flip: bool,
+    pub disable_rotate: bool,
+    /// How likely the tile is to be picked compared to the other tiles that
+    /// fit. Tiles with a weight of 0 are never picked.
+    pub weight: f32,
+    /// How many times the tile can be used in a path. Rotated and mirrored
+    /// versions count as the same tile.
+    pub max_uses: Option<usize>,
+    /// If true, every path has to use this tile at least once.
+    pub required: bool,
+    /// The index of the tile in the list it was created from. Set by
+    /// `ComplexGenerator::new` and used to count uses.
+    pub index: usize,
+}
+
+impl Default for ComplexTile {
+    fn default() -> Self {
+        Self {
+            connection_north: None,
+            connection_south: None,
+            connection_west: None,
+            connection_east: None,
+            connection_up: None,
+            connection_down: None,
+            grid: BlockGrid::default(),
+            cells: BlockPos::new(1, 1, 1),
+            disable_flip: false,
+            disable_rotate: false,
+            weight: 1.0,
+            max_uses: None,
+            required: false,
+            index: 0,
+        }
+    }
 }
 
 impl Eq for ComplexTile {}
@@ -229,6 +421,7 @@ impl std::hash::Hash for ComplexTile {
         self.connection_east.hash(state);
         self.connection_up.hash(state);
         self.connection_down.hash(state);
+        self.cells.hash(state);
         self.disable_flip.hash(state);
     }
 }
@@ -241,66 +434,145 @@ impl PartialEq for ComplexTile {
             && self.connection_east == other.connection_east
             && self.connection_up == other.connection_up
             && self.connection_down == other.connection_down
+            && self.cells == other.cells
             && self.disable_flip == other.disable_flip
     }
 }
 
 impl ComplexTile {
-    pub fn get_next(&self, direction: Direction) -> Option<Connection> {
+    pub fn get_next(&self, direction: Direction) -> Option<&Connection> {
         match direction {
-            Direction::North => self.connection_north.clone(),
-            Direction::South => self.connection_south.clone(),
-            Direction::West => self.connection_west.clone(),
-            Direction::East => self.connection_east.clone(),
-            Direction::Up => self.connection_up.clone(),
-            Direction::Down => self.connection_down.clone(),
+            Direction::North => self.connection_north.as_ref(),
+            Direction::South => self.connection_south.as_ref(),
+            Direction::West => self.connection_west.as_ref(),
+            Direction::East => self.connection_east.as_ref(),
+            Direction::Up => self.connection_up.as_ref(),
+            Direction::Down => self.connection_down.as_ref(),
         }
     }
 
-    /// Returns the tile rotated 90 degrees clockwise
-    pub fn rotate_cw(&self, origin: BlockPos) -> ComplexTile {
+    /// Every tile position the tile takes up, relative to its first one.
+    pub fn cell_positions(&self) -> impl Iterator<Item = BlockPos> {
+        let cells = self.cells;
+        (0..cells.y).flat_map(move |y| {
+            (0..cells.z).flat_map(move |z| (0..cells.x).map(move |x| BlockPos::new(x, y, z)))
+        })
+    }
+
+    pub fn has_cell(&self, cell: BlockPos) -> bool {
+        (0..self.cells.x).contains(&cell.x)
+            && (0..self.cells.y).contains(&cell.y)
+            && (0..self.cells.z).contains(&cell.z)
+    }
+
+    /// Whether `cell` is at the edge of the tile in `direction`, which is
+    /// where a connection in that direction has to be.
+    fn is_edge(&self, cell: BlockPos, direction: Direction) -> bool {
+        self.has_cell(cell)
+            && match direction {
+                Direction::North => cell.z == 0,
+                Direction::South => cell.z == self.cells.z - 1,
+                Direction::West => cell.x == 0,
+                Direction::East => cell.x == self.cells.x - 1,
+                Direction::Up => cell.y == self.cells.y - 1,
+                Direction::Down => cell.y == 0,
+            }
+    }
+
+    /// Returns the tile rotated 90 degrees clockwise. Only tiles on square
+    /// tile positions can be rotated by 90 degrees. The tile takes up the
+    /// tile positions turned as well, so a tile that is two positions long is
+    /// two positions wide afterwards.
+    pub fn rotate_cw(&self, origin: TileOrigin) -> ComplexTile {
+        let cells = self.cells;
+        let rotate = |c: &Connection| c.rotate_cw(origin, cells);
+        ComplexTile {
+            connection_north: self.connection_west.as_ref().map(rotate),
+            connection_south: self.connection_east.as_ref().map(rotate),
+            connection_west: self.connection_south.as_ref().map(rotate),
+            connection_east: self.connection_north.as_ref().map(rotate),
+            connection_up: self.connection_up.as_ref().map(rotate),
+            connection_down: self.connection_down.as_ref().map(rotate),
+            grid: self.grid.transform(
+                |pos| origin.rotate_cw_in(cells, pos),
+                BlockProperties::rotate_cw,
+            ),
+            cells: BlockPos::new(cells.z, cells.y, cells.x),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the tile rotated 180 degrees
+    pub fn rotate_180(&self, origin: TileOrigin) -> ComplexTile {
+        let cells = self.cells;
+        let rotate = |c: &Connection| c.rotate_180(origin, cells);
         ComplexTile {
-            connection_north: self.connection_west.as_ref().map(|c| c.rotate_cw(origin)),
-            connection_south: self.connection_east.as_ref().map(|c| c.rotate_cw(origin)),
-            connection_west: self.connection_south.as_ref().map(|c| c.rotate_cw(origin)),
-            connection_east: self.connection_north.as_ref().map(|c| c.rotate_cw(origin)),
-            connection_up: self.connection_up.as_ref().map(|c| c.rotate_cw(origin)),
-            connection_down: self.connection_down.as_ref().map(|c| c.rotate_cw(origin)),
-            grid: self.grid.rotate_cw(origin),
+            connection_north: self.connection_south.as_ref().map(rotate),
+            connection_south: self.connection_north.as_ref().map(rotate),
+            connection_west: self.connection_east.as_ref().map(rotate),
+            connection_east: self.connection_west.as_ref().map(rotate),
+            connection_up: self.connection_up.as_ref().map(rotate),
+            connection_down: self.connection_down.as_ref().map(rotate),
+            grid: self.grid.transform(
+                |pos| origin.rotate_180_in(cells, pos),
+                |block| block.rotate_cw().rotate_cw(),
+            ),
             ..self.clone()
         }
     }
 
     /// Returns the tile flipped along the X axis
-    pub fn flip_x(&self, origin: BlockPos) -> ComplexTile {
+    pub fn flip_x(&self, origin: TileOrigin) -> ComplexTile {
+        let cells = self.cells;
+        let flip = |c: &Connection| c.flip_x(origin, cells);
         ComplexTile {
-            connection_north: self.connection_north.as_ref().map(|c| c.flip_x(origin)),
-            connection_south: self.connection_south.as_ref().map(|c| c.flip_x(origin)),
-            connection_west: self.connection_east.as_ref().map(|c| c.flip_x(origin)),
-            connection_east: self.connection_west.as_ref().map(|c| c.flip_x(origin)),
-            connection_up: self.connection_up.as_ref().map(|c| c.flip_x(origin)),
-            connection_down: self.connection_down.as_ref().map(|c| c.flip_x(origin)),
-            grid: self.grid.flip_x(origin),
+            connection_north: self.connection_north.as_ref().map(flip),
+            connection_south: self.connection_south.as_ref().map(flip),
+            connection_west: self.connection_east.as_ref().map(flip),
+            connection_east: self.connection_west.as_ref().map(flip),
+            connection_up: self.connection_up.as_ref().map(flip),
+            connection_down: self.connection_down.as_ref().map(flip),
+            grid: self
+                .grid
+                .transform(|pos| origin.flip_x_in(cells, pos), BlockProperties::flip_x),
             ..self.clone()
         }
     }
 
     /// Returns all the rotated and mirrored versions of the tile, without duplicates
-    pub fn get_all_rotations(&self, origin: BlockPos, square: bool) -> Vec<ComplexTile> {
-        let mut tiles = HashSet::new();
+    ///
+    /// The order is always the same so that a seeded RNG picks the same tiles.
+    /// If the tile positions aren't square, tiles are only rotated by 180
+    /// degrees, since a quarter turn would swap the x and z size of every
+    /// position the tile takes up. A longer tile on square positions, like
+    /// one that takes up 2x1 positions, is rotated by 90 degrees. Tiles with
+    /// `disable_rotate` aren't rotated at all.
+    pub fn get_all_rotations(&self, origin: TileOrigin) -> Vec<ComplexTile> {
+        let square = origin.tile_size.x == origin.tile_size.z;
+        let mut tiles = Vec::new();
+        let mut push = |tile: ComplexTile| {
+            if !tiles.contains(&tile) {
+                tiles.push(tile);
+            }
+        };
         let mut current_tile = self.clone();
-        for _ in 0..if square { 4 } else { 2 } {
-            // if square, rotate 4 times, else rotate 2 times twice.
-            tiles.insert(current_tile.clone());
+        let rotations = match (self.disable_rotate, square) {
+            (true, _) => 1,
+            (false, true) => 4,
+            (false, false) => 2,
+        };
+        for _ in 0..rotations {
+            push(current_tile.clone());
             if !self.disable_flip {
-                tiles.insert(current_tile.flip_x(origin));
-            }
-            current_tile = current_tile.rotate_cw(origin);
-            if !square {
-                current_tile = current_tile.rotate_cw(origin); // rotate 2 times for non-square grid
+                push(current_tile.flip_x(origin));
             }
+            current_tile = if square {
+                current_tile.rotate_cw(origin)
+            } else {
+                current_tile.rotate_180(origin)
+            };
         }
-        tiles.into_iter().collect()
+        tiles
     }
 
     /// Places the tile in the grid at the given position
@@ -310,7 +582,7 @@ impl ComplexTile {
         block_map: &BuiltBlockCollectionMap,
         pos: BlockPos,
     ) {
-        for (block_pos, block) in &self.grid.blocks {
+        for (block_pos, block) in self.grid.sorted_blocks() {
             let block_pos = pos + block_pos.as_ivec3();
             let block = block.get_block(&block_map);
             grid.insert(block_pos, block);
@@ -328,10 +600,20 @@ impl ComplexTile {
     /// If a direction is Up or Down, then the `attach_direction` must be
     /// defined and must be either North, South, West, or East.
     ///
+    /// Every connection has to be on a tile position at the edge of the tile
+    /// in its direction.
+    ///
     /// Returns the first error it finds.
     pub fn verify(&self) -> Result<(), String> {
         let mut connections = HashMap::new();
 
+        if self.cells.x <= 0 || self.cells.y <= 0 || self.cells.z <= 0 {
+            return Err(format!(
+                "The tile takes up {:?} tile positions, but it must be positive",
+                self.cells
+            ));
+        }
+
         if let Some(connection) = &self.connection_north {
             connections.insert(Direction::North, connection);
         }
@@ -386,6 +668,13 @@ impl ComplexTile {
         }
 
         for (direction, connection) in &connections {
+            if !self.is_edge(connection.cell, *direction) {
+                return Err(format!(
+                    "The connection {:?} is on the tile position {:?}, which isn't at the edge of the tile in that direction",
+                    direction, connection.cell,
+                ));
+            }
+
             if let Some(next_connection) = connections.get(&connection.next_direction) {
                 if next_connection.next_direction != *direction {
                     return Err(format!(
@@ -430,151 +719,395 @@ impl ComplexTile {
     }
 }
 
+/// The `ComplexGenError` enum represents the ways a `ComplexGenerator` can
+/// fail to be built or to generate a path.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComplexGenError {
+    /// The tile size isn't positive.
+    InvalidTileSize(BlockPos),
+    /// The bounds are empty or don't contain the starting tile.
+    InvalidBounds {
+        min_pos: BlockPos,
+        max_pos: BlockPos,
+    },
+    /// A tile failed `ComplexTile::verify`.
+    InvalidTile { index: usize, error: String },
+    /// The connections of the tiles can never make a path.
+    Unsatisfiable(String),
+    /// No path was found in the given number of tries.
+    RetriesExhausted(usize),
+}
+
+impl std::fmt::Display for ComplexGenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComplexGenError::InvalidTileSize(size) => {
+                write!(f, "Invalid tile size {:?}, it must be positive", size)
+            }
+            ComplexGenError::InvalidBounds { min_pos, max_pos } => write!(
+                f,
+                "The bounds {:?} to {:?} don't contain the starting tile",
+                min_pos, max_pos
+            ),
+            ComplexGenError::InvalidTile { index, error } => {
+                write!(f, "Invalid tile {}: {}", index, error)
+            }
+            ComplexGenError::Unsatisfiable(reason) => write!(f, "Unsatisfiable tiles: {}", reason),
+            ComplexGenError::RetriesExhausted(tries) => {
+                write!(f, "Failed to generate a path. Tried {} times", tries)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ComplexGenError {}
+
+/// The `ComplexTileSet` struct holds every rotated and mirrored version of a
+/// list of tiles, indexed by their connections. It never changes after it is
+/// built, so it is shared between all the generators built from the same
+/// preset and tiles are referred to by their index in `tiles`.
+///
+/// Tiles can take up several positions of the grid, so tiles of different
+/// sizes can be mixed as long as they are made of whole tile positions.
+///
+/// Properties:
+///
+/// * `tile_size`: The size of a tile position.
+/// * `tiles`: Every version of every tile.
+/// * `starting_tiles`: The tiles that can be the start of the path.
+/// * `tiles_by_north`, `tiles_by_south`, ...: The tiles that can be placed
+/// through a connection in that direction, by the connection's name.
+/// * `required`: The `ComplexTile::index` of the tiles every path has to use.
+/// * `limited`: The `ComplexTile::index` of the tiles that are required or
+/// have `max_uses`, in order.
 #[derive(Clone, Debug)]
-pub struct ComplexGenerator {
-    // TODO: Might want to use Rc<ComplexTile> instead of cloning.
-    pub tile_size: BlockPos, // TODO: What to do if grid size is even?
-    pub min_pos: BlockPos,
-    pub max_pos: BlockPos,
+pub struct ComplexTileSet {
+    pub tile_size: BlockPos,
     pub tiles: Vec<ComplexTile>,
-    pub starting_tiles: Vec<ComplexTile>,
-    pub tiles_by_north: HashMap<String, Vec<ComplexTile>>,
-    pub tiles_by_south: HashMap<String, Vec<ComplexTile>>,
-    pub tiles_by_west: HashMap<String, Vec<ComplexTile>>,
-    pub tiles_by_east: HashMap<String, Vec<ComplexTile>>,
-    pub tiles_by_up: HashMap<String, Vec<ComplexTile>>,
-    pub tiles_by_down: HashMap<String, Vec<ComplexTile>>,
-    pub tile_grid: HashMap<BlockPos, ComplexTile>,
+    pub starting_tiles: Vec<usize>,
+    pub tiles_by_north: HashMap<String, Vec<usize>>,
+    pub tiles_by_south: HashMap<String, Vec<usize>>,
+    pub tiles_by_west: HashMap<String, Vec<usize>>,
+    pub tiles_by_east: HashMap<String, Vec<usize>>,
+    pub tiles_by_up: HashMap<String, Vec<usize>>,
+    pub tiles_by_down: HashMap<String, Vec<usize>>,
+    pub required: HashSet<usize>,
+    pub limited: Vec<usize>,
 }
 
-impl ComplexGenerator {
-    pub fn new(
-        tiles: Vec<ComplexTile>,
-        tile_size: BlockPos,
-        min_pos: BlockPos,
-        max_pos: BlockPos,
-    ) -> ComplexGenerator {
-        if tile_size.x % 2 == 0 || tile_size.z % 2 == 0 {
-            panic!("Tile x & z size must be odd"); // TODO
+impl ComplexTileSet {
+    pub fn new(tiles: Vec<ComplexTile>, tile_size: BlockPos) -> Result<Self, ComplexGenError> {
+        if tile_size.x <= 0 || tile_size.y <= 0 || tile_size.z <= 0 {
+            return Err(ComplexGenError::InvalidTileSize(tile_size));
         }
 
         let mut new_tiles = Vec::new();
-        let origin = BlockPos::new(0, 0, tile_size.z / 2);
-        for tile in tiles {
-            if let Err(e) = tile.verify() {
-                panic!("Invalid tile: {}", e);
-            }
-            new_tiles.extend(tile.get_all_rotations(origin, tile_size.x == tile_size.y));
-        }
-
-        let tiles = new_tiles;
-        let mut starting_tiles = Vec::new();
-        let mut tiles_by_north = HashMap::new();
-        let mut tiles_by_south = HashMap::new();
-        let mut tiles_by_west = HashMap::new();
-        let mut tiles_by_east = HashMap::new();
-        let mut tiles_by_up = HashMap::new();
-        let mut tiles_by_down = HashMap::new();
-
-        let tile_grid = HashMap::new();
-
-        for tile in &tiles {
-            if let Some(Connection {
-                name,
-                can_next,
-                can_start,
-                ..
-            }) = &tile.connection_north
-            {
-                if *can_next {
-                    tiles_by_north
-                        .entry(name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(tile.clone());
-
-                    if *can_start {
-                        starting_tiles.push(tile.clone());
-                    }
-                }
+        let origin = TileOrigin::new(tile_size);
+        let mut required = HashSet::new();
+        let mut limited = Vec::new();
+        for (index, mut tile) in tiles.into_iter().enumerate() {
+            if let Err(error) = tile.verify() {
+                return Err(ComplexGenError::InvalidTile { index, error });
             }
-            if let Some(Connection { name, can_next, .. }) = &tile.connection_south {
-                if *can_next {
-                    tiles_by_south
-                        .entry(name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(tile.clone());
-                }
+            if tile.weight < 0.0 {
+                return Err(ComplexGenError::InvalidTile {
+                    index,
+                    error: format!("The weight {} is negative", tile.weight),
+                });
             }
-            if let Some(Connection { name, can_next, .. }) = &tile.connection_west {
-                if *can_next {
-                    tiles_by_west
-                        .entry(name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(tile.clone());
+            if tile.required {
+                if tile.max_uses == Some(0) || tile.weight == 0.0 {
+                    return Err(ComplexGenError::InvalidTile {
+                        index,
+                        error: "The tile is required, but can never be used".to_owned(),
+                    });
                 }
+                required.insert(index);
             }
-            if let Some(Connection { name, can_next, .. }) = &tile.connection_east {
-                if *can_next {
-                    tiles_by_east
-                        .entry(name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(tile.clone());
-                }
+            if tile.required || tile.max_uses.is_some() {
+                limited.push(index);
             }
-            if let Some(Connection { name, can_next, .. }) = &tile.connection_up {
-                if *can_next {
-                    tiles_by_up
-                        .entry(name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(tile.clone());
+            tile.index = index;
+            new_tiles.extend(tile.get_all_rotations(origin));
+        }
+
+        let mut set = Self {
+            tile_size,
+            tiles: new_tiles,
+            starting_tiles: Vec::new(),
+            tiles_by_north: HashMap::new(),
+            tiles_by_south: HashMap::new(),
+            tiles_by_west: HashMap::new(),
+            tiles_by_east: HashMap::new(),
+            tiles_by_up: HashMap::new(),
+            tiles_by_down: HashMap::new(),
+            required,
+            limited,
+        };
+
+        for (i, tile) in set.tiles.iter().enumerate() {
+            for direction in DIRECTIONS {
+                let Some(connection) = tile.get_next(direction) else {
+                    continue;
+                };
+                if !connection.can_next {
+                    continue;
                 }
-            }
-            if let Some(Connection { name, can_next, .. }) = &tile.connection_down {
-                if *can_next {
-                    tiles_by_down
-                        .entry(name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(tile.clone());
+
+                let by_name = match direction {
+                    Direction::North => &mut set.tiles_by_north,
+                    Direction::South => &mut set.tiles_by_south,
+                    Direction::West => &mut set.tiles_by_west,
+                    Direction::East => &mut set.tiles_by_east,
+                    Direction::Up => &mut set.tiles_by_up,
+                    Direction::Down => &mut set.tiles_by_down,
+                };
+                by_name
+                    .entry(connection.name.clone())
+                    .or_insert_with(Vec::new)
+                    .push(i);
+
+                if direction == Direction::North && connection.can_start {
+                    set.starting_tiles.push(i);
                 }
             }
         }
-        Self {
-            tile_size,
-            min_pos,
-            max_pos,
-            tiles,
-            starting_tiles,
-            tiles_by_north,
-            tiles_by_south,
-            tiles_by_west,
-            tiles_by_east,
-            tiles_by_up,
-            tiles_by_down,
-            tile_grid,
-        }
-    }
 
-    pub fn get_tile(&self, pos: BlockPos) -> Option<&ComplexTile> {
-        self.tile_grid.get(&pos)
+        Ok(set)
     }
 
-    pub fn get_tiles_by_dir_name(
-        &self,
-        direction: Direction,
-        name: &str,
-    ) -> Option<Vec<ComplexTile>> {
-        if let Some(v) = match direction {
+    pub fn get_tiles_by_dir_name(&self, direction: Direction, name: &str) -> Option<&[usize]> {
+        match direction {
             Direction::North => self.tiles_by_north.get(name),
             Direction::South => self.tiles_by_south.get(name),
             Direction::West => self.tiles_by_west.get(name),
             Direction::East => self.tiles_by_east.get(name),
             Direction::Up => self.tiles_by_up.get(name),
             Direction::Down => self.tiles_by_down.get(name),
-        } {
-            Some(v.clone())
-        } else {
-            None
         }
+        .map(|tiles| tiles.as_slice())
+    }
+}
+
+/// The `ComplexExit` struct represents where a path leaves the grid of a
+/// `ComplexGenerator`.
+///
+/// Properties:
+///
+/// * `pos`: The last block of the path, relative to the start tile.
+/// * `direction`: The direction the path leaves the last tile in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComplexExit {
+    pub pos: BlockPos,
+    pub direction: Direction,
+}
+
+/// The `ComplexGenerator` struct finds a path of tiles through a grid.
+///
+/// Properties:
+///
+/// * `tile_size`: The size of a tile position.
+/// * `min_pos`: The lowest tile position the path can go to.
+/// * `max_pos`: The highest tile position the path can go to.
+/// * `tile_set`: The tiles the path is made of, shared with other generators.
+/// * `tile_grid`: The placed tiles, as indices into `tile_set.tiles`, by the
+/// first tile position they take up.
+/// * `occupied`: Every tile position a tile of `tile_grid` takes up, with the
+/// position the tile is placed at.
+/// * `min_length`: The least number of tiles a path can have.
+/// * `max_length`: The most tiles a path can have. If `None`, there is no
+/// limit.
+/// * `uses`: How many times each tile is used in `tile_grid`, by
+/// `ComplexTile::index`.
+/// * `backend`: How the path is found.
+#[derive(Clone, Debug)]
+pub struct ComplexGenerator {
+    pub tile_size: BlockPos,
+    pub min_pos: BlockPos,
+    pub max_pos: BlockPos,
+    pub tile_set: Arc<ComplexTileSet>,
+    pub tile_grid: HashMap<BlockPos, usize>,
+    pub occupied: HashMap<BlockPos, BlockPos>,
+    pub min_length: usize,
+    pub max_length: Option<usize>,
+    pub uses: HashMap<usize, usize>,
+    pub backend: ComplexBackend,
+}
+
+impl ComplexGenerator {
+    pub fn new(
+        tiles: Vec<ComplexTile>,
+        tile_size: BlockPos,
+        min_pos: BlockPos,
+        max_pos: BlockPos,
+    ) -> Result<ComplexGenerator, ComplexGenError> {
+        let tile_set = ComplexTileSet::new(tiles, tile_size)?;
+        Self::from_tile_set(Arc::new(tile_set), min_pos, max_pos)
+    }
+
+    /// Creates a generator that shares an already built tile set.
+    pub fn from_tile_set(
+        tile_set: Arc<ComplexTileSet>,
+        min_pos: BlockPos,
+        max_pos: BlockPos,
+    ) -> Result<ComplexGenerator, ComplexGenError> {
+        let gen = Self {
+            tile_size: tile_set.tile_size,
+            min_pos,
+            max_pos,
+            tile_set,
+            tile_grid: HashMap::new(),
+            occupied: HashMap::new(),
+            min_length: 1,
+            max_length: None,
+            uses: HashMap::new(),
+            backend: ComplexBackend::Dfs,
+        };
+
+        gen.check_connections()?;
+
+        Ok(gen)
+    }
+
+    /// Limits how many tiles a path can have.
+    pub fn with_length(
+        mut self,
+        min_length: usize,
+        max_length: Option<usize>,
+    ) -> Result<Self, ComplexGenError> {
+        let required = self.tile_set.required.len();
+        if max_length.is_some_and(|max| max < min_length.max(required) || max == 0) {
+            return Err(ComplexGenError::Unsatisfiable(format!(
+                "A path can't have between {} and {:?} tiles",
+                min_length, max_length
+            )));
+        }
+
+        self.min_length = min_length;
+        self.max_length = max_length;
+        Ok(self)
+    }
+
+    pub fn with_backend(mut self, backend: ComplexBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Checks that a path can be started and continued with the tiles.
+    ///
+    /// There has to be a starting tile, and unless a single tile reaches the
+    /// end of the grid, some tile has to be able to follow a starting tile.
+    fn check_connections(&self) -> Result<(), ComplexGenError> {
+        if self.tile_set.starting_tiles.is_empty() {
+            return Err(ComplexGenError::Unsatisfiable(
+                "No tile can be the start of the path".to_owned(),
+            ));
+        }
+
+        if self.max_pos.z <= 1 {
+            return Ok(());
+        }
+
+        let can_continue = self.tile_set.starting_tiles.iter().any(|i| {
+            let tile = &self.tile_set.tiles[*i];
+            let Some(Connection { next_direction, .. }) = tile.get_next(Direction::North) else {
+                return false;
+            };
+            let Some(Connection { name, .. }) = tile.get_next(*next_direction) else {
+                return false;
+            };
+            self.get_tiles_by_dir_name(next_direction.get_opposite(), name)
+                .is_some_and(|tiles| !tiles.is_empty())
+        });
+
+        if !can_continue {
+            return Err(ComplexGenError::Unsatisfiable(
+                "No tile can follow any of the starting tiles".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Gets the tile that takes up a tile position and the position the tile
+    /// is placed at.
+    pub fn get_tile(&self, pos: BlockPos) -> Option<(BlockPos, &ComplexTile)> {
+        let anchor = *self.occupied.get(&pos)?;
+        let tile = self.tile_grid.get(&anchor)?;
+        Some((anchor, &self.tile_set.tiles[*tile]))
+    }
+
+    /// Gets the connection of the tile at a tile position that faces
+    /// `direction`, if the connection is on that position.
+    pub fn get_face(&self, pos: BlockPos, direction: Direction) -> Option<&Connection> {
+        let (anchor, tile) = self.get_tile(pos)?;
+        tile.get_next(direction)
+            .filter(|c| anchor + c.cell.as_ivec3() == pos)
+    }
+
+    /// Follows the path through the tile at `pos`, which it comes into while
+    /// moving in `direction`. Returns the tile position after the tile, the
+    /// direction the path leaves in and the connection it leaves through.
+    pub fn step(
+        &self,
+        pos: BlockPos,
+        direction: Direction,
+    ) -> Option<(BlockPos, Direction, &Connection)> {
+        let (anchor, tile) = self.get_tile(pos)?;
+        let entry = tile
+            .get_next(direction.get_opposite())
+            .filter(|c| anchor + c.cell.as_ivec3() == pos)?;
+        let next_direction = entry.next_direction;
+        let exit = tile.get_next(next_direction)?;
+        let next_pos = anchor + exit.cell.as_ivec3() + next_direction.to_ivec3();
+        Some((next_pos, next_direction, exit))
+    }
+
+    pub fn get_tiles_by_dir_name(&self, direction: Direction, name: &str) -> Option<&[usize]> {
+        self.tile_set.get_tiles_by_dir_name(direction, name)
+    }
+
+    /// Whether a tile can take up a tile position. The `max_pos.z` row is
+    /// where paths leave the grid, so no tile can be there.
+    pub fn in_bounds(&self, pos: BlockPos) -> bool {
+        pos.x >= self.min_pos.x
+            && pos.y >= self.min_pos.y
+            && pos.z >= self.min_pos.z
+            && pos.x <= self.max_pos.x
+            && pos.y <= self.max_pos.y
+            && pos.z < self.max_pos.z
+    }
+
+    /// Whether a tile placed at `anchor` stays within the bounds and doesn't
+    /// overlap another tile.
+    fn fits(&self, anchor: BlockPos, tile: &ComplexTile) -> bool {
+        tile.cell_positions().all(|cell| {
+            let pos = anchor + cell.as_ivec3();
+            self.in_bounds(pos) && !self.occupied.contains_key(&pos)
+        })
+    }
+
+    /// Whether every side of a tile placed at `anchor` matches the tiles next
+    /// to it. Sides that face each other either both have a connection with
+    /// the same name or both have none.
+    fn matches_neighbors(&self, anchor: BlockPos, tile: &ComplexTile) -> bool {
+        tile.cell_positions().all(|cell| {
+            DIRECTIONS.iter().all(|direction| {
+                let next_cell = cell + direction.to_ivec3();
+                let next_pos = anchor + next_cell.as_ivec3();
+                if tile.has_cell(next_cell) || self.get_tile(next_pos).is_none() {
+                    return true;
+                }
+
+                let ours = tile.get_next(*direction).filter(|c| c.cell == cell);
+                let theirs = self.get_face(next_pos, direction.get_opposite());
+                match (ours, theirs) {
+                    (Some(ours), Some(theirs)) => ours.name == theirs.name,
+                    (None, None) => true,
+                    _ => false,
+                }
+            })
+        })
     }
 
     /// The `get_placement` function is used to select a tile based on the
@@ -582,35 +1115,26 @@ impl ComplexGenerator {
     /// connects to the current tile and also ensures that it's not creating an
     /// infinite loop. If all checks pass, it returns the new position,
     /// direction, and the name of the connection for the next tile.
-    fn get_end_of_path(
-        &self,
+    fn get_end_of_path<'a>(
+        &'a self,
         pos: BlockPos,
         direction: Direction,
-        name: &str,
-    ) -> Option<(BlockPos, Direction, String)> {
+        name: &'a str,
+    ) -> Option<(BlockPos, Direction, &'a str)> {
         let mut current_pos = pos;
         let mut current_direction = direction;
-        let mut current_name = name.to_owned();
-        loop {
-            if let Some(tile) = self.get_tile(current_pos) {
-                if let Some(Connection { next_direction, .. }) =
-                    tile.get_next(current_direction.get_opposite())
-                {
-                    current_pos = current_pos + next_direction.to_ivec3();
-                    current_direction = next_direction;
-                    current_name = tile
-                        .get_next(current_direction)
-                        .expect("Should have a name")
-                        .name;
-
-                    if current_direction == direction && current_pos == pos {
-                        return None;
-                    }
-                    continue;
-                }
+        let mut current_name = name;
+        while let Some((next_pos, next_direction, exit)) = self.step(current_pos, current_direction)
+        {
+            current_pos = next_pos;
+            current_direction = next_direction;
+            current_name = &exit.name;
+
+            if current_direction == direction && current_pos == pos {
+                return None;
             }
-            return Some((current_pos, current_direction, current_name));
         }
+        Some((current_pos, current_direction, current_name))
     }
 
     /// The `get_placement` function is used to select a tile based on the
@@ -622,117 +1146,86 @@ impl ComplexGenerator {
         &self,
         pos: BlockPos,
         direction: Direction,
-        name: String,
-    ) -> Option<(BlockPos, Direction, Vec<ComplexTile>)> {
-        let tile = match self.get_tile(pos) {
-            Some(c) => c,
-            None => return None,
-        };
-        if let Some(Connection {
+        name: &str,
+    ) -> Option<(BlockPos, Direction, Vec<usize>)> {
+        let (anchor, tile) = self.get_tile(pos)?;
+        let Connection {
             name: next_name,
             next_direction,
             ..
-        }) = tile.get_next(direction.get_opposite())
-        {
-            // Check to make sure that the next tile connects to the current tile
-            if let Some(next_tile) = self.get_tile(pos + next_direction.to_ivec3()) {
-                let next_tile_next = next_tile.get_next(next_direction.get_opposite());
-                if let Some(Connection {
+        } = tile.get_next(direction.get_opposite())?;
+        let next_direction = *next_direction;
+        let exit = tile.get_next(next_direction)?;
+
+        // Check to make sure that the next tile connects to the current tile
+        let next_pos = anchor + exit.cell.as_ivec3() + next_direction.to_ivec3();
+        if self.get_tile(next_pos).is_some() {
+            match self.get_face(next_pos, next_direction.get_opposite()) {
+                Some(Connection {
                     name: next_tile_name,
                     ..
-                }) = next_tile_next
-                {
-                    if next_tile_name != next_name {
-                        return None;
-                    }
-                } else {
-                    return None;
-                }
+                }) if next_tile_name == next_name => {}
+                _ => return None,
             }
+        }
 
-            // Make sure we're not making an infinite loop
-            for direction in direction.get_forward_and_orthogonal() {
-                if let None = self.get_end_of_path(pos, direction, &name) {
-                    return None;
-                }
-            }
+        // Make sure we're not making an infinite loop
+        for direction in direction.get_forward_and_orthogonal() {
+            self.get_end_of_path(pos, direction, name)?;
+        }
 
-            // We're good!
-
-            // Get current pos, direction, and name
-            let (pos, direction, name) = self
-                .get_end_of_path(pos, direction, &name)
-                .expect("Should have a path");
-
-            // Get the possible tiles that can be placed here
-            let mut tiles = self.get_tiles_by_dir_name(direction.get_opposite(), &name)?;
-
-            // If next direction is Up or Down, then we need to filter out tiles
-            // that don't have the same attach_direction. We need to get the
-            // attach_direction first.
-            let attach_direction = tile
-                .get_next(next_direction)
-                .and_then(|c| c.attach_direction);
-
-            // Filter out tiles that don't connect to the adjacent tiles
-            // TODO: Move this to a separate function
-            tiles = tiles
-                .into_iter()
-                .filter(|tile| {
-                    // If the tile has an attach_direction, then it must match
-                    // the attach_direction of the previous tile
-                    if let Some(attach_direction) = attach_direction {
-                        if let Some(Connection {
-                            attach_direction: tile_attach_direction,
+        // We're good!
+
+        // Get current pos, direction, and name
+        let (pos, direction, name) = self.get_end_of_path(pos, direction, name)?;
+
+        // Get the possible tiles that can be placed here
+        let tiles = self.get_tiles_by_dir_name(direction.get_opposite(), name)?;
+
+        // If next direction is Up or Down, then we need to filter out tiles
+        // that don't have the same attach_direction. We need to get the
+        // attach_direction first.
+        let attach_direction = tile
+            .get_next(next_direction)
+            .and_then(|c| c.attach_direction);
+
+        // Filter out tiles that don't connect to the adjacent tiles
+        // TODO: Move this to a separate function
+        let tiles: Vec<usize> = tiles
+            .iter()
+            .copied()
+            .filter(|i| {
+                let tile = &self.tile_set.tiles[*i];
+
+                // If the tile has an attach_direction, then it must match
+                // the attach_direction of the previous tile
+                if let Some(attach_direction) = attach_direction {
+                    match tile.get_next(next_direction.get_opposite()) {
+                        Some(Connection {
+                            attach_direction: Some(tile_attach_direction),
                             ..
-                        }) = tile.get_next(next_direction.get_opposite())
-                        {
-                            if let Some(tile_attach_direction) = tile_attach_direction {
-                                if tile_attach_direction != attach_direction {
-                                    return false;
-                                }
-                            } else {
-                                return false;
-                            }
-                        } else {
-                            return false;
-                        }
+                        }) if *tile_attach_direction == attach_direction => {}
+                        _ => return false,
                     }
-                    for direction in direction.get_forward_and_orthogonal() {
-                        if let Some(next_tile) = self.get_tile(pos + direction.to_ivec3()) {
-                            let our_next = tile.get_next(direction);
-                            let their_next = next_tile.get_next(direction.get_opposite());
-
-                            match (our_next, their_next) {
-                                (
-                                    Some(Connection { name: our_name, .. }),
-                                    Some(Connection {
-                                        name: their_name, ..
-                                    }),
-                                ) => {
-                                    if our_name != their_name {
-                                        return false;
-                                    }
-                                }
-                                (None, None) => {}
-                                _ => return false,
-                            }
-                        }
-                    }
-                    true
-                })
-                .collect();
-
-            // There should be at least one tile left
-            if tiles.is_empty() {
-                return None;
-            }
+                }
 
-            // We're done!
-            return Some((pos, direction, tiles));
-        } else {
+                // The tile has to fit and match the tiles around every
+                // position it takes up
+                let Some(entry) = tile.get_next(direction.get_opposite()) else {
+                    return false;
+                };
+                let anchor = pos - entry.cell.as_ivec3();
+                self.fits(anchor, tile) && self.matches_neighbors(anchor, tile)
+            })
+            .collect();
+
+        // There should be at least one tile left
+        if tiles.is_empty() {
             return None;
         }
+
+        // We're done!
+        Some((pos, direction, tiles))
     }
 
     /// The dfs function is the main driver of the path generation.
@@ -743,29 +1236,47 @@ impl ComplexGenerator {
     /// doesn't lead to the end, the function calls itself recursively with the
     /// new position, direction, and possible tiles. If none of the tiles lead
     /// to a valid path, the function backtracks by removing the current
-    /// position from the grid and adding it to the visited set.
+    /// position from the grid and adding it to the visited set, together with
+    /// what else the dead end depends on (see `visited_key`).
     ///
     /// Stops when it reaches the end of the grid (max.z).
     fn dfs(
         &mut self,
         current_pos: BlockPos, // doesn't exist in the grid
         current_direction: Direction,
-        mut current_tiles: Vec<ComplexTile>,
-        visited: &mut HashSet<BlockPos>,
-    ) -> Option<BlockPos> {
-        let mut rng = rand::thread_rng();
-        current_tiles.shuffle(&mut rng);
-        for tile in current_tiles {
-            let Connection {
-                next_direction: direction,
-                ..
-            } = tile
-                .get_next(current_direction.get_opposite())
-                .expect("Tile should have been filtered out if it doesn't have a connection");
-            let Connection { name, .. } = tile
-                .get_next(direction)
-                .expect("If the tile has a connection, it should have a name");
-            let pos = current_pos + direction.to_ivec3();
+        current_tiles: Vec<usize>,
+        visited: &mut HashSet<(BlockPos, Vec<usize>)>,
+    ) -> Result<Option<BlockPos>, ComplexGenError> {
+        let tile_set = self.tile_set.clone();
+
+        for i in weighted_shuffle(&tile_set, current_tiles) {
+            let tile = &tile_set.tiles[i];
+            if !self.can_use(tile) {
+                continue;
+            }
+
+            let Some(entry) = tile.get_next(current_direction.get_opposite()) else {
+                return Err(ComplexGenError::InvalidTile {
+                    index: tile.index,
+                    error: format!(
+                        "It has no connection to the {:?}",
+                        current_direction.get_opposite()
+                    ),
+                });
+            };
+            let anchor = current_pos - entry.cell.as_ivec3();
+            let direction = entry.next_direction;
+            let Some(exit) = tile.get_next(direction) else {
+                return Err(ComplexGenError::InvalidTile {
+                    index: tile.index,
+                    error: format!(
+                        "It leads to the {:?} but has no connection there",
+                        direction
+                    ),
+                });
+            };
+            let name = &exit.name;
+            let pos = anchor + exit.cell.as_ivec3() + direction.to_ivec3();
             if pos.x < self.min_pos.x
                 || pos.y < self.min_pos.y
                 || pos.z < self.min_pos.z
@@ -776,120 +1287,474 @@ impl ComplexGenerator {
                 continue;
             }
 
-            if visited.contains(&pos) {
+            if visited.contains(&self.visited_key(pos)) || !self.fits(anchor, tile) {
                 continue;
             }
 
             if pos.z == self.max_pos.z {
-                // We're done!
-                self.tile_grid.insert(current_pos, tile);
-                return Some(current_pos);
+                self.insert_tile(anchor, i);
+                if self.is_complete() {
+                    // We're done!
+                    return Ok(Some(current_pos));
+                }
+                self.remove_tile(anchor);
+                continue;
             }
 
-            self.tile_grid.insert(current_pos, tile);
-            match self.get_placement(current_pos, current_direction, name) {
-                Some((new_pos, new_direction, new_tiles)) => {
-                    if let Some(t) = self.dfs(new_pos, new_direction, new_tiles, visited) {
-                        return Some(t);
-                    }
+            if self
+                .max_length
+                .is_some_and(|max| self.tile_grid.len() + 1 >= max)
+            {
+                // The path can't reach the end with the tiles it has left.
+                continue;
+            }
+
+            self.insert_tile(anchor, i);
+            if let Some((new_pos, new_direction, new_tiles)) =
+                self.get_placement(current_pos, current_direction, name)
+            {
+                if let Some(t) = self.dfs(new_pos, new_direction, new_tiles, visited)? {
+                    return Ok(Some(t));
                 }
-                None => {}
             }
-            self.tile_grid.remove(&current_pos);
-            visited.insert(pos);
+            self.remove_tile(anchor);
+            visited.insert(self.visited_key(pos));
+        }
+        Ok(None)
+    }
+
+    /// Whether a path can still be finished from a position doesn't only
+    /// depend on the position. It also depends on how often the tiles with
+    /// `max_uses` or `required` have been used and, if the length of the path
+    /// is limited, on how long it is. A dead end is only skipped again when
+    /// all of them are the same.
+    fn visited_key(&self, pos: BlockPos) -> (BlockPos, Vec<usize>) {
+        let mut counts: Vec<usize> = self
+            .tile_set
+            .limited
+            .iter()
+            .map(|index| self.uses.get(index).copied().unwrap_or(0))
+            .collect();
+
+        if self.min_length > 1 || self.max_length.is_some() {
+            counts.push(self.tile_grid.len());
+        }
+
+        (pos, counts)
+    }
+
+    /// Whether the tile can be placed without going over its `max_uses`.
+    pub fn can_use(&self, tile: &ComplexTile) -> bool {
+        tile.weight > 0.0
+            && tile.max_uses.map_or(true, |max| {
+                self.uses.get(&tile.index).copied().unwrap_or(0) < max
+            })
+    }
+
+    /// Whether the path in `tile_grid` is long enough and uses every required
+    /// tile.
+    fn is_complete(&self) -> bool {
+        self.tile_grid.len() >= self.min_length
+            && self
+                .tile_set
+                .required
+                .iter()
+                .all(|index| self.uses.get(index).is_some_and(|uses| *uses > 0))
+    }
+
+    /// Places a tile with its first tile position at `pos`.
+    pub fn insert_tile(&mut self, pos: BlockPos, tile: usize) {
+        self.remove_tile(pos);
+
+        let placed = &self.tile_set.tiles[tile];
+        *self.uses.entry(placed.index).or_insert(0) += 1;
+        for cell in placed.cell_positions() {
+            self.occupied.insert(pos + cell.as_ivec3(), pos);
+        }
+        self.tile_grid.insert(pos, tile);
+    }
+
+    fn remove_tile(&mut self, pos: BlockPos) {
+        if let Some(tile) = self.tile_grid.remove(&pos) {
+            let placed = &self.tile_set.tiles[tile];
+            *self.uses.entry(placed.index).or_insert(1) -= 1;
+            for cell in placed.cell_positions() {
+                self.occupied.remove(&(pos + cell.as_ivec3()));
+            }
         }
-        None
     }
 
-    pub fn generate_dfs(&mut self) -> Option<BlockPos> {
+    pub fn generate_dfs(&mut self) -> Result<Option<BlockPos>, ComplexGenError> {
         let mut visited = HashSet::new();
         let current_pos = BlockPos::new(0, 0, 0);
         let current_direction = Direction::South;
-        let current_tiles = self.starting_tiles.clone();
+        let current_tiles = self.tile_set.starting_tiles.clone();
 
-        return self.dfs(current_pos, current_direction, current_tiles, &mut visited);
+        self.dfs(current_pos, current_direction, current_tiles, &mut visited)
     }
 
-    pub fn get_block_segments(&self) -> Vec<Vec<BlockPos>> {
+    /// Runs the backend until it finds a path, at most `tries` times.
+    /// Returns where the path leaves the grid.
+    pub fn generate_path(&mut self, tries: usize) -> Result<ComplexExit, ComplexGenError> {
+        for _ in 0..tries {
+            self.tile_grid.clear();
+            self.occupied.clear();
+            self.uses.clear();
+            let end = match self.backend {
+                ComplexBackend::Dfs => self.generate_dfs()?,
+                ComplexBackend::Wfc => self.generate_wfc(),
+            };
+            if let Some(exit) = end.and_then(|_| self.get_exit()) {
+                return Ok(exit);
+            }
+        }
+
+        Err(ComplexGenError::RetriesExhausted(tries))
+    }
+
+    /// Follows the path from the start to its last tile and returns where it
+    /// leaves the grid. The exit block is the block of the last connection
+    /// that is furthest in the exit direction. If that connection has no
+    /// blocks, the blocks of the connection the path came in through are used.
+    pub fn get_exit(&self) -> Option<ComplexExit> {
+        let mut current_pos = BlockPos::new(0, 0, 0);
+        let mut current_direction = Direction::South;
+        let mut last = None;
+
+        while let Some((anchor, tile)) = self.get_tile(current_pos) {
+            let entry = tile
+                .get_next(current_direction.get_opposite())
+                .filter(|c| anchor + c.cell.as_ivec3() == current_pos)?;
+            let next_direction = entry.next_direction;
+
+            let exit = tile.get_next(next_direction)?;
+            let blocks = exit.blocks.as_ref().or(entry.blocks.as_ref());
+            last = Some((anchor, next_direction, blocks));
+
+            current_pos = anchor + exit.cell.as_ivec3() + next_direction.to_ivec3();
+            current_direction = next_direction;
+        }
+
+        let (anchor, direction, blocks) = last?;
+        let forward = direction.to_ivec3();
+        let block = blocks?
+            .iter()
+            .max_by_key(|b| (b.as_ivec3().dot(forward), b.y, b.z, b.x))?;
+
+        Some(ComplexExit {
+            pos: anchor.mul_block_pos(self.tile_size) + block.as_ivec3(),
+            direction,
+        })
+    }
+
+    /// Follows the path from the start and groups the blocks of its
+    /// connections into the segments the player jumps between.
+    pub fn get_block_segments(&self) -> Result<Vec<Vec<BlockPos>>, ComplexGenError> {
         let mut segments = Vec::new();
         let mut current_segment = Vec::new();
         let mut current_pos = BlockPos::new(0, 0, 0);
         let mut current_direction = Direction::South;
 
-        loop {
-            if let Some(tile) = self.get_tile(current_pos) {
-                if let Some(Connection {
-                    next_direction,
-                    blocks,
-                    ..
-                }) = tile.get_next(current_direction.get_opposite())
-                {
-                    let has_blocks = if let Some(blocks) = blocks {
-                        current_segment.extend(
-                            blocks
-                                .iter()
-                                .map(|b| current_pos.mul_block_pos(self.tile_size) + b.as_ivec3()),
-                        );
-                        true
-                    } else {
-                        false
-                    };
-                    if let Some(Connection { blocks, .. }) = tile.get_next(next_direction) {
-                        if let Some(blocks) = blocks {
-                            if has_blocks {
-                                segments.push(current_segment);
-                                current_segment = Vec::new();
-                            }
-                            current_segment.extend(
-                                blocks
-                                    .iter()
-                                    .map(|b| current_pos.mul_block_pos(self.tile_size) + b.as_ivec3()),
-                            );
-                        }
-                    }
-                    current_pos = current_pos + next_direction.to_ivec3();
-                    current_direction = next_direction;
-                    continue;
-                } else {
-                    panic!("Tile should have been filtered out if it doesn't have a connection");
-                }
+        while let Some((anchor, tile)) = self.get_tile(current_pos) {
+            let Some(Connection {
+                next_direction,
+                blocks,
+                ..
+            }) = tile.get_next(current_direction.get_opposite())
+            else {
+                return Err(ComplexGenError::InvalidTile {
+                    index: tile.index,
+                    error: format!(
+                        "It has no connection to the {:?}",
+                        current_direction.get_opposite()
+                    ),
+                });
+            };
+
+            let offset = anchor.mul_block_pos(self.tile_size);
+            let has_blocks = if let Some(blocks) = blocks {
+                current_segment.extend(blocks.iter().map(|b| offset + b.as_ivec3()));
+                true
             } else {
-                if !current_segment.is_empty() {
+                false
+            };
+            let Some(exit) = tile.get_next(*next_direction) else {
+                break;
+            };
+            if let Some(blocks) = &exit.blocks {
+                if has_blocks {
                     segments.push(current_segment);
+                    current_segment = Vec::new();
                 }
-                break;
+                current_segment.extend(blocks.iter().map(|b| offset + b.as_ivec3()));
             }
+            current_pos = anchor + exit.cell.as_ivec3() + next_direction.to_ivec3();
+            current_direction = *next_direction;
         }
-        segments
+
+        if !current_segment.is_empty() {
+            segments.push(current_segment);
+        }
+        Ok(segments)
+    }
+}
+
+/// Orders the tiles randomly, with tiles with a higher weight more likely to
+/// come first. Tiles with a weight of 0 are left out.
+fn weighted_shuffle(tile_set: &ComplexTileSet, tiles: Vec<usize>) -> Vec<usize> {
+    let mut weighted: WeightedVec<usize> = tiles
+        .into_iter()
+        .map(|i| (i, tile_set.tiles[i].weight))
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect();
+
+    let mut tiles = Vec::with_capacity(weighted.len());
+    while !weighted.is_empty() {
+        let index = weighted.get_random_index().unwrap_or(weighted.len() - 1);
+        tiles.push(weighted.vec.remove(index).0);
     }
+    tiles
 }
 
-impl BlockGenerator for ComplexGenerator {
-    fn generate(&self, params: &BlockGenParams) -> GenerateResult {
+impl ComplexGenerator {
+    /// Places the blocks of the path found by `generate_path`. Unlike the other
+    /// generators this can fail, if a tile doesn't connect the way the path
+    /// needs it to.
+    pub fn generate(&self, params: &BlockGenParams) -> Result<GenerateResult, ComplexGenError> {
         let mut blocks = HashMap::new();
         let mut children = Vec::new();
 
-        for (pos, tile) in &self.tile_grid {
+        // Every tile draws from the RNG, so they are placed in a fixed order.
+        let mut tiles: Vec<_> = self.tile_grid.iter().collect();
+        tiles.sort_by_key(|(pos, _)| (pos.y, pos.z, pos.x));
+
+        for (pos, tile) in tiles {
             let pos = pos.mul_block_pos(self.tile_size);
 
-            tile.place(&mut blocks, &params.block_map.rebuild(), pos);
+            self.tile_set.tiles[*tile].place(&mut blocks, &params.block_map.rebuild(), pos);
         }
 
-        let segments = self.get_block_segments();
+        let segments = self.get_block_segments()?;
 
         for segment in segments {
             children.push(ChildGeneration::check_blocks(segment.into_iter().collect()));
         }
 
-        children[0].reached = true;
+        if let Some(first) = children.first_mut() {
+            first.reached = true;
+        }
 
-        GenerateResult {
+        Ok(GenerateResult {
             start: BlockPos::new(0, 0, 0),
             end: BlockPos::new(0, 0, 0),
             blocks,
             children,
             alt_blocks: HashMap::new(),
             lines: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random;
+
+    use super::*;
+
+    const TILE_SIZE: BlockPos = BlockPos::new(3, 1, 3);
+
+    /// A tile that goes straight from north to south.
+    fn straight_tile() -> ComplexTile {
+        ComplexTile {
+            connection_north: Some(Connection {
+                name: "straight".to_owned(),
+                next_direction: Direction::South,
+                blocks: Some(HashSet::from([BlockPos::new(0, 0, 0)])),
+                ..Default::default()
+            }),
+            connection_south: Some(Connection {
+                name: "straight".to_owned(),
+                next_direction: Direction::North,
+                blocks: Some(HashSet::from([BlockPos::new(0, 0, 2)])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Every block of a tile position of `origin`.
+    fn blocks(origin: TileOrigin) -> Vec<BlockPos> {
+        let size = origin.tile_size;
+        let min_x = -(size.x / 2);
+        let mut blocks = Vec::new();
+        for x in min_x..min_x + size.x {
+            for z in 0..size.z {
+                blocks.push(BlockPos::new(x, 0, z));
+            }
         }
+        blocks
+    }
+
+    /// Generates paths four tiles long with a few seeds and returns how often
+    /// each tile was used in every one of them.
+    fn generate_uses(
+        tiles: Vec<ComplexTile>,
+        backend: ComplexBackend,
+    ) -> Vec<HashMap<usize, usize>> {
+        (0..20)
+            .map(|seed| {
+                let mut gen = ComplexGenerator::new(
+                    tiles.clone(),
+                    TILE_SIZE,
+                    BlockPos::new(0, 0, 0),
+                    BlockPos::new(0, 0, 4),
+                )
+                .unwrap()
+                .with_backend(backend);
+
+                random::with_rng(&mut random::seeded_rng(seed), || gen.generate_path(200)).unwrap();
+
+                let mut uses = HashMap::new();
+                for tile in gen.tile_grid.values() {
+                    *uses.entry(gen.tile_set.tiles[*tile].index).or_insert(0) += 1;
+                }
+                assert_eq!(uses, gen.uses);
+                uses
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotate_cw_four_times_is_identity() {
+        for size in [3, 4, 5] {
+            let origin = TileOrigin::new(BlockPos::new(size, 1, size));
+            let blocks = blocks(origin);
+
+            for pos in &blocks {
+                let mut rotated = *pos;
+                for _ in 0..4 {
+                    rotated = origin.rotate_cw(rotated);
+                    assert!(blocks.contains(&rotated), "{:?} left the tile", rotated);
+                }
+                assert_eq!(rotated, *pos);
+            }
+        }
+    }
+
+    #[test]
+    fn rotate_cw_turns_clockwise() {
+        let origin = TileOrigin::new(BlockPos::new(5, 1, 5));
+
+        // The north west corner goes to the north east corner.
+        assert_eq!(
+            origin.rotate_cw(BlockPos::new(-2, 0, 0)),
+            BlockPos::new(2, 0, 0)
+        );
+        assert_eq!(
+            origin.rotate_cw(BlockPos::new(2, 0, 0)),
+            BlockPos::new(2, 0, 4)
+        );
+        assert_eq!(
+            origin.rotate_cw(BlockPos::new(0, 3, 2)),
+            BlockPos::new(0, 3, 2)
+        );
+    }
+
+    #[test]
+    fn rotate_180_is_two_quarter_turns() {
+        for size in [3, 4] {
+            let origin = TileOrigin::new(BlockPos::new(size, 1, size));
+
+            for pos in blocks(origin) {
+                assert_eq!(
+                    origin.rotate_180(pos),
+                    origin.rotate_cw(origin.rotate_cw(pos))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flip_x_twice_is_identity() {
+        let origin = TileOrigin::new(BlockPos::new(4, 1, 3));
+
+        for pos in blocks(origin) {
+            assert!(blocks(origin).contains(&origin.flip_x(pos)));
+            assert_eq!(origin.flip_x(origin.flip_x(pos)), pos);
+        }
+    }
+
+    #[test]
+    fn rotate_cw_in_moves_the_tile_positions() {
+        let origin = TileOrigin::new(TILE_SIZE);
+        let mut cells = BlockPos::new(1, 1, 2);
+        let start = BlockPos::new(-1, 0, 5);
+        let mut pos = start;
+
+        for _ in 0..4 {
+            pos = origin.rotate_cw_in(cells, pos);
+            cells = BlockPos::new(cells.z, cells.y, cells.x);
+
+            let (cell, local) = origin.split(pos);
+            assert!(
+                cell.x < cells.x && cell.z < cells.z,
+                "{:?} left the tile",
+                pos
+            );
+            assert!(blocks(origin).contains(&local));
+        }
+        assert_eq!(pos, start);
+
+        // The far corner of the second tile position ends up in the first
+        // position of the rotated tile, which is 2 positions wide.
+        assert_eq!(
+            origin.rotate_cw_in(BlockPos::new(1, 1, 2), start),
+            BlockPos::new(-1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn max_uses_is_never_exceeded() {
+        let limited = ComplexTile {
+            weight: 100.0,
+            max_uses: Some(1),
+            ..straight_tile()
+        };
+
+        for backend in [ComplexBackend::Dfs, ComplexBackend::Wfc] {
+            for uses in generate_uses(vec![limited.clone(), straight_tile()], backend) {
+                assert!(uses.get(&0).copied().unwrap_or(0) <= 1, "{:?}", uses);
+                assert_eq!(uses.values().sum::<usize>(), 4);
+            }
+        }
+    }
+
+    #[test]
+    fn required_tiles_are_used() {
+        let required = ComplexTile {
+            weight: 0.05,
+            required: true,
+            ..straight_tile()
+        };
+
+        for backend in [ComplexBackend::Dfs, ComplexBackend::Wfc] {
+            for uses in generate_uses(vec![straight_tile(), required.clone()], backend) {
+                assert!(uses.get(&1).copied().unwrap_or(0) >= 1, "{:?}", uses);
+            }
+        }
+    }
+
+    #[test]
+    fn required_tiles_have_to_be_usable() {
+        let unusable = ComplexTile {
+            max_uses: Some(0),
+            required: true,
+            ..straight_tile()
+        };
+
+        assert!(matches!(
+            ComplexTileSet::new(vec![straight_tile(), unusable], TILE_SIZE),
+            Err(ComplexGenError::InvalidTile { index: 1, .. })
+        ));
     }
 }
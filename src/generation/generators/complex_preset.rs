@@ -0,0 +1,387 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use serde::{Deserialize, Serialize};
+use valence::prelude::*;
+
+use crate::generation::{
+    block_grid::{BlockGrid, BlockProperties},
+    generator::{BlockGenParams, GenerateResult},
+    schematic::{SchematicGrid, SchematicMapping},
+};
+
+use super::{
+    complex_gen::{
+        ComplexExit, ComplexGenError, ComplexGenerator, ComplexTile, ComplexTileSet, Connection,
+        Direction,
+    },
+    complex_wfc::ComplexBackend,
+};
+
+/// The directory complex presets are loaded from. Every `.json` file in it is
+/// one preset.
+pub const COMPLEX_PRESETS_DIR: &str = "presets/complex";
+
+/// The `ComplexPreset` struct describes a complex generation. A fresh
+/// `ComplexGenerator` is built from it for every generation, so no path is
+/// shared between them. The tile set is only built once and shared by every
+/// copy of the preset.
+///
+/// Properties:
+///
+/// * `tile_size`: The size of a tile position. Tiles take up one or more
+/// positions. If the positions aren't square, tiles are only rotated by 180
+/// degrees, since a quarter turn wouldn't fit them.
+/// * `min_pos`: The lowest tile position the path can go to.
+/// * `max_pos`: The highest tile position the path can go to.
+/// * `tiles`: The tiles the path is made of. Their rotations and mirrored
+/// versions are added when the generator is built.
+/// * `min_length`: The least number of tiles a path can have.
+/// * `max_length`: The most tiles a path can have, if limited.
+/// * `backend`: How the path is found.
+/// * `tile_set`: The tile set built from `tiles` the first time a generator
+/// is built.
+#[derive(Clone, Debug)]
+pub struct ComplexPreset {
+    pub tile_size: BlockPos,
+    pub min_pos: BlockPos,
+    pub max_pos: BlockPos,
+    pub tiles: Arc<Vec<ComplexTile>>,
+    pub min_length: usize,
+    pub max_length: Option<usize>,
+    pub backend: ComplexBackend,
+    tile_set: Arc<OnceLock<Result<Arc<ComplexTileSet>, ComplexGenError>>>,
+}
+
+impl ComplexPreset {
+    pub fn new(
+        tiles: Vec<ComplexTile>,
+        tile_size: BlockPos,
+        min_pos: BlockPos,
+        max_pos: BlockPos,
+    ) -> Self {
+        Self {
+            tile_size,
+            min_pos,
+            max_pos,
+            tiles: Arc::new(tiles),
+            min_length: 1,
+            max_length: None,
+            backend: ComplexBackend::Dfs,
+            tile_set: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Limits how many tiles a path can have.
+    pub fn with_length(mut self, min_length: usize, max_length: Option<usize>) -> Self {
+        self.min_length = min_length;
+        self.max_length = max_length;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: ComplexBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Checks that the bounds contain the starting tile and that the tiles
+    /// make a valid tile set.
+    pub fn validate(&self) -> Result<(), ComplexGenError> {
+        let (min, max) = (self.min_pos, self.max_pos);
+        if !(min.x..=max.x).contains(&0) || !(min.y..=max.y).contains(&0) || min.z > 0 || max.z < 1
+        {
+            return Err(ComplexGenError::InvalidBounds {
+                min_pos: min,
+                max_pos: max,
+            });
+        }
+
+        self.tile_set().map(|_| ())
+    }
+
+    /// Gets the tile set of the preset, building it the first time.
+    pub fn tile_set(&self) -> Result<Arc<ComplexTileSet>, ComplexGenError> {
+        self.tile_set
+            .get_or_init(|| ComplexTileSet::new(self.tiles.to_vec(), self.tile_size).map(Arc::new))
+            .clone()
+    }
+
+    /// Builds a new generator from the preset, after checking it with
+    /// `validate`.
+    pub fn build(&self) -> Result<ComplexGenerator, ComplexGenError> {
+        self.validate()?;
+        ComplexGenerator::from_tile_set(self.tile_set()?, self.min_pos, self.max_pos)?
+            .with_length(self.min_length, self.max_length)
+            .map(|gen| gen.with_backend(self.backend))
+    }
+
+    /// Builds a new generator, finds a path and places its blocks. Also
+    /// returns where the path leaves the grid.
+    pub fn generate(
+        &self,
+        params: &BlockGenParams,
+        tries: usize,
+    ) -> Result<(GenerateResult, ComplexExit), ComplexGenError> {
+        let mut gen = self.build()?;
+        let exit = gen.generate_path(tries)?;
+        Ok((gen.generate(params)?, exit))
+    }
+
+    /// Loads and validates a preset from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let file: ComplexPresetFile = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+        let dir = path.as_ref().parent().unwrap_or(Path::new("."));
+        let preset = file.into_preset(dir)?;
+        preset.build().map_err(|e| e.to_string())?;
+        Ok(preset)
+    }
+
+    /// Loads every preset in `COMPLEX_PRESETS_DIR`, sorted by file name so the
+    /// order is always the same. Presets that fail to load are skipped.
+    pub fn load_all() -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(COMPLEX_PRESETS_DIR) else {
+            return Vec::new();
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .collect();
+        paths.sort();
+
+        paths
+            .into_iter()
+            .filter_map(|path| match Self::load(&path) {
+                Ok(preset) => Some(preset),
+                Err(e) => {
+                    println!("Failed to load {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// The `ComplexPresetFile` struct is how a `ComplexPreset` is stored on disk.
+/// Positions are `[x, y, z]` arrays. Tiles can be loaded from schematics,
+/// which are turned into theme blocks with `mapping`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComplexPresetFile {
+    pub tile_size: [i32; 3],
+    pub min_pos: [i32; 3],
+    pub max_pos: [i32; 3],
+    pub tiles: Vec<TileFile>,
+    #[serde(default = "default_min_length")]
+    pub min_length: usize,
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub backend: ComplexBackend,
+    #[serde(default)]
+    pub mapping: SchematicMapping,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TileFile {
+    pub north: Option<ConnectionFile>,
+    pub south: Option<ConnectionFile>,
+    pub west: Option<ConnectionFile>,
+    pub east: Option<ConnectionFile>,
+    pub up: Option<ConnectionFile>,
+    pub down: Option<ConnectionFile>,
+    pub blocks: Vec<BlockFile>,
+    /// How many tile positions the tile takes up on each axis.
+    pub cells: [i32; 3],
+    pub disable_flip: bool,
+    pub disable_rotate: bool,
+    pub weight: f32,
+    pub max_uses: Option<usize>,
+    pub required: bool,
+    /// A schematic with the blocks of the tile, relative to the preset file.
+    /// Its x = 0 is the lowest x of the tile.
+    pub schematic: Option<PathBuf>,
+}
+
+impl Default for TileFile {
+    fn default() -> Self {
+        Self {
+            north: None,
+            south: None,
+            west: None,
+            east: None,
+            up: None,
+            down: None,
+            blocks: Vec::new(),
+            cells: [1, 1, 1],
+            disable_flip: false,
+            disable_rotate: false,
+            weight: 1.0,
+            max_uses: None,
+            required: false,
+            schematic: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionFile {
+    pub name: String,
+    pub next_direction: Direction,
+    #[serde(default = "default_true")]
+    pub can_next: bool,
+    #[serde(default = "default_true")]
+    pub can_start: bool,
+    #[serde(default)]
+    pub blocks: Option<Vec<[i32; 3]>>,
+    #[serde(default)]
+    pub attach_direction: Option<Direction>,
+    /// The tile position of the tile the connection is on, for tiles that take
+    /// up more than one.
+    #[serde(default)]
+    pub cell: [i32; 3],
+    /// The name of a connection marker in the tile's schematic. The blocks
+    /// below those markers become the blocks of the connection.
+    #[serde(default)]
+    pub marker: Option<String>,
+}
+
+/// A block of a tile. `block` is the name of a block collection in the
+/// theme, and `properties` are block state properties like `"facing": "north"`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockFile {
+    pub pos: [i32; 3],
+    pub block: String,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_min_length() -> usize {
+    1
+}
+
+fn to_block_pos([x, y, z]: [i32; 3]) -> BlockPos {
+    BlockPos::new(x, y, z)
+}
+
+impl ComplexPresetFile {
+    /// Converts the file into a preset. Schematics are loaded relative to
+    /// `dir`.
+    pub fn into_preset(self, dir: &Path) -> Result<ComplexPreset, String> {
+        let tile_size = to_block_pos(self.tile_size);
+        let tiles = self
+            .tiles
+            .into_iter()
+            .map(|tile| tile.into_tile(dir, tile_size, &self.mapping))
+            .collect::<Result<_, _>>()?;
+
+        Ok(ComplexPreset::new(
+            tiles,
+            tile_size,
+            to_block_pos(self.min_pos),
+            to_block_pos(self.max_pos),
+        )
+        .with_length(self.min_length, self.max_length)
+        .with_backend(self.backend))
+    }
+}
+
+impl TileFile {
+    fn into_tile(
+        self,
+        dir: &Path,
+        tile_size: BlockPos,
+        mapping: &SchematicMapping,
+    ) -> Result<ComplexTile, String> {
+        let schematic = match &self.schematic {
+            Some(path) => Some(
+                SchematicGrid::load(dir.join(path), mapping)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?
+                    .offset(BlockPos::new(-(tile_size.x / 2), 0, 0)),
+            ),
+            None => None,
+        };
+
+        let connection = |file: Option<ConnectionFile>| {
+            file.map(|file| file.into_connection(schematic.as_ref()))
+                .transpose()
+        };
+        let connection_north = connection(self.north)?;
+        let connection_south = connection(self.south)?;
+        let connection_west = connection(self.west)?;
+        let connection_east = connection(self.east)?;
+        let connection_up = connection(self.up)?;
+        let connection_down = connection(self.down)?;
+
+        let mut blocks = schematic
+            .as_ref()
+            .map(|schematic| schematic.grid.blocks.clone())
+            .unwrap_or_default();
+        for block in self.blocks {
+            let mut properties = Vec::new();
+            for (name, value) in &block.properties {
+                let prop_name = PropName::from_str(name)
+                    .ok_or_else(|| format!("Unknown block property {}", name))?;
+                let prop_value = PropValue::from_str(value)
+                    .ok_or_else(|| format!("Unknown block property value {}", value))?;
+                properties.push((prop_name, prop_value));
+            }
+
+            blocks.insert(
+                to_block_pos(block.pos),
+                BlockProperties::new(block.block, properties),
+            );
+        }
+
+        Ok(ComplexTile {
+            connection_north,
+            connection_south,
+            connection_west,
+            connection_east,
+            connection_up,
+            connection_down,
+            grid: BlockGrid::new(blocks),
+            cells: to_block_pos(self.cells),
+            disable_flip: self.disable_flip,
+            disable_rotate: self.disable_rotate,
+            weight: self.weight,
+            max_uses: self.max_uses,
+            required: self.required,
+            index: 0,
+        })
+    }
+}
+
+impl ConnectionFile {
+    fn into_connection(self, schematic: Option<&SchematicGrid>) -> Result<Connection, String> {
+        let blocks = match (self.blocks, &self.marker) {
+            (Some(blocks), _) => Some(blocks.into_iter().map(to_block_pos).collect::<HashSet<_>>()),
+            (None, Some(marker)) => Some(
+                schematic
+                    .and_then(|s| s.connections.get(marker))
+                    .cloned()
+                    .ok_or_else(|| format!("No connection markers for {}", marker))?,
+            ),
+            (None, None) => None,
+        };
+
+        Ok(Connection {
+            name: self.name,
+            next_direction: self.next_direction,
+            can_next: self.can_next,
+            can_start: self.can_start,
+            blocks,
+            attach_direction: self.attach_direction,
+            cell: to_block_pos(self.cell),
+        })
+    }
+}
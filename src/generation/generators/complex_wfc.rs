@@ -0,0 +1,469 @@
+// Wave Function Collapse for `ComplexGenerator`. Instead of only placing the
+// tiles of the path, every tile position within the bounds is filled, so the
+// tiles next to the path can be decoration that still matches its connections.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use valence::prelude::*;
+
+use crate::{utils::*, weighted_vec::WeightedVec};
+
+use super::complex_gen::{ComplexGenerator, Connection, Direction, DIRECTIONS};
+
+/// How likely a tile position is to be left empty compared to a tile with a
+/// weight of 1.
+const EMPTY_WEIGHT: f32 = 1.0;
+
+/// How a `ComplexGenerator` finds a path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplexBackend {
+    /// Searches for a path one tile at a time. Only the path is placed.
+    #[default]
+    Dfs,
+    /// Fills every tile position within the bounds with Wave Function
+    /// Collapse, then follows the path from the start. The tiles that aren't
+    /// part of the path are placed as decoration.
+    Wfc,
+}
+
+/// The `Part` struct represents one of the tile positions a tile takes up.
+/// Tiles that take up several tile positions are filled in one position at a
+/// time, and the positions next to a part have to be the rest of its tile.
+///
+/// Properties:
+///
+/// * `tile`: The index of the tile in `ComplexTileSet::tiles`.
+/// * `cell`: Which tile position of the tile it is, relative to the first
+/// one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Part {
+    tile: usize,
+    cell: BlockPos,
+}
+
+impl Part {
+    fn is_first(&self) -> bool {
+        self.cell == BlockPos::new(0, 0, 0)
+    }
+}
+
+/// A tile position while it is being filled. `None` stands for leaving the
+/// position empty, and `Some` for a part of a tile.
+type Options = Vec<Option<Part>>;
+
+impl ComplexGenerator {
+    /// Fills the grid with Wave Function Collapse and returns the position of
+    /// the last tile of the path, or `None` if the grid couldn't be filled or
+    /// the path doesn't reach the end.
+    pub fn generate_wfc(&mut self) -> Option<BlockPos> {
+        self.tile_grid.clear();
+        self.occupied.clear();
+        self.uses.clear();
+
+        let positions = self.wfc_positions();
+        let index: HashMap<BlockPos, usize> =
+            positions.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+        let mut options: Vec<Options> = positions
+            .iter()
+            .map(|pos| self.initial_options(*pos))
+            .collect();
+
+        // Everything has to agree with the starting tile and the edges before
+        // anything is picked.
+        let mut changed: Vec<usize> = (0..positions.len()).collect();
+        if !self.settle(&positions, &index, &mut options, &mut changed) {
+            return None;
+        }
+
+        // Always collapse the position with the fewest options left. Ties go
+        // to the first one, so a seeded RNG fills the grid the same way.
+        while let Some(i) = (0..positions.len())
+            .filter(|i| options[*i].len() > 1)
+            .min_by_key(|i| options[*i].len())
+        {
+            options[i] = vec![self.pick_option(&options[i])?];
+
+            let mut changed = vec![i];
+            if !self.settle(&positions, &index, &mut options, &mut changed) {
+                return None;
+            }
+        }
+
+        // Every tile is placed from its first part, which counts its uses
+        // again.
+        self.uses.clear();
+        for (pos, options) in positions.iter().zip(&options) {
+            if let Some(Some(part)) = options.first() {
+                if part.is_first() {
+                    self.insert_tile(*pos, part.tile);
+                }
+            }
+        }
+
+        self.extract_path()
+    }
+
+    /// Every tile position a path can go through, in a fixed order. The
+    /// `max_pos.z` row is where paths leave the grid, so it is left out.
+    fn wfc_positions(&self) -> Vec<BlockPos> {
+        let mut positions = Vec::new();
+        for z in self.min_pos.z..self.max_pos.z {
+            for y in self.min_pos.y..=self.max_pos.y {
+                for x in self.min_pos.x..=self.max_pos.x {
+                    positions.push(BlockPos::new(x, y, z));
+                }
+            }
+        }
+        positions
+    }
+
+    /// The options a position starts with. The start position has to be a
+    /// starting tile, every tile has to fit within the grid, and no tile can
+    /// have a connection that leaves the grid anywhere but at the end.
+    fn initial_options(&self, pos: BlockPos) -> Options {
+        let start = BlockPos::new(0, 0, 0);
+
+        let mut options: Options = self
+            .tile_set
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| self.can_use(tile))
+            .flat_map(|(i, tile)| tile.cell_positions().map(move |cell| (i, tile, cell)))
+            .filter(|(_, tile, cell)| {
+                let anchor = pos - cell.as_ivec3();
+                tile.cell_positions()
+                    .all(|c| self.in_bounds(anchor + c.as_ivec3()))
+            })
+            .filter(|(_, tile, cell)| {
+                DIRECTIONS.iter().all(|direction| {
+                    let next = pos + direction.to_ivec3();
+                    tile.get_next(*direction).map_or(true, |c| c.cell != *cell)
+                        || self.in_bounds(next)
+                        || next.z == self.max_pos.z
+                        || (pos == start && *direction == Direction::North)
+                })
+            })
+            .filter(|(_, tile, cell)| {
+                pos != start
+                    || tile
+                        .connection_north
+                        .as_ref()
+                        .is_some_and(|c| c.cell == *cell && c.can_next && c.can_start)
+            })
+            .map(|(tile, _, cell)| Some(Part { tile, cell }))
+            .collect();
+
+        if pos != start {
+            options.push(None);
+        }
+
+        options
+    }
+
+    /// Picks one of the options by weight, without going over `max_uses`. The
+    /// weight of a tile is split between its parts, so big tiles aren't
+    /// picked more often.
+    fn pick_option(&self, options: &Options) -> Option<Option<Part>> {
+        let weighted: WeightedVec<Option<Part>> = options
+            .iter()
+            .filter_map(|option| match option {
+                None => Some((None, EMPTY_WEIGHT)),
+                Some(part) if self.can_use(&self.tile_set.tiles[part.tile]) => {
+                    let tile = &self.tile_set.tiles[part.tile];
+                    let cells = tile.cells.x * tile.cells.y * tile.cells.z;
+                    Some((Some(*part), tile.weight / cells as f32))
+                }
+                Some(_) => None,
+            })
+            .collect();
+
+        if weighted.is_empty() {
+            return None;
+        }
+
+        weighted
+            .get_random()
+            .or_else(|| weighted.iter().last())
+            .copied()
+    }
+
+    /// Propagates the changes and keeps `max_uses`. Positions can be left
+    /// with a single tile by propagation as well as by being picked, so the
+    /// uses are counted from every position with only the first part of a
+    /// tile left. Tiles that reached their `max_uses` are removed from the
+    /// other positions, which is propagated again. Returns false if a
+    /// position has no options left or a tile is used too often.
+    fn settle(
+        &mut self,
+        positions: &[BlockPos],
+        index: &HashMap<BlockPos, usize>,
+        options: &mut [Options],
+        changed: &mut Vec<usize>,
+    ) -> bool {
+        loop {
+            if !self.propagate(positions, index, options, changed) {
+                return false;
+            }
+
+            self.uses.clear();
+            for option in options.iter() {
+                if let [Some(part)] = option.as_slice() {
+                    if part.is_first() {
+                        let tile_index = self.tile_set.tiles[part.tile].index;
+                        *self.uses.entry(tile_index).or_insert(0) += 1;
+                    }
+                }
+            }
+
+            let tiles = &self.tile_set.tiles;
+            let over_limit = self.uses.iter().any(|(tile_index, uses)| {
+                tiles
+                    .iter()
+                    .find(|tile| tile.index == *tile_index)
+                    .and_then(|tile| tile.max_uses)
+                    .is_some_and(|max| *uses > max)
+            });
+            if over_limit {
+                return false;
+            }
+
+            for (i, option) in options.iter_mut().enumerate() {
+                if option.len() < 2 {
+                    continue;
+                }
+
+                let before = option.len();
+                option.retain(|part| part.map_or(true, |part| self.can_use(&tiles[part.tile])));
+
+                if option.is_empty() {
+                    return false;
+                }
+                if option.len() != before {
+                    changed.push(i);
+                }
+            }
+
+            if changed.is_empty() {
+                return true;
+            }
+        }
+    }
+
+    /// Removes the options that don't match any option of a neighbor, until
+    /// nothing changes. Returns false if a position has no options left.
+    fn propagate(
+        &self,
+        positions: &[BlockPos],
+        index: &HashMap<BlockPos, usize>,
+        options: &mut [Options],
+        changed: &mut Vec<usize>,
+    ) -> bool {
+        let start = BlockPos::new(0, 0, 0);
+
+        while let Some(i) = changed.pop() {
+            let pos = positions[i];
+
+            for direction in DIRECTIONS {
+                let next_pos = pos + direction.to_ivec3();
+                let Some(&j) = index.get(&next_pos) else {
+                    continue;
+                };
+
+                // The path comes into the start from outside, whatever is
+                // next to it.
+                if (pos == start && direction == Direction::North)
+                    || (next_pos == start && direction == Direction::South)
+                {
+                    continue;
+                }
+
+                let before = options[j].len();
+                let ours = options[i].clone();
+                options[j].retain(|theirs| {
+                    ours.iter()
+                        .any(|ours| self.matches(*ours, *theirs, direction))
+                });
+
+                if options[j].is_empty() {
+                    return false;
+                }
+                if options[j].len() != before {
+                    changed.push(j);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Whether `theirs` can be next to `ours` in `direction`. A part of a tile
+    /// has to be next to the rest of its tile. Otherwise, either both or
+    /// neither have to have a connection facing each other, with the same
+    /// name and the same `attach_direction` for Up and Down.
+    fn matches(&self, ours: Option<Part>, theirs: Option<Part>, direction: Direction) -> bool {
+        let tiles = &self.tile_set.tiles;
+
+        if let Some(ours) = ours {
+            let cell = ours.cell + direction.to_ivec3();
+            if tiles[ours.tile].has_cell(cell) {
+                return theirs == Some(Part { cell, ..ours });
+            }
+        }
+        if let Some(theirs) = theirs {
+            if tiles[theirs.tile].has_cell(theirs.cell - direction.to_ivec3()) {
+                return false;
+            }
+        }
+
+        let get = |part: Option<Part>, direction: Direction| -> Option<&Connection> {
+            part.and_then(|p| {
+                tiles[p.tile]
+                    .get_next(direction)
+                    .filter(|c| c.cell == p.cell)
+            })
+        };
+
+        match (get(ours, direction), get(theirs, direction.get_opposite())) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a.name == b.name
+                    && (direction.is_news() || a.attach_direction == b.attach_direction)
+            }
+            _ => false,
+        }
+    }
+
+    /// Follows the path from the start through `tile_grid`. Returns the
+    /// position of its last tile if it reaches the end and meets the length
+    /// and required tile limits.
+    fn extract_path(&self) -> Option<BlockPos> {
+        let mut pos = BlockPos::new(0, 0, 0);
+        let mut direction = Direction::South;
+        let mut visited = HashSet::new();
+        let mut used = HashSet::new();
+
+        loop {
+            let (_, tile) = self.get_tile(pos)?;
+            let entry = self.get_face(pos, direction.get_opposite())?;
+            if !entry.can_next || !visited.insert(pos) {
+                return None;
+            }
+            used.insert(tile.index);
+
+            let (next, next_direction, _) = self.step(pos, direction)?;
+            if next.z == self.max_pos.z {
+                break;
+            }
+            pos = next;
+            direction = next_direction;
+        }
+
+        let length = visited.len();
+        let long_enough = length >= self.min_length;
+        let short_enough = self.max_length.map_or(true, |max| length <= max);
+        let has_required = self.tile_set.required.iter().all(|i| used.contains(i));
+
+        (long_enough && short_enough && has_required).then_some(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::complex_gen::ComplexTile;
+    use super::*;
+
+    /// A tile that goes straight from north to south.
+    fn straight_tile() -> ComplexTile {
+        ComplexTile {
+            connection_north: Some(Connection {
+                name: "straight".to_owned(),
+                next_direction: Direction::South,
+                blocks: Some(HashSet::from([BlockPos::new(0, 0, 0)])),
+                ..Default::default()
+            }),
+            connection_south: Some(Connection {
+                name: "straight".to_owned(),
+                next_direction: Direction::North,
+                blocks: Some(HashSet::from([BlockPos::new(0, 0, 2)])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A generator with room for a path four tiles long and a column of
+    /// decoration east of it. The second tile is required.
+    fn generator() -> ComplexGenerator {
+        let required = ComplexTile {
+            required: true,
+            ..straight_tile()
+        };
+
+        ComplexGenerator::new(
+            vec![straight_tile(), required],
+            BlockPos::new(3, 1, 3),
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(1, 0, 4),
+        )
+        .unwrap()
+    }
+
+    /// The first version of the tile in the tile set, which goes north to
+    /// south.
+    fn tile(gen: &ComplexGenerator, index: usize) -> usize {
+        gen.tile_set
+            .tiles
+            .iter()
+            .position(|tile| tile.index == index && tile.connection_north.is_some())
+            .unwrap()
+    }
+
+    /// Places a straight path along x = 0 with the given tiles.
+    fn place_path(gen: &mut ComplexGenerator, tiles: &[usize]) {
+        for (z, index) in tiles.iter().enumerate() {
+            let tile = tile(gen, *index);
+            gen.insert_tile(BlockPos::new(0, 0, z as i32), tile);
+        }
+    }
+
+    #[test]
+    fn extract_path_follows_the_path_to_the_end() {
+        let mut gen = generator();
+        place_path(&mut gen, &[0, 1, 0, 0]);
+
+        assert_eq!(gen.extract_path(), Some(BlockPos::new(0, 0, 3)));
+    }
+
+    #[test]
+    fn extract_path_needs_the_path_to_reach_the_end() {
+        let mut gen = generator();
+        place_path(&mut gen, &[0, 1, 0]);
+
+        assert_eq!(gen.extract_path(), None);
+    }
+
+    #[test]
+    fn extract_path_only_counts_required_tiles_on_the_path() {
+        let mut gen = generator();
+        place_path(&mut gen, &[0, 0, 0, 0]);
+        let required = tile(&gen, 1);
+        gen.insert_tile(BlockPos::new(1, 0, 0), required);
+
+        assert_eq!(gen.extract_path(), None);
+    }
+
+    #[test]
+    fn extract_path_checks_the_length() {
+        let mut gen = generator().with_length(1, Some(3)).unwrap();
+        place_path(&mut gen, &[0, 1, 0, 0]);
+        assert_eq!(gen.extract_path(), None);
+
+        let mut gen = generator().with_length(5, None).unwrap();
+        place_path(&mut gen, &[0, 1, 0, 0]);
+        assert_eq!(gen.extract_path(), None);
+    }
+}
@@ -1,19 +1,32 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
 use valence::prelude::*;
 
 use crate::{
     generation::{
-        block_collection::BuiltBlockCollectionMap, block_grid::BlockGrid,
-        generation::ChildGeneration, generator::*,
+        block_collection::BuiltBlockCollectionMap,
+        block_grid::BlockGrid,
+        generation::ChildGeneration,
+        generator::*,
+        schematic::{SchematicGrid, SchematicMapping},
     },
     line::Line3,
     prediction::prediction_state::PredictionState,
+    random,
     utils::*,
     weighted_vec::WeightedVec,
 };
 
+/// The directory single custom presets are loaded from. Every `.json` file in
+/// it is one preset.
+pub const SINGLE_PRESETS_DIR: &str = "presets/single";
+
 /// The `SingleCustomPreset` struct represents a single custom generation preset.
 /// It is used to store the blocks used in a custom generation preset.
 ///
@@ -33,13 +46,56 @@ pub struct SingleCustomPreset {
 }
 
 impl SingleCustomPreset {
+    /// Loads a preset from a JSON file that points to a schematic. The start
+    /// and end are where the start and end markers are.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let file: SingleCustomPresetFile =
+            serde_json::from_str(&data).map_err(|e| e.to_string())?;
+        let dir = path.as_ref().parent().unwrap_or(Path::new("."));
+
+        let schematic = SchematicGrid::load(dir.join(&file.schematic), &file.mapping)
+            .map_err(|e| format!("{}: {}", file.schematic.display(), e))?;
+
+        Ok(Self {
+            blocks: schematic.grid,
+            start_pos: schematic.start.ok_or("Missing start marker")?,
+            end_pos: schematic.end.ok_or("Missing end marker")?,
+        })
+    }
+
+    /// Loads every preset in `SINGLE_PRESETS_DIR`, sorted by file name so the
+    /// order is always the same. Presets that fail to load are skipped.
+    pub fn load_all() -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(SINGLE_PRESETS_DIR) else {
+            return Vec::new();
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .collect();
+        paths.sort();
+
+        paths
+            .into_iter()
+            .filter_map(|path| match Self::load(&path) {
+                Ok(preset) => Some(preset),
+                Err(e) => {
+                    println!("Failed to load {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect()
+    }
+
     fn get_blocks(
         &self,
         offset: BlockPos,
         map: &BuiltBlockCollectionMap,
     ) -> HashMap<BlockPos, BlockState> {
         let mut blocks = HashMap::new();
-        for (pos, props) in self.blocks.blocks.iter() {
+        for (pos, props) in self.blocks.sorted_blocks() {
             blocks.insert(*pos + offset.as_ivec3(), props.get_block(map));
         }
 
@@ -61,6 +117,20 @@ impl BlockGenerator for SingleCustomPreset {
     }
 }
 
+/// The `SingleCustomPresetFile` struct is how a `SingleCustomPreset` is
+/// stored on disk.
+///
+/// Properties:
+///
+/// * `schematic`: The schematic with the blocks, relative to the preset file.
+/// * `mapping`: How the blocks of the schematic become theme blocks.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SingleCustomPresetFile {
+    pub schematic: PathBuf,
+    #[serde(default)]
+    pub mapping: SchematicMapping,
+}
+
 #[derive(Clone, Debug)]
 pub struct SingularMultiCustomPreset {
     pub preset: SingleCustomPreset,
@@ -79,7 +149,7 @@ pub struct MultiCustomPreset {
 
 impl BlockGenerator for MultiCustomPreset {
     fn generate(&self, params: &BlockGenParams) -> GenerateResult {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let mut length = rng.gen_range(self.min_length..=self.max_length);
         let mut children = Vec::new();
         let mut lines = Vec::new();
@@ -110,7 +180,7 @@ impl BlockGenerator for MultiCustomPreset {
                     PredictionState::running_jump_block(BlockPos::new(0, 0, 0), random_yaw());
                 let mut prev_pos = prediction.pos;
 
-                let target_y = rand::thread_rng().gen_range(-1..=1) as f64;
+                let target_y = random::rng().gen_range(-1..=1) as f64;
 
                 loop {
                     let mut new_prediction = prediction.clone();
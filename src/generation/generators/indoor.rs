@@ -11,6 +11,7 @@ use crate::{
     },
     line::Line3,
     prediction::prediction_state::PredictionState,
+    random,
     utils::*,
 };
 
@@ -107,7 +108,7 @@ impl IndoorGenerator {
         platform_level: i32,
         map: &BuiltBlockCollectionMap,
     ) -> BlockPos {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         // TODO: Improve
 
         let start = BlockPos::new(rng.gen_range(1..size.x - 1), platform_level, 0);
@@ -136,7 +137,7 @@ impl IndoorGenerator {
             return prev;
         }
 
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let (min_yaw, max_yaw) = get_min_max_yaw(prev, size);
 
@@ -196,7 +197,7 @@ impl BlockGenerator for IndoorGenerator {
     fn generate(&self, params: &BlockGenParams) -> GenerateResult {
         let map = &params.block_map;
         let mut blocks = HashMap::new();
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let mut size: IVec3 = IVec3::new(rng.gen_range(5..=10), 7, rng.gen_range(15..=30));
 
@@ -7,7 +7,10 @@ use noise::{
 use rand::Rng;
 use valence::prelude::*;
 
-use crate::generation::generator::{BlockGenParams, BlockGenerator, GenerateResult};
+use crate::{
+    generation::generator::{BlockGenParams, BlockGenerator, GenerateResult},
+    random,
+};
 
 pub struct IslandGenerator {
     pub grass: String,
@@ -22,7 +25,7 @@ pub struct IslandGenerator {
 
 impl BlockGenerator for IslandGenerator {
     fn generate(&self, params: &BlockGenParams) -> GenerateResult {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let radius = rng.gen_range(self.min_radius..=self.max_radius);
         let pow = rng.gen_range(self.min_point_power..=self.max_point_power);
 
@@ -1,6 +1,8 @@
 pub use blink_blocks::*;
 pub use cave::*;
 pub use complex_gen::*;
+pub use complex_preset::*;
+pub use complex_wfc::*;
 pub use custom_generation::*;
 pub use indoor::*;
 pub use island::*;
@@ -9,6 +11,8 @@ pub use snake::*;
 pub mod blink_blocks;
 pub mod cave;
 pub mod complex_gen;
+pub mod complex_preset;
+pub mod complex_wfc;
 pub mod custom_generation;
 pub mod indoor;
 pub mod island;
@@ -10,6 +10,7 @@ use crate::{
         generation::ChildGeneration,
         generator::{BlockGenParams, BlockGenerator, GenerateResult},
     },
+    random,
     utils::*,
 };
 
@@ -101,7 +102,7 @@ impl SnakeGenerator {
             BlockPos::new(0, 0, -1),
         ];
 
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let mut i_decided_to_go_down = false;
 
@@ -181,7 +182,7 @@ impl SnakeGenerator {
 
     /// Sets the end by picking a random position furthest in the Z direction.
     pub fn set_end_random(&mut self) {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let mut max = 0;
         let mut max_poses = Vec::new();
         for pos in &self.poses {
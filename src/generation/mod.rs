@@ -1,6 +1,10 @@
 pub mod block_collection;
 pub mod block_grid;
+pub mod course;
+pub mod difficulty;
+pub mod fork;
 pub mod generation;
 pub mod generator;
 pub mod generators;
+pub mod schematic;
 pub mod theme;
@@ -0,0 +1,609 @@
+// Reads Sponge (`.schem`) and vanilla structure (`.nbt`) files so presets can
+// be built in Minecraft instead of being written out block by block.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use valence::{
+    nbt::{self, Compound, List, Value},
+    prelude::*,
+};
+
+use super::block_grid::{BlockGrid, BlockProperties};
+
+/// Blocks that are left out of the grid.
+const AIR_BLOCKS: [&str; 4] = ["air", "cave_air", "void_air", "structure_void"];
+/// The data version of Minecraft 1.20.1, which saved schematics are marked
+/// with.
+const DATA_VERSION: i32 = 3465;
+
+/// The `SchematicBlock` struct represents a block state as it is stored in a
+/// schematic.
+///
+/// Properties:
+///
+/// * `name`: The block id without the `minecraft:` namespace.
+/// * `properties`: The block state properties, like `("facing", "north")`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SchematicBlock {
+    pub name: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl SchematicBlock {
+    /// Parses a block state like `minecraft:oak_stairs[facing=north]`.
+    pub fn parse(state: &str) -> Self {
+        let (name, properties) = match state.split_once('[') {
+            Some((name, properties)) => (name, properties.trim_end_matches(']')),
+            None => (state, ""),
+        };
+
+        Self {
+            name: strip_namespace(name).to_string(),
+            properties: properties
+                .split(',')
+                .filter_map(|p| p.split_once('='))
+                .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn from_state(state: BlockState) -> Self {
+        let kind = state.to_kind();
+        Self {
+            name: kind.to_str().to_string(),
+            properties: kind
+                .props()
+                .iter()
+                .filter_map(|name| {
+                    state
+                        .get(*name)
+                        .map(|value| (name.to_str().to_string(), value.to_str().to_string()))
+                })
+                .collect(),
+        }
+    }
+
+    /// Formats the block state the way schematics store it, like
+    /// `minecraft:oak_stairs[facing=north]`.
+    pub fn to_state_string(&self) -> String {
+        let mut state = format!("minecraft:{}", self.name);
+        if !self.properties.is_empty() {
+            let properties: Vec<_> = self
+                .properties
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            state.push_str(&format!("[{}]", properties.join(",")));
+        }
+        state
+    }
+
+    pub fn is_air(&self) -> bool {
+        AIR_BLOCKS.contains(&self.name.as_str())
+    }
+}
+
+/// The `Schematic` struct represents the blocks of a schematic file.
+///
+/// Properties:
+///
+/// * `size`: The size of the schematic.
+/// * `offset`: Where the schematic's 0, 0, 0 was in the world it was saved
+/// from, so pasting it at the world's 0, 0, 0 puts it back in place. It is
+/// only kept for other tools. Presets are placed by their start marker, so
+/// `to_grid` doesn't use it.
+/// * `blocks`: Every block that isn't air, ordered by y, then z, then x.
+#[derive(Clone, Debug)]
+pub struct Schematic {
+    pub size: BlockPos,
+    pub offset: BlockPos,
+    pub blocks: Vec<(BlockPos, SchematicBlock)>,
+}
+
+impl Schematic {
+    /// Loads a Sponge schematic or a vanilla structure file. Both can be
+    /// compressed with gzip or not.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let data = fs::read(&path).map_err(|e| e.to_string())?;
+        let data = if data.starts_with(&[0x1f, 0x8b]) {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(data.as_slice())
+                .read_to_end(&mut decompressed)
+                .map_err(|e| e.to_string())?;
+            decompressed
+        } else {
+            data
+        };
+
+        let (root, _): (Compound, String) =
+            nbt::from_binary(&mut data.as_slice()).map_err(|e| e.to_string())?;
+
+        if root.contains_key("palette") || root.contains_key("palettes") {
+            Self::from_structure(&root)
+        } else {
+            Self::from_sponge(&root)
+        }
+    }
+
+    /// Reads a Sponge schematic of version 2 or 3.
+    pub fn from_sponge(root: &Compound) -> Result<Self, String> {
+        // Version 3 puts everything in a `Schematic` compound and the blocks in
+        // a `Blocks` compound.
+        let root = match root.get("Schematic") {
+            Some(Value::Compound(schematic)) => schematic,
+            _ => root,
+        };
+        let (palette, data) = match root.get("Blocks") {
+            Some(Value::Compound(blocks)) => (get_compound(blocks, "Palette")?, blocks.get("Data")),
+            _ => (get_compound(root, "Palette")?, root.get("BlockData")),
+        };
+        let Some(Value::ByteArray(data)) = data else {
+            return Err("Missing block data".to_string());
+        };
+
+        let size = BlockPos::new(
+            get_int(root, "Width")?,
+            get_int(root, "Height")?,
+            get_int(root, "Length")?,
+        );
+
+        let mut states = vec![None; palette.len()];
+        for (state, index) in palette.iter() {
+            let Value::Int(index) = index else {
+                return Err(format!("Invalid palette index for {}", state));
+            };
+            let slot = states
+                .get_mut(*index as usize)
+                .ok_or_else(|| format!("Palette index {} is out of range", index))?;
+            *slot = Some(SchematicBlock::parse(state));
+        }
+
+        let indices = read_varints(data)?;
+        if indices.len() != (size.x * size.y * size.z) as usize {
+            return Err("The block data doesn't match the size".to_string());
+        }
+
+        let mut blocks = Vec::new();
+        for (i, index) in indices.into_iter().enumerate() {
+            let i = i as i32;
+            let pos = BlockPos::new(i % size.x, i / (size.x * size.z), (i / size.x) % size.z);
+            let block = states
+                .get(index)
+                .cloned()
+                .flatten()
+                .ok_or_else(|| format!("Palette index {} is out of range", index))?;
+            if !block.is_air() {
+                blocks.push((pos, block));
+            }
+        }
+
+        let offset = match root.get("Offset") {
+            Some(Value::IntArray(offset)) if offset.len() == 3 => {
+                BlockPos::new(offset[0], offset[1], offset[2])
+            }
+            _ => BlockPos::new(0, 0, 0),
+        };
+
+        Ok(Self::new(size, offset, blocks))
+    }
+
+    /// Reads a vanilla structure file. Only the first palette is used.
+    pub fn from_structure(root: &Compound) -> Result<Self, String> {
+        let size = get_pos(root, "size")?;
+
+        let palette = match (root.get("palette"), root.get("palettes")) {
+            (Some(Value::List(List::Compound(palette))), _) => palette,
+            (_, Some(Value::List(List::List(palettes)))) => match palettes.first() {
+                Some(List::Compound(palette)) => palette,
+                _ => return Err("Missing palette".to_string()),
+            },
+            _ => return Err("Missing palette".to_string()),
+        };
+
+        let states = palette
+            .iter()
+            .map(|state| {
+                let Some(Value::String(name)) = state.get("Name") else {
+                    return Err("Missing block name".to_string());
+                };
+                let mut properties = Vec::new();
+                if let Some(Value::Compound(props)) = state.get("Properties") {
+                    for (name, value) in props.iter() {
+                        if let Value::String(value) = value {
+                            properties.push((name.clone(), value.clone()));
+                        }
+                    }
+                }
+                Ok(SchematicBlock {
+                    name: strip_namespace(name).to_string(),
+                    properties,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let Some(Value::List(List::Compound(entries))) = root.get("blocks") else {
+            return Err("Missing blocks".to_string());
+        };
+
+        let mut blocks = Vec::new();
+        for entry in entries {
+            let pos = get_pos(entry, "pos")?;
+            let index = get_int(entry, "state")?;
+            let block = states
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| format!("Palette index {} is out of range", index))?;
+            if !block.is_air() {
+                blocks.push((pos, block));
+            }
+        }
+
+        Ok(Self::new(size, BlockPos::new(0, 0, 0), blocks))
+    }
+
+    fn new(size: BlockPos, offset: BlockPos, mut blocks: Vec<(BlockPos, SchematicBlock)>) -> Self {
+        blocks.sort_by_key(|(pos, _)| (pos.y, pos.z, pos.x));
+        Self {
+            size,
+            offset,
+            blocks,
+        }
+    }
+
+    /// Creates a schematic that just fits around blocks in the world. Air is
+    /// left out. Generations can overlap, like the branches of a fork, so a
+    /// later block replaces an earlier one at the same position, the same way
+    /// it does when they are placed.
+    pub fn from_world_blocks(blocks: impl IntoIterator<Item = (BlockPos, BlockState)>) -> Self {
+        let blocks: HashMap<BlockPos, BlockState> = blocks.into_iter().collect();
+        let blocks: Vec<_> = blocks
+            .into_iter()
+            .map(|(pos, block)| (pos, SchematicBlock::from_state(block)))
+            .filter(|(_, block)| !block.is_air())
+            .collect();
+
+        let Some(min) = blocks
+            .iter()
+            .map(|(pos, _)| *pos)
+            .reduce(|a, b| BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)))
+        else {
+            return Self::new(BlockPos::new(0, 0, 0), BlockPos::new(0, 0, 0), Vec::new());
+        };
+        let max = blocks.iter().map(|(pos, _)| *pos).fold(min, |a, b| {
+            BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+        });
+
+        let size = BlockPos::new(max.x - min.x + 1, max.y - min.y + 1, max.z - min.z + 1);
+        let blocks = blocks
+            .into_iter()
+            .map(|(pos, block)| {
+                (
+                    BlockPos::new(pos.x - min.x, pos.y - min.y, pos.z - min.z),
+                    block,
+                )
+            })
+            .collect();
+
+        Self::new(size, min, blocks)
+    }
+
+    /// Saves the schematic as a gzipped Sponge schematic of version 2, which
+    /// WorldEdit and most other tools can read. Fails if two blocks are at the
+    /// same position or a block is outside of `size`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let (width, height, length) = (self.size.x, self.size.y, self.size.z);
+        if [width, height, length]
+            .iter()
+            .any(|size| *size < 0 || *size > u16::MAX as i32)
+        {
+            return Err("The schematic is too big".to_string());
+        }
+
+        let air = SchematicBlock::parse("air");
+        let mut palette = vec![air.clone()];
+        let mut indices = HashMap::from([(air, 0)]);
+        let mut data = vec![0; (width * height * length) as usize];
+        let mut filled = vec![false; data.len()];
+
+        for (pos, block) in &self.blocks {
+            if pos.x < 0
+                || pos.y < 0
+                || pos.z < 0
+                || pos.x >= width
+                || pos.y >= height
+                || pos.z >= length
+            {
+                return Err(format!(
+                    "The block at {} {} {} is outside of the schematic",
+                    pos.x, pos.y, pos.z
+                ));
+            }
+
+            let i = (pos.x + pos.z * width + pos.y * width * length) as usize;
+            if std::mem::replace(&mut filled[i], true) {
+                return Err(format!(
+                    "There is more than one block at {} {} {}",
+                    pos.x, pos.y, pos.z
+                ));
+            }
+
+            let index = *indices.entry(block.clone()).or_insert_with(|| {
+                palette.push(block.clone());
+                palette.len() - 1
+            });
+            data[i] = index;
+        }
+
+        let mut palette_nbt: Compound = Compound::new();
+        for (index, block) in palette.iter().enumerate() {
+            palette_nbt.insert(block.to_state_string(), Value::Int(index as i32));
+        }
+
+        let mut root: Compound = Compound::new();
+        root.insert("Version", Value::Int(2));
+        root.insert("DataVersion", Value::Int(DATA_VERSION));
+        // Sizes are unsigned shorts
+        root.insert("Width", Value::Short(width as u16 as i16));
+        root.insert("Height", Value::Short(height as u16 as i16));
+        root.insert("Length", Value::Short(length as u16 as i16));
+        root.insert(
+            "Offset",
+            Value::IntArray(vec![self.offset.x, self.offset.y, self.offset.z]),
+        );
+        root.insert("PaletteMax", Value::Int(palette.len() as i32));
+        root.insert("Palette", Value::Compound(palette_nbt));
+        root.insert("BlockData", Value::ByteArray(write_varints(&data)));
+
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let file = fs::File::create(&path).map_err(|e| e.to_string())?;
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        nbt::to_binary(&root, &mut encoder, "Schematic").map_err(|e| e.to_string())?;
+        encoder
+            .finish()
+            .and_then(|mut file| file.flush())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Turns the schematic into a grid of theme blocks. Marker blocks are
+    /// left out and mark the block below them.
+    pub fn to_grid(&self, mapping: &SchematicMapping) -> Result<SchematicGrid, String> {
+        let mut grid = HashMap::new();
+        let mut start = None;
+        let mut end = None;
+        let mut connections: HashMap<String, HashSet<BlockPos>> = HashMap::new();
+
+        for (pos, block) in &self.blocks {
+            let below = BlockPos::new(pos.x, pos.y - 1, pos.z);
+            let name = block.name.as_str();
+
+            if name == strip_namespace(&mapping.start) {
+                if start.replace(below).is_some() {
+                    return Err("More than one start marker".to_string());
+                }
+                continue;
+            }
+            if name == strip_namespace(&mapping.end) {
+                if end.replace(below).is_some() {
+                    return Err("More than one end marker".to_string());
+                }
+                continue;
+            }
+            if let Some(connection) = mapping.get_connection(name) {
+                connections
+                    .entry(connection.to_string())
+                    .or_default()
+                    .insert(below);
+                continue;
+            }
+
+            let collection = mapping
+                .get_collection(name)
+                .ok_or_else(|| format!("No block collection for {}", name))?;
+
+            let mut properties = Vec::new();
+            for (name, value) in &block.properties {
+                let prop_name = PropName::from_str(name)
+                    .ok_or_else(|| format!("Unknown block property {}", name))?;
+                let prop_value = PropValue::from_str(value)
+                    .ok_or_else(|| format!("Unknown block property value {}", value))?;
+                properties.push((prop_name, prop_value));
+            }
+
+            grid.insert(
+                *pos,
+                BlockProperties::new(collection.to_string(), properties),
+            );
+        }
+
+        let marked = start
+            .iter()
+            .chain(end.iter())
+            .chain(connections.values().flatten());
+        for pos in marked {
+            if !grid.contains_key(pos) {
+                return Err(format!(
+                    "The marker at {} {} {} isn't on top of a block",
+                    pos.x,
+                    pos.y + 1,
+                    pos.z
+                ));
+            }
+        }
+
+        Ok(SchematicGrid {
+            grid: BlockGrid::new(grid),
+            start,
+            end,
+            connections,
+        })
+    }
+}
+
+/// The `SchematicMapping` struct describes how the blocks of a schematic
+/// become theme blocks. Block ids can be written with or without the
+/// `minecraft:` namespace.
+///
+/// Properties:
+///
+/// * `palette`: The block collection every block id is placed as.
+/// * `start`: The marker block put on top of the start block.
+/// * `end`: The marker block put on top of the end block.
+/// * `connections`: Marker blocks put on top of the blocks of a connection,
+/// and the name of the connection they mark.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchematicMapping {
+    pub palette: HashMap<String, String>,
+    pub start: String,
+    pub end: String,
+    pub connections: HashMap<String, String>,
+}
+
+impl Default for SchematicMapping {
+    fn default() -> Self {
+        Self {
+            palette: HashMap::new(),
+            start: "lime_wool".to_string(),
+            end: "red_wool".to_string(),
+            connections: HashMap::new(),
+        }
+    }
+}
+
+impl SchematicMapping {
+    pub fn get_collection(&self, block: &str) -> Option<&str> {
+        self.palette
+            .iter()
+            .find(|(id, _)| strip_namespace(id) == block)
+            .map(|(_, collection)| collection.as_str())
+    }
+
+    pub fn get_connection(&self, block: &str) -> Option<&str> {
+        self.connections
+            .iter()
+            .find(|(id, _)| strip_namespace(id) == block)
+            .map(|(_, connection)| connection.as_str())
+    }
+}
+
+/// The `SchematicGrid` struct is a schematic turned into theme blocks.
+///
+/// Properties:
+///
+/// * `grid`: The blocks of the schematic, named after block collections.
+/// * `start`: The block below the start marker, if there is one.
+/// * `end`: The block below the end marker, if there is one.
+/// * `connections`: The blocks below each kind of connection marker, by the
+/// name of the connection.
+#[derive(Clone, Debug)]
+pub struct SchematicGrid {
+    pub grid: BlockGrid,
+    pub start: Option<BlockPos>,
+    pub end: Option<BlockPos>,
+    pub connections: HashMap<String, HashSet<BlockPos>>,
+}
+
+impl SchematicGrid {
+    /// Loads a schematic and turns it into theme blocks.
+    pub fn load(path: impl AsRef<Path>, mapping: &SchematicMapping) -> Result<Self, String> {
+        Schematic::load(path)?.to_grid(mapping)
+    }
+
+    /// Moves every position by `offset`.
+    pub fn offset(self, offset: BlockPos) -> Self {
+        let offset = offset.as_ivec3();
+        Self {
+            grid: self
+                .grid
+                .transform(|pos| pos + offset, |block| block.clone()),
+            start: self.start.map(|pos| pos + offset),
+            end: self.end.map(|pos| pos + offset),
+            connections: self
+                .connections
+                .into_iter()
+                .map(|(name, blocks)| (name, blocks.into_iter().map(|p| p + offset).collect()))
+                .collect(),
+        }
+    }
+}
+
+fn strip_namespace(id: &str) -> &str {
+    id.strip_prefix("minecraft:").unwrap_or(id)
+}
+
+fn get_int(compound: &Compound, key: &str) -> Result<i32, String> {
+    match compound.get(key) {
+        Some(Value::Byte(v)) => Ok(*v as i32),
+        // Sponge sizes are unsigned shorts
+        Some(Value::Short(v)) => Ok(*v as u16 as i32),
+        Some(Value::Int(v)) => Ok(*v),
+        _ => Err(format!("Missing {}", key)),
+    }
+}
+
+fn get_pos(compound: &Compound, key: &str) -> Result<BlockPos, String> {
+    match compound.get(key) {
+        Some(Value::List(List::Int(pos))) if pos.len() == 3 => {
+            Ok(BlockPos::new(pos[0], pos[1], pos[2]))
+        }
+        _ => Err(format!("Missing {}", key)),
+    }
+}
+
+fn get_compound<'a>(compound: &'a Compound, key: &str) -> Result<&'a Compound, String> {
+    match compound.get(key) {
+        Some(Value::Compound(v)) => Ok(v),
+        _ => Err(format!("Missing {}", key)),
+    }
+}
+
+/// Writes the palette indices of a Sponge schematic as VarInts.
+fn write_varints(values: &[usize]) -> Vec<i8> {
+    let mut data = Vec::new();
+    for value in values {
+        let mut value = *value;
+        loop {
+            if value < 0x80 {
+                data.push(value as u8 as i8);
+                break;
+            }
+            data.push((value & 0x7f | 0x80) as u8 as i8);
+            value >>= 7;
+        }
+    }
+    data
+}
+
+/// Reads the palette indices of a Sponge schematic, which are stored as
+/// VarInts.
+fn read_varints(data: &[i8]) -> Result<Vec<usize>, String> {
+    let mut values = Vec::new();
+    let mut value = 0;
+    let mut shift = 0;
+
+    for byte in data {
+        let byte = *byte as u8;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            values.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+            if shift > 28 {
+                return Err("VarInt is too long".to_string());
+            }
+        }
+    }
+
+    Ok(values)
+}
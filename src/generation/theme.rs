@@ -1,6 +1,18 @@
 use crate::weighted_vec::WeightedVec;
 
-use super::{block_collection::BlockCollectionMap, generator::GenerationType};
+use super::{
+    block_collection::BlockCollectionMap, difficulty::Difficulty, generator::GenerationType,
+};
+
+/// The `CourseLength` enum represents how long a finite course is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CourseLength {
+    /// The finish follows this many generations, not counting the start.
+    Segments(u32),
+    /// The finish follows the generation the player is on once they have
+    /// this many points. Practice runs aren't scored, so they never get there.
+    Points(u32),
+}
 
 /// The `GenerationTheme` struct represents a theme for a parkour generation.
 ///
@@ -12,11 +24,25 @@ use super::{block_collection::BlockCollectionMap, generator::GenerationType};
 /// which is a vector of elements of type `GenerationType` with associated weights.
 /// Each element in the vector is assigned a weight, which determines the probability
 /// of that element being chosen.
+/// * `length`: How long a course with this theme is. If `None`, the course is
+/// infinite.
+/// * `finish`: The block collection the finish platform is made of.
+/// * `fallback`: The generation type used instead of one that fails to
+/// generate.
+/// * `collectible_chance`: The chance of a collectible being placed next to
+/// each generation.
+/// * `fork_chance`: The chance of the course forking into a safe and a hard
+/// branch instead of continuing with a single generation.
 #[derive(Clone, Debug)]
 pub struct GenerationTheme {
     pub name: String,
     pub block_map: BlockCollectionMap,
     pub generation_types: WeightedVec<GenerationType>,
+    pub length: Option<CourseLength>,
+    pub finish: String,
+    pub fallback: GenerationType,
+    pub collectible_chance: f64,
+    pub fork_chance: f64,
 }
 
 impl GenerationTheme {
@@ -29,10 +55,107 @@ impl GenerationTheme {
             name,
             block_map,
             generation_types,
+            length: None,
+            finish: "finish".to_owned(),
+            fallback: GenerationType::Single("jump_block".to_owned()),
+            collectible_chance: 0.0,
+            fork_chance: 0.0,
         }
     }
 
-    pub fn get_random_generation_type(&self) -> GenerationType {
-        self.generation_types.get_random().unwrap().clone()
+    /// Makes courses with this theme end once they are `length` long.
+    pub fn with_length(mut self, length: CourseLength) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    /// Uses `fallback` instead of generation types that fail to generate.
+    pub fn with_fallback(mut self, fallback: GenerationType) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Places collectibles next to generations with the given chance.
+    pub fn with_collectibles(mut self, chance: f64) -> Self {
+        self.collectible_chance = chance;
+        self
+    }
+
+    /// Makes the course fork with the given chance. Forks are never generated
+    /// on finite courses.
+    pub fn with_forks(mut self, chance: f64) -> Self {
+        self.fork_chance = chance;
+        self
+    }
+
+    /// Creates a copy of the theme that only generates the finish platform.
+    pub fn finish(&self) -> Self {
+        Self {
+            generation_types: WeightedVec::from(vec![GenerationType::Finish(self.finish.clone())]),
+            collectible_chance: 0.0,
+            fork_chance: 0.0,
+            ..self.clone()
+        }
+    }
+
+    /// Creates a copy of the theme that only has the generation types with the
+    /// given name. Returns `None` if the theme has none of them.
+    pub fn only(&self, kind: &str) -> Option<Self> {
+        let generation_types: WeightedVec<GenerationType> = self
+            .generation_types
+            .vec
+            .iter()
+            .filter(|(t, _)| t.name() == kind)
+            .cloned()
+            .collect();
+
+        if generation_types.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            generation_types,
+            ..self.clone()
+        })
+    }
+
+    /// Creates a copy of the theme that doesn't generate teleport pads. If the
+    /// theme only has teleport pads, the copy only generates the fallback.
+    pub fn without_teleports(&self) -> Self {
+        let mut generation_types: WeightedVec<GenerationType> = self
+            .generation_types
+            .vec
+            .iter()
+            .filter(|(t, _)| !matches!(t, GenerationType::Teleport(_)))
+            .cloned()
+            .collect();
+
+        if generation_types.is_empty() {
+            generation_types.push(self.fallback.clone(), 1.0);
+        }
+
+        Self {
+            generation_types,
+            ..self.clone()
+        }
+    }
+
+    /// Picks a random generation type. Types that need timing are weighted
+    /// according to the difficulty.
+    pub fn get_random_generation_type(&self, difficulty: Difficulty) -> GenerationType {
+        let weighted: WeightedVec<&GenerationType> = self
+            .generation_types
+            .vec
+            .iter()
+            .map(|(t, w)| {
+                if t.is_timed() {
+                    (t, w * difficulty.timed_weight())
+                } else {
+                    (t, *w)
+                }
+            })
+            .collect();
+
+        (*weighted.get_random().unwrap()).clone()
     }
 }
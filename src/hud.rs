@@ -0,0 +1,175 @@
+use valence::{
+    boss_bar::{
+        BossBarBundle, BossBarColor, BossBarDivision, BossBarFlags, BossBarHealth, BossBarStyle,
+        BossBarTitle,
+    },
+    prelude::*,
+    scoreboard::{Objective, ObjectiveBundle, ObjectiveDisplay, ObjectiveScores},
+};
+
+use crate::{config::ServerConfig, game_state::GameState};
+
+/// The `Hud` component holds the entities that make up a player's HUD. They
+/// are spawned on an entity layer only the player can see.
+///
+/// Properties:
+///
+/// * `sidebar`: The scoreboard objective shown in the sidebar.
+/// * `boss_bar`: The boss bar showing the progress to the next milestone.
+/// * `combo_bar`: The boss bar showing the player's combo and how much time
+/// is left to keep it.
+/// * `last_score`: The score the player had on the last tick. Used to tell
+/// when the player gets points.
+#[derive(Component)]
+pub struct Hud {
+    pub sidebar: Option<Entity>,
+    pub boss_bar: Option<Entity>,
+    pub combo_bar: Option<Entity>,
+    pub last_score: u32,
+}
+
+impl Hud {
+    /// Spawns the HUD of a player. `layer` has to be an entity layer only the
+    /// player can see.
+    pub fn spawn(config: &ServerConfig, layer: Entity, commands: &mut Commands) -> Self {
+        let sidebar = config.hud.sidebar.then(|| {
+            commands
+                .spawn(ObjectiveBundle {
+                    name: Objective::new("parkour"),
+                    display: ObjectiveDisplay("Parkour".color(Color::GOLD).bold()),
+                    layer: EntityLayerId(layer),
+                    ..Default::default()
+                })
+                .id()
+        });
+
+        let boss_bar = config
+            .hud
+            .boss_bar
+            .then(|| spawn_boss_bar(BossBarColor::Purple, layer, commands));
+
+        let combo_bar = config
+            .hud
+            .combo_bar
+            .then(|| spawn_boss_bar(BossBarColor::Yellow, layer, commands));
+
+        Self {
+            sidebar,
+            boss_bar,
+            combo_bar,
+            last_score: 0,
+        }
+    }
+
+    pub fn despawn(&self, commands: &mut Commands) {
+        for entity in [self.sidebar, self.boss_bar, self.combo_bar]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(mut entity) = commands.get_entity(entity) {
+                entity.insert(Despawned);
+            }
+        }
+    }
+}
+
+fn spawn_boss_bar(color: BossBarColor, layer: Entity, commands: &mut Commands) -> Entity {
+    commands
+        .spawn(BossBarBundle {
+            title: BossBarTitle(Text::default()),
+            health: BossBarHealth(0.0),
+            style: BossBarStyle {
+                color,
+                division: BossBarDivision::NoDivision,
+            },
+            flags: BossBarFlags::default(),
+            layer: EntityLayerId(layer),
+            ..Default::default()
+        })
+        .id()
+}
+
+/// Keeps the sidebar and boss bars up to date.
+pub fn update_hud(
+    clients: Query<(&GameState, &Hud)>,
+    mut sidebars: Query<(&mut ObjectiveDisplay, &mut ObjectiveScores)>,
+    mut boss_bars: Query<(&mut BossBarTitle, &mut BossBarHealth)>,
+    config: Res<ServerConfig>,
+) {
+    let milestones = &config.hud.milestones;
+
+    for (state, hud) in clients.iter() {
+        if let Some(Ok((mut display, mut scores))) = hud.sidebar.map(|e| sidebars.get_mut(e)) {
+            let title = state.theme.name.clone().color(Color::GOLD).bold()
+                + format!(" #{}", state.seed).color(Color::GRAY).not_bold();
+            if display.0 != title {
+                display.0 = title;
+            }
+
+            let best = state.player_data.best_score.max(state.score);
+            let combo = if state.combo.broken(state.tick, &config.combo) {
+                0
+            } else {
+                state.combo.count
+            };
+            for (key, value) in [("Score", state.score), ("Combo", combo), ("Best", best)] {
+                if scores.get(key) != Some(&(value as i32)) {
+                    scores.insert(key, value as i32);
+                }
+            }
+        }
+
+        if let Some(entity) = hud.boss_bar {
+            let (text, progress) = if state.practice {
+                ("Practice mode".color(Color::GREEN), 1.0)
+            } else if let Some(next) = milestones.next(state.score) {
+                let previous = milestones.previous(state.score);
+                (
+                    "Next milestone: ".color(Color::WHITE)
+                        + next.to_string().color(Color::LIGHT_PURPLE).bold(),
+                    (state.score - previous) as f32 / (next - previous) as f32,
+                )
+            } else {
+                ("All milestones reached".color(Color::GOLD), 1.0)
+            };
+
+            set_boss_bar(&mut boss_bars, entity, text, progress);
+        }
+
+        if let Some(entity) = hud.combo_bar {
+            let combo = &state.combo;
+            let (text, progress) = if combo.count == 0 || combo.broken(state.tick, &config.combo) {
+                ("No combo".color(Color::GRAY), 0.0)
+            } else {
+                (
+                    "Combo ".color(Color::WHITE)
+                        + combo.count.to_string().color(Color::YELLOW).bold()
+                        + format!(" x{:.2}", combo.multiplier(&config.combo)).color(Color::GOLD),
+                    combo.time_left(state.tick, &config.combo),
+                )
+            };
+
+            set_boss_bar(&mut boss_bars, entity, text, progress);
+        }
+    }
+}
+
+/// Only changes the boss bar if it is different, so it isn't resent every
+/// tick.
+fn set_boss_bar(
+    boss_bars: &mut Query<(&mut BossBarTitle, &mut BossBarHealth)>,
+    entity: Entity,
+    text: Text,
+    progress: f32,
+) {
+    let Ok((mut title, mut health)) = boss_bars.get_mut(entity) else {
+        return;
+    };
+
+    if title.0 != text {
+        title.0 = text;
+    }
+    if health.0 != progress {
+        health.0 = progress;
+    }
+}
@@ -0,0 +1,121 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use valence::prelude::*;
+
+use crate::generation::difficulty::Difficulty;
+
+pub const LEADERBOARD_PATH: &str = "data/leaderboard.json";
+/// How many entries every difficulty keeps. Lower scores are dropped.
+pub const MAX_ENTRIES: usize = 100;
+
+/// The `LeaderboardEntry` struct represents a player's best score on a theme,
+/// seed and difficulty.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub username: String,
+    pub score: u32,
+    pub seed: u64,
+    pub theme: String,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+}
+
+/// The `Leaderboard` resource holds the best score of every player on every
+/// theme, seed and difficulty they have played, sorted from highest to lowest.
+/// Every difficulty has its own leaderboard of at most `MAX_ENTRIES` entries,
+/// so the lookups only return entries of one difficulty.
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    /// Loads the leaderboard from disk. Returns an empty leaderboard if it
+    /// doesn't exist yet.
+    pub fn load() -> Self {
+        fs::read_to_string(LEADERBOARD_PATH)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = Path::new(LEADERBOARD_PATH).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(LEADERBOARD_PATH, serde_json::to_string(self)?)
+    }
+
+    /// Submits a score. Returns true if it is the player's new best on that
+    /// theme, seed and difficulty and made it onto the leaderboard, which is
+    /// the only time it has to be saved again.
+    pub fn submit(&mut self, entry: LeaderboardEntry) -> bool {
+        let existing = self.entries.iter().position(|e| {
+            e.username == entry.username
+                && e.theme == entry.theme
+                && e.seed == entry.seed
+                && e.difficulty == entry.difficulty
+        });
+        if existing.is_some_and(|i| self.entries[i].score >= entry.score) {
+            return false;
+        }
+
+        let rank = self
+            .for_difficulty(entry.difficulty)
+            .take_while(|e| e.score >= entry.score)
+            .count();
+        if rank >= MAX_ENTRIES {
+            return false;
+        }
+
+        if let Some(i) = existing {
+            self.entries.remove(i);
+        }
+
+        // The entries stay sorted, so the new one goes after every entry
+        // with at least its score.
+        let difficulty = entry.difficulty;
+        let index = self.entries.partition_point(|e| e.score >= entry.score);
+        self.entries.insert(index, entry);
+
+        if self.for_difficulty(difficulty).count() > MAX_ENTRIES {
+            if let Some(last) = self
+                .entries
+                .iter()
+                .rposition(|e| e.difficulty == difficulty)
+            {
+                self.entries.remove(last);
+            }
+        }
+
+        true
+    }
+
+    /// Every entry of a difficulty, from highest to lowest.
+    pub fn for_difficulty(
+        &self,
+        difficulty: Difficulty,
+    ) -> impl Iterator<Item = &LeaderboardEntry> {
+        self.entries
+            .iter()
+            .filter(move |e| e.difficulty == difficulty)
+    }
+
+    pub fn best(&self, difficulty: Difficulty) -> Option<&LeaderboardEntry> {
+        self.for_difficulty(difficulty).next()
+    }
+
+    pub fn best_for_player(
+        &self,
+        username: &str,
+        difficulty: Difficulty,
+    ) -> Option<&LeaderboardEntry> {
+        self.for_difficulty(difficulty)
+            .find(|e| e.username == username)
+    }
+
+    pub fn top(&self, count: usize, difficulty: Difficulty) -> Vec<&LeaderboardEntry> {
+        self.for_difficulty(difficulty).take(count).collect()
+    }
+}
@@ -0,0 +1,13 @@
+//! The course generation of the parkour server. Nothing in here places blocks
+//! in a world, so courses can be generated and looked at without a server.
+
+#![allow(clippy::type_complexity)]
+
+pub mod alt_block;
+pub mod generation;
+pub mod line;
+pub mod prediction;
+pub mod random;
+pub mod themes;
+pub mod utils;
+pub mod weighted_vec;
@@ -3,35 +3,63 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use alt_block::AltBlockParams;
+use anti_cheat::MovementCheck;
+use checkpoint::Checkpoint;
+use combo::Combo;
+use commands::*;
+use config::ServerConfig;
 use game_state::GameState;
-use generation::block_collection::*;
-use generation::block_grid::{BlockGrid, BlockProperties};
-use generation::generator::{GenerationType, Generator};
-use generation::generators::*;
-use generation::theme::GenerationTheme;
+use generation::course::{self, CourseState, START_POS};
+use generation::difficulty::Difficulty;
+use generation::fork::{Branch, Fork, HARD_BONUS};
+use generation::theme::CourseLength;
+use hud::Hud;
+use leaderboard::{Leaderboard, LeaderboardEntry};
+use placement::{PlaceFork, PlaceGeneration};
+use player_data::PlayerData;
 use prediction::prediction_state::PredictionState;
-use utils::JumpDirection;
+use recording::{Ghost, RecordedGeneration, RunRecording};
+use themes::Themes;
+use timer::RunTimer;
+use valence::command::scopes::CommandScopes;
+use valence::command::{AddCommand, CommandScopeRegistry};
 use valence::entity::block_display;
 use valence::prelude::*;
-use valence::protocol::packets::play::DisconnectS2c;
 use valence::protocol::sound::{Sound, SoundCategory};
-use valence::protocol::WritePacket;
 use valence::spawn::IsFlat;
 
-mod alt_block;
+use mc_parkou_rs::{alt_block, generation, line, prediction, random, themes, utils};
+
+mod anti_cheat;
 mod block_types;
+mod checkpoint;
+mod collectibles;
+mod combo;
+mod commands;
+mod config;
 mod game_state;
-mod generation;
-mod line;
-mod prediction;
-mod utils;
-mod weighted_vec;
-
-const START_POS: BlockPos = BlockPos::new(0, 100, 0);
-const DIFF: i32 = 10;
-const MIN_Y: i32 = START_POS.y - DIFF;
-const MAX_Y: i32 = START_POS.y + DIFF;
+mod hud;
+mod leaderboard;
+mod milestones;
+mod placement;
+mod player_data;
+mod practice;
+mod recording;
+mod teleport;
+mod timer;
+
 const VIEW_DIST: u8 = 32;
+/// How many chunks around a teleport pad's destination are loaded when it is
+/// generated.
+const TELEPORT_VIEW_DIST: u8 = 2;
+/// How far below the lowest point of the nearby course a player has to be to
+/// have fallen.
+const FALL_MARGIN: f64 = 4.0;
+/// How many generations, starting with the last one reached, are taken into
+/// account when checking if a player has fallen.
+const FALL_GENERATIONS: usize = 2;
+/// How many generations are kept ahead of the one the player is on.
+const AHEAD_GENERATIONS: usize = 10;
 
 pub fn main() {
     App::new()
@@ -40,21 +68,72 @@ pub fn main() {
             ..Default::default()
         })
         .add_plugins(DefaultPlugins)
+        .insert_resource(Leaderboard::load())
+        .insert_resource(ServerConfig::load())
+        .insert_resource(Themes::default())
+        .add_command::<ResetCommand>()
+        .add_command::<ThemeCommand>()
+        .add_command::<SeedCommand>()
+        .add_command::<DifficultyCommand>()
+        .add_command::<LinesCommand>()
+        .add_command::<StatsCommand>()
+        .add_command::<TopCommand>()
+        .add_command::<GhostCommand>()
+        .add_command::<PracticeCommand>()
+        .add_command::<AdminCommand>()
         .add_systems(Startup, setup)
+        .add_systems(PostStartup, suggest_theme_names)
         .add_systems(
             Update,
             (
                 init_clients,
                 reset_clients.after(init_clients),
                 manage_chunks.after(reset_clients).before(manage_blocks),
+                anti_cheat::check_movement
+                    .after(reset_clients)
+                    .before(manage_blocks),
+                anti_cheat::deny_digging,
+                anti_cheat::deny_placing,
+                anti_cheat::deny_flying,
                 manage_blocks,
                 update_alt_blocks,
                 spawn_lines,
+                recording::record_runs.after(manage_blocks),
+                recording::update_ghosts.after(recording::record_runs),
+                practice::handle_practice_items.before(manage_blocks),
+                collectibles::update_collectibles.after(manage_blocks),
+                teleport::handle_teleports.after(manage_blocks),
+                hud::update_hud.after(manage_blocks),
+                milestones::celebrate_milestones.after(manage_blocks),
                 despawn_disconnected_clients,
                 cleanup_clients,
             ),
         )
-        .add_systems(EventLoopUpdate, (detect_stop_running,))
+        .add_systems(
+            Update,
+            (
+                handle_reset_command,
+                handle_theme_command,
+                handle_seed_command,
+                handle_difficulty_command,
+                handle_lines_command,
+                handle_stats_command,
+                handle_top_command,
+                handle_ghost_command,
+                handle_practice_command,
+                handle_admin_command,
+            )
+                .before(reset_clients),
+        )
+        .add_systems(
+            EventLoopUpdate,
+            (
+                combo::detect_stop_running,
+                anti_cheat::check_movement_packets,
+                anti_cheat::deny_creative_actions,
+                complete_theme_names,
+            ),
+        )
         .run();
 }
 
@@ -63,9 +142,12 @@ fn setup(
     server: Res<Server>,
     dimensions: Res<DimensionTypeRegistry>,
     biomes: Res<BiomeRegistry>,
+    mut command_scopes: ResMut<CommandScopeRegistry>,
 ) {
     let layer = LayerBundle::new(ident!("overworld"), &dimensions, &biomes, &server);
 
+    command_scopes.link(ADMIN_SCOPE, PLAYER_SCOPE);
+
     commands.spawn(layer);
 }
 
@@ -79,13 +161,18 @@ fn init_clients(
             &mut VisibleEntityLayers,
             &mut IsFlat,
             &mut GameMode,
+            &Username,
+            &mut CommandScopes,
         ),
         Added<Client>,
     >,
-    layers: Query<Entity, (With<ChunkLayer>, With<EntityLayer>)>,
+    // Clients have their own layers, which aren't the shared one.
+    layers: Query<Entity, (With<ChunkLayer>, With<EntityLayer>, Without<Client>)>,
     server: Res<Server>,
     dimensions: Res<DimensionTypeRegistry>,
     biomes: Res<BiomeRegistry>,
+    config: Res<ServerConfig>,
+    themes: Res<Themes>,
     mut commands: Commands,
 ) {
     for (
@@ -96,6 +183,8 @@ fn init_clients(
         mut visible_entity_layers,
         mut is_flat,
         mut game_mode,
+        username,
+        mut scopes,
     ) in clients.iter_mut()
     {
         let layer = layers.single();
@@ -106,1149 +195,26 @@ fn init_clients(
 
         visible_chunk_layer.0 = entity;
         is_flat.0 = true;
-        *game_mode = GameMode::Creative; // TODO: Change to adventure
+        *game_mode = GameMode::Adventure;
+
+        scopes.add(PLAYER_SCOPE);
+        if config.is_admin(&username.0) {
+            scopes.add(ADMIN_SCOPE);
+        }
 
         client.send_chat_message("Welcome to epic infinite parkour game!".italic());
 
         let state = GameState {
             generations: VecDeque::new(),
-            direction: JumpDirection::DoesntMatter,
-            theme: GenerationTheme::new(
-                "name".to_owned(),
-                BlockCollectionMap::from([
-                    (
-                        "jump_block",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::GRASS_BLOCK,
-                                BlockState::OAK_LOG,
-                                BlockState::BIRCH_LOG,
-                                BlockState::OAK_LEAVES,
-                                BlockState::BIRCH_LEAVES,
-                                BlockState::DIRT,
-                                BlockState::MOSS_BLOCK,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "ramp_stone",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::STONE,
-                                BlockState::COBBLESTONE,
-                                BlockState::MOSSY_COBBLESTONE,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "ramp_stone_slab",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::STONE_SLAB,
-                                BlockState::COBBLESTONE_SLAB,
-                                BlockState::MOSSY_COBBLESTONE_SLAB,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "ramp_wood",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::OAK_PLANKS,
-                                BlockState::SPRUCE_PLANKS,
-                                BlockState::BIRCH_PLANKS,
-                                BlockState::JUNGLE_PLANKS,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "ramp_wood_slab",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::OAK_SLAB,
-                                BlockState::SPRUCE_SLAB,
-                                BlockState::BIRCH_SLAB,
-                                BlockState::JUNGLE_SLAB,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "indoor_walls",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::BRICKS,],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "indoor_floor",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::COBBLED_DEEPSLATE,],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "indoor_platforms",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::STONE,],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "water",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::WATER,],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "cave",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::STONE,
-                                BlockState::COBBLESTONE,
-                                BlockState::MOSSY_COBBLESTONE,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "island_grass",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::GRASS_BLOCK,],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "island_dirt",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::DIRT, BlockState::COARSE_DIRT,],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "island_stone",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::STONE,
-                                BlockState::COBBLESTONE,
-                                BlockState::MOSSY_COBBLESTONE,
-                                BlockState::DEEPSLATE,
-                                BlockState::COBBLED_DEEPSLATE,
-                                BlockState::ANDESITE,
-                                BlockState::DIORITE,
-                                BlockState::GRANITE,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "concrete",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::WHITE_CONCRETE,
-                                BlockState::ORANGE_CONCRETE,
-                                BlockState::MAGENTA_CONCRETE,
-                                BlockState::LIGHT_BLUE_CONCRETE,
-                                BlockState::YELLOW_CONCRETE,
-                                BlockState::LIME_CONCRETE,
-                                BlockState::PINK_CONCRETE,
-                                BlockState::GRAY_CONCRETE,
-                                BlockState::LIGHT_GRAY_CONCRETE,
-                                BlockState::CYAN_CONCRETE,
-                                BlockState::PURPLE_CONCRETE,
-                                BlockState::BLUE_CONCRETE,
-                                BlockState::BROWN_CONCRETE,
-                                BlockState::GREEN_CONCRETE,
-                                BlockState::RED_CONCRETE,
-                                // BlockState::BLACK_CONCRETE, // black has no contrast and is completely invisible at night
-                            ],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "blink_on",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::BLUE_CONCRETE,],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "blink_off",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::RED_CONCRETE,],
-                            uniform: true,
-                        }),
-                    ),
-                    (
-                        "platform",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![
-                                BlockState::STONE,
-                                BlockState::COBBLESTONE,
-                                BlockState::MOSSY_COBBLESTONE,
-                                BlockState::OAK_PLANKS,
-                                BlockState::SPRUCE_PLANKS,
-                                BlockState::BIRCH_PLANKS,
-                                BlockState::JUNGLE_PLANKS,
-                            ],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "bridge_side",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::BRICKS],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "bridge_wall",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::BRICK_WALL],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "bridge_floor",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::OAK_PLANKS],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "bridge_pillar",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::STONE_BRICKS],
-                            uniform: false,
-                        }),
-                    ),
-                    (
-                        "bridge_pillar_stair",
-                        BlockCollection(BlockChoice {
-                            blocks: weighted_vec![BlockState::STONE_BRICK_STAIRS],
-                            uniform: false,
-                        }),
-                    ),
-                ]),
-                weighted_vec![
-                    (GenerationType::Single("jump_block".to_string()), 30.0),
-                    (GenerationType::Ramp("ramp_stone".to_string()), 10.0),
-                    (GenerationType::Ramp("ramp_wood".to_string()), 10.0),
-                    (
-                        GenerationType::Indoor {
-                            walls: "indoor_walls".to_string(),
-                            floor: Some("indoor_floor".to_string()),
-                            platforms: "indoor_platforms".to_string(),
-                        },
-                        2.0
-                    ),
-                    (
-                        GenerationType::Indoor {
-                            walls: "indoor_walls".to_string(),
-                            floor: Some("water".to_string()),
-                            platforms: "indoor_platforms".to_string(),
-                        },
-                        2.0
-                    ),
-                    (
-                        GenerationType::Indoor {
-                            walls: "indoor_walls".to_string(),
-                            floor: None,
-                            platforms: "indoor_platforms".to_string(),
-                        },
-                        1.0
-                    ),
-                    (GenerationType::Cave("cave".to_string()), 5.0),
-                    (GenerationType::Snake("concrete".to_string()), 10.0),
-                    (
-                        GenerationType::BlinkBlocks {
-                            on: "blink_on".to_string(),
-                            off: "blink_off".to_string(),
-                            delay: 20,
-                            overlap: 5,
-                        },
-                        10.0
-                    ),
-                    (
-                        GenerationType::SingleCustom(SingleCustomPreset {
-                            blocks: BlockGrid::from([
-                                (
-                                    BlockPos::new(0, 0, 0),
-                                    BlockProperties::new("platform".to_string(), vec![])
-                                ),
-                                (
-                                    BlockPos::new(0, 0, 1),
-                                    BlockProperties::new("platform".to_string(), vec![])
-                                ),
-                                (
-                                    BlockPos::new(0, 0, 2),
-                                    BlockProperties::new("platform".to_string(), vec![])
-                                ),
-                                (
-                                    BlockPos::new(0, 1, 2),
-                                    BlockProperties::new("platform".to_string(), vec![])
-                                ),
-                                (
-                                    BlockPos::new(0, 2, 2),
-                                    BlockProperties::new("platform".to_string(), vec![])
-                                ),
-                                (
-                                    BlockPos::new(0, 0, 3),
-                                    BlockProperties::new("platform".to_string(), vec![])
-                                ),
-                                (
-                                    BlockPos::new(0, 0, 4),
-                                    BlockProperties::new("platform".to_string(), vec![])
-                                ),
-                            ]),
-                            start_pos: BlockPos::new(0, 0, 0),
-                            end_pos: BlockPos::new(0, 0, 4)
-                        }),
-                        10.0
-                    ),
-                    (
-                        GenerationType::MultiCustom(MultiCustomPreset {
-                            presets: HashMap::from([
-                                // TODO: Finish bridge. Also, put everything here in a separate file.
-                                (
-                                    "start".to_string(),
-                                    SingularMultiCustomPreset {
-                                        preset: SingleCustomPreset {
-                                            blocks: BlockGrid::from([
-                                                (
-                                                    BlockPos::new(0, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                            ]),
-                                            start_pos: BlockPos::new(0, 0, 0),
-                                            end_pos: BlockPos::new(0, 0, 3)
-                                        },
-                                        nexts: vec!["middle".to_string()],
-                                        fixed_offset: Some(BlockPos::new(0, 0, 3)),
-                                    }
-                                ),
-                                (
-                                    "middle".to_string(),
-                                    SingularMultiCustomPreset {
-                                        preset: SingleCustomPreset {
-                                            blocks: BlockGrid::from([
-                                                (
-                                                    BlockPos::new(0, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-2, 1, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-2, 1, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-2, 1, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-2, 1, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(2, 1, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(2, 1, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(2, 1, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(2, 1, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_wall".to_string(),
-                                                        vec![
-                                                            (PropName::North, PropValue::Low),
-                                                            (PropName::South, PropValue::Low),
-                                                            (PropName::Up, PropValue::False)
-                                                        ],
-                                                    ),
-                                                ),
-                                            ]),
-                                            start_pos: BlockPos::new(0, 0, 0),
-                                            end_pos: BlockPos::new(0, 0, 3)
-                                        },
-                                        nexts: vec!["middle".to_string()],
-                                        fixed_offset: Some(BlockPos::new(0, 0, 3)),
-                                    },
-                                ),
-                                (
-                                    "end".to_string(),
-                                    SingularMultiCustomPreset {
-                                        preset: SingleCustomPreset {
-                                            blocks: BlockGrid::from([
-                                                (
-                                                    BlockPos::new(0, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 0),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 1),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_pillar".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 2),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(0, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(-1, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                                (
-                                                    BlockPos::new(1, 0, 3),
-                                                    BlockProperties::new(
-                                                        "bridge_floor".to_string(),
-                                                        vec![]
-                                                    ),
-                                                ),
-                                            ]),
-                                            start_pos: BlockPos::new(0, 0, 0),
-                                            end_pos: BlockPos::new(0, 0, 3)
-                                        },
-                                        nexts: vec!["middle".to_string()],
-                                        fixed_offset: Some(BlockPos::new(0, 0, 3)),
-                                    },
-                                )
-                            ]),
-                            start: weighted_vec!["start".to_string()],
-                            end: weighted_vec!["end".to_string()],
-                            min_length: 2,
-                            max_length: 4,
-                        }),
-                        10.0
-                    ),
-                    (
-                        GenerationType::Island {
-                            grass: "island_grass".to_string(),
-                            dirt: "island_dirt".to_string(),
-                            stone: "island_stone".to_string(),
-                            water: "water".to_string(),
-                            min_radius: 4,
-                            max_radius: 12,
-                            min_point_power: 1.0,
-                            max_point_power: 1.75,
-                        },
-                        10.0
-                    ),
-                    (
-                        GenerationType::ComplexCustom(ComplexGenerator::new(
-                            vec![
-                                ComplexTile {
-                                    connection_north: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::South,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(0, 0, 0),
-                                            BlockPos::new(0, 0, 1),
-                                        ])),
-                                        ..Default::default()
-                                    }),
-                                    connection_south: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::North,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(0, 0, 3),
-                                            BlockPos::new(0, 0, 4),
-                                        ])),
-                                        ..Default::default()
-                                    }),
-                                    grid: BlockGrid::from([
-                                        (
-                                            BlockPos::new(0, 0, 0),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 1),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 3),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 4),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                    ]),
-                                    ..Default::default()
-                                },
-                                ComplexTile {
-                                    connection_north: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::East,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(0, 0, 0),
-                                            BlockPos::new(0, 0, 1),
-                                        ])),
-                                        ..Default::default()
-                                    }),
-                                    connection_east: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::North,
-                                        blocks: Some(HashSet::from([BlockPos::new(2, 0, 2),])),
-                                        ..Default::default()
-                                    }),
-                                    grid: BlockGrid::from([
-                                        (
-                                            BlockPos::new(0, 0, 0),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 1),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(2, 0, 2),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                    ]),
-                                    ..Default::default()
-                                },
-                                ComplexTile {
-                                    connection_north: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::South,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(0, 0, 0),
-                                            BlockPos::new(0, 0, 1),
-                                            BlockPos::new(0, 0, 2),
-                                            BlockPos::new(0, 0, 3),
-                                            BlockPos::new(0, 0, 4),
-                                        ])),
-                                        ..Default::default()
-                                    }),
-                                    connection_south: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::North,
-                                        ..Default::default()
-                                    }),
-                                    connection_east: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::West,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(2, 1, 2),
-                                            BlockPos::new(1, 2, 2),
-                                            BlockPos::new(1, 2, 3),
-                                            BlockPos::new(1, 2, 1),
-                                        ])),
-                                        can_next: false,
-                                        ..Default::default()
-                                    }),
-                                    connection_west: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::East,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(-1, 1, 2),
-                                            BlockPos::new(-2, 2, 2),
-                                            BlockPos::new(-2, 2, 1),
-                                            BlockPos::new(-2, 2, 3),
-                                        ])),
-                                        can_next: false,
-                                        ..Default::default()
-                                    }),
-                                    grid: BlockGrid::from([
-                                        (
-                                            BlockPos::new(0, 0, 0),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 1),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 2),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 3),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 0, 4),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(2, 1, 2),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(1, 2, 2),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(1, 2, 1),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(1, 2, 3),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(-1, 2, 2),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(-1, 2, 1),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(-1, 2, 3),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(-2, 1, 2),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                    ]),
-                                    ..Default::default()
-                                },
-                                ComplexTile {
-                                    connection_north: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::Up,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(0, 1, 0),
-                                            BlockPos::new(0, 2, 1),
-                                            BlockPos::new(0, 3, 2),
-                                        ])),
-                                        can_start: false,
-                                        ..Default::default()
-                                    }),
-                                    connection_up: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::North,
-                                        attach_direction: Some(complex_gen::Direction::North),
-                                        ..Default::default()
-                                    }),
-                                    grid: BlockGrid::from([
-                                        (
-                                            BlockPos::new(0, 1, 0),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 2, 1),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, 3, 2),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                    ]),
-                                    ..Default::default()
-                                },
-                                ComplexTile {
-                                    connection_north: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::Down,
-                                        blocks: Some(HashSet::from([
-                                            BlockPos::new(0, 0, 0),
-                                            BlockPos::new(0, -1, 1),
-                                        ])),
-                                        can_start: false,
-                                        ..Default::default()
-                                    }),
-                                    connection_down: Some(Connection {
-                                        name: "a".to_string(),
-                                        next_direction: complex_gen::Direction::North,
-                                        attach_direction: Some(complex_gen::Direction::South),
-                                        ..Default::default()
-                                    }),
-                                    grid: BlockGrid::from([
-                                        (
-                                            BlockPos::new(0, 0, 0),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                        (
-                                            BlockPos::new(0, -1, 1),
-                                            BlockProperties::new("concrete".to_string(), vec![])
-                                        ),
-                                    ]),
-                                    ..Default::default()
-                                },
-                                // // a -> b
-                                // ComplexTile {
-                                //     connection_north: Some(Connection {
-                                //         name: "a".to_string(),
-                                //         next_direction: complex_gen::Direction::South,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     connection_south: Some(Connection {
-                                //         name: "b".to_string(),
-                                //         next_direction: complex_gen::Direction::North,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     grid: BlockGrid::from([
-                                //         (
-                                //             BlockPos::new(0, 0, 0),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 1, 1),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 2, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 3, 3),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 4),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //     ]),
-                                //     ..Default::default()
-                                // },
-                                // // b
-                                // ComplexTile {
-                                //     connection_north: Some(Connection {
-                                //         name: "b".to_string(),
-                                //         next_direction: complex_gen::Direction::South,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     connection_south: Some(Connection {
-                                //         name: "b".to_string(),
-                                //         next_direction: complex_gen::Direction::North,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     grid: BlockGrid::from([
-                                //         (
-                                //             BlockPos::new(0, 4, 0),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 1),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 3),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 4),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //     ]),
-                                //     ..Default::default()
-                                // },
-                                // ComplexTile {
-                                //     connection_north: Some(Connection {
-                                //         name: "b".to_string(),
-                                //         next_direction: complex_gen::Direction::East,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     connection_east: Some(Connection {
-                                //         name: "b".to_string(),
-                                //         next_direction: complex_gen::Direction::North,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     grid: BlockGrid::from([
-                                //         (
-                                //             BlockPos::new(0, 4, 0),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 1),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(1, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(2, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //     ]),
-                                //     ..Default::default()
-                                // },
-                                // // a & b junction
-                                // ComplexTile {
-                                //     connection_north: Some(Connection {
-                                //         name: "a".to_string(),
-                                //         next_direction: complex_gen::Direction::South,
-                                //         ..Default::default()
-                                //     }),
-                                //     connection_south: Some(Connection {
-                                //         name: "a".to_string(),
-                                //         next_direction: complex_gen::Direction::North,
-                                //         ..Default::default()
-                                //     }),
-                                //     connection_east: Some(Connection {
-                                //         name: "b".to_string(),
-                                //         next_direction: complex_gen::Direction::West,
-                                //         can_next: false,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     connection_west: Some(Connection {
-                                //         name: "b".to_string(),
-                                //         next_direction: complex_gen::Direction::East,
-                                //         can_next: false,
-                                //         can_start: false,
-                                //         ..Default::default()
-                                //     }),
-                                //     grid: BlockGrid::from([
-                                //         (
-                                //             BlockPos::new(0, 0, 0),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 0, 1),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 0, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 0, 3),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 0, 4),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(2, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(1, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(0, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(-1, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //         (
-                                //             BlockPos::new(-2, 4, 2),
-                                //             BlockProperties::new("platform".to_string(), vec![])
-                                //         ),
-                                //     ]),
-                                //     ..Default::default()
-                                // },
-                            ],
-                            BlockPos::new(5, 5, 5),
-                            BlockPos::new(-5, 0, 0),
-                            BlockPos::new(5, 1, 10),
-                        )),
-                        5.0
-                    ),
-                ],
-            ),
+            fork: None,
+            theme: themes.default_theme().clone(),
             score: 0,
-            combo: 0,
-            target_y: 0,
-            stopped_running: false,
+            coins: 0,
+            combo: Combo::default(),
             tick: 0,
             alt_block_entities: HashMap::new(),
             prev_alt_block_states: HashMap::new(),
+            collectible_entities: HashMap::new(),
             prev_pos: DVec3::new(
                 START_POS.x as f64 + 0.5,
                 START_POS.y as f64 + 1.0,
@@ -1265,11 +231,41 @@ fn init_clients(
             ),
             line_entities: HashMap::new(),
             lines: HashSet::new(),
+            seed: 0,
+            course: CourseState::new(0),
+            recording: RunRecording::default(),
+            ghost: None,
+            pending_ghost: None,
+            pending_restart: None,
+            difficulty: Difficulty::Normal,
+            show_lines: true,
+            player_data: PlayerData::load(&username.0),
+            flags: Vec::new(),
+            lives: 0,
+            checkpoint: None,
+            practice: false,
+            timer: RunTimer::default(),
+            pending_velocity: None,
+            milestones: HashSet::new(),
         };
 
         let layer = ChunkLayer::new(ident!("overworld"), &dimensions, &biomes, &server);
 
-        commands.entity(entity).insert((state, layer));
+        let check = MovementCheck::new(DVec3::new(
+            START_POS.x as f64 + 0.5,
+            START_POS.y as f64 + 1.0,
+            START_POS.z as f64 + 0.5,
+        ));
+
+        // The HUD is spawned on an entity layer of the client's own so other
+        // players can't see it.
+        let hud_layer = EntityLayer::new(&server);
+        visible_entity_layers.0.insert(entity);
+        let hud = Hud::spawn(&config, entity, &mut commands);
+
+        commands
+            .entity(entity)
+            .insert((state, layer, hud_layer, check, hud));
     }
 }
 
@@ -1278,13 +274,19 @@ fn reset_clients(
     mut clients: Query<(
         Entity,
         &mut Client,
+        &Username,
         &mut Position,
         &mut Look,
         &mut GameState,
         &mut ChunkLayer,
+        &mut MovementCheck,
     )>,
+    mut leaderboard: ResMut<Leaderboard>,
+    config: Res<ServerConfig>,
 ) {
-    for (entity, mut client, mut pos, mut look, mut state, mut layer) in clients.iter_mut() {
+    for (entity, mut client, username, mut pos, mut look, mut state, mut layer, mut check) in
+        clients.iter_mut()
+    {
         state.test_state.yaw = look.yaw / 180.0 * std::f32::consts::PI;
         state.test_state.vel = pos.0 - state.prev_pos;
         // if state.test_state.vel.y == 0. {
@@ -1334,32 +336,65 @@ fn reset_clients(
             // particle_outline_block(bbb.next_params.next_pos, Vec3::new(0., 1., 0.), &mut client);
         }
 
-        state.lines = lines.into_iter().collect();
+        state.lines = if state.show_lines {
+            lines.into_iter().collect()
+        } else {
+            HashSet::new()
+        };
 
-        let out_of_bounds = (pos.0.y as i32) < START_POS.y - 40;
+        let out_of_bounds = pos.0.y < get_fall_threshold(&state);
+        let pending_ghost = state.pending_ghost.take();
+        let pending_restart = state.pending_restart.take();
+
+        if out_of_bounds
+            && pending_ghost.is_none()
+            && pending_restart.is_none()
+            && !state.is_added()
+            && (state.lives > 0 || state.practice)
+        {
+            let (spawn, yaw) = respawn(&mut state, &mut layer, &mut commands);
+            pos.set(spawn);
+            check.teleported(spawn);
+            look.yaw = yaw;
+            look.pitch = 0.0;
+
+            if !state.practice {
+                state.lives -= 1;
 
-        if out_of_bounds || state.is_added() {
-            if out_of_bounds && !state.is_added() {
                 client.send_chat_message(
-                    "Your score was ".italic()
+                    "You fell! Lives left: ".italic()
                         + state
-                            .score
+                            .lives
                             .to_string()
                             .color(Color::GOLD)
                             .bold()
                             .not_italic(),
                 );
+            }
+
+            continue;
+        }
 
-                client.write_packet(&DisconnectS2c {
-                    reason: ("Your score was ".italic()
+        if out_of_bounds || pending_ghost.is_some() || pending_restart.is_some() || state.is_added()
+        {
+            if !state.is_added() {
+                end_run(&mut state, &mut leaderboard, &mut commands);
+            }
+
+            if out_of_bounds && !state.is_added() {
+                client.send_chat_message(
+                    "Your score was ".italic()
                         + state
                             .score
                             .to_string()
                             .color(Color::GOLD)
                             .bold()
-                            .not_italic())
-                    .into(),
-                });
+                            .not_italic(),
+                );
+
+                for line in state.timer.summary(state.tick) {
+                    client.send_chat_message(line);
+                }
             }
 
             // Init chunks.
@@ -1367,27 +402,25 @@ fn reset_clients(
                 layer.insert_chunk(pos, UnloadedChunk::new());
             }
 
-            state.score = 0;
-            state.combo = 0;
-            {
-                let state = &mut *state;
-                for block in &state.generations {
-                    block.remove(
-                        &mut layer,
-                        &mut state.alt_block_entities,
-                        &mut state.prev_alt_block_states,
-                        &mut commands,
-                    );
-                }
-            }
+            let seed = match (&pending_ghost, pending_restart) {
+                (Some((_, recording)), _) => recording.seed,
+                (None, Some(seed)) => seed,
+                (None, None) => random::random_seed(),
+            };
 
-            state.generations.clear();
-            let gen = Generator::first_in_generation(START_POS, &state.theme);
-            gen.place(&mut layer);
-            state.generations.push_back(gen);
+            start_run(
+                &mut state,
+                &mut layer,
+                &mut commands,
+                &username.0,
+                seed,
+                config.failure.lives(),
+            );
 
-            for _ in 0..10 {
-                generate_next_block(&mut state, &mut layer);
+            // The ghost is spawned on the client's own entity layer so only
+            // the player racing it can see it.
+            if let Some((source, recording)) = pending_ghost {
+                state.ghost = Some(Ghost::spawn(source, recording, entity, &mut commands));
             }
 
             pos.set([
@@ -1395,6 +428,7 @@ fn reset_clients(
                 START_POS.y as f64 + 1.0,
                 START_POS.z as f64 + 0.5,
             ]);
+            check.teleported(pos.0);
             look.yaw = 0.0;
             look.pitch = 0.0;
         }
@@ -1404,10 +438,14 @@ fn reset_clients(
 fn cleanup_clients(
     mut commands: Commands,
     mut disconnected_clients: RemovedComponents<Client>,
-    mut query: Query<&mut GameState>,
+    mut query: Query<(&mut GameState, &Hud)>,
+    mut leaderboard: ResMut<Leaderboard>,
 ) {
     for entity in disconnected_clients.read() {
-        if let Ok(mut state) = query.get_mut(entity) {
+        if let Ok((mut state, hud)) = query.get_mut(entity) {
+            end_run(&mut state, &mut leaderboard, &mut commands);
+            hud.despawn(&mut commands);
+
             for entity in state.line_entities.values() {
                 commands.entity(*entity).insert(Despawned);
             }
@@ -1416,6 +454,10 @@ fn cleanup_clients(
                 commands.entity(*entity).insert(Despawned);
             }
 
+            for entity in state.collectible_entities.values() {
+                commands.entity(*entity).insert(Despawned);
+            }
+
             state.generations.clear();
         }
     }
@@ -1436,7 +478,9 @@ fn update_alt_blocks(
 
         let state = &mut *state;
 
-        for gen in state.generations.iter_mut() {
+        let fork = state.fork.iter().flat_map(Fork::generations);
+
+        for gen in state.generations.iter().chain(fork) {
             gen.update_alt_blocks(
                 &params,
                 &mut state.alt_block_entities,
@@ -1449,16 +493,6 @@ fn update_alt_blocks(
     }
 }
 
-fn detect_stop_running(mut event: EventReader<SprintEvent>, mut clients: Query<&mut GameState>) {
-    for mut state in clients.iter_mut() {
-        if let Some(event) = event.read().next() {
-            if matches!(event.state, SprintState::Stop) {
-                state.stopped_running = true;
-            }
-        }
-    }
-}
-
 fn spawn_lines(mut commands: Commands, mut clients: Query<(&mut GameState, &EntityLayerId)>) {
     for (mut state, layer) in clients.iter_mut() {
         let mut to_remove = Vec::new();
@@ -1495,8 +529,11 @@ fn spawn_lines(mut commands: Commands, mut clients: Query<(&mut GameState, &Enti
 fn manage_blocks(
     mut commands: Commands,
     mut clients: Query<(&mut Client, &Position, &mut GameState, &mut ChunkLayer)>,
+    config: Res<ServerConfig>,
 ) {
-    for (client, pos, mut state, mut layer) in clients.iter_mut() {
+    for (mut client, pos, mut state, mut layer) in clients.iter_mut() {
+        resolve_fork(&mut state, *pos, &mut client, &mut layer, &mut commands);
+
         if let Some(index) = state
             .generations
             .iter()
@@ -1518,35 +555,252 @@ fn manage_blocks(
 
                     for _ in 0..index {
                         remove_block(state, &mut *layer, &mut commands);
-                        generate_next_block(state, &mut layer);
                     }
+                    generate_next_blocks(state, &mut layer, index as u32);
                 }
-                reached_thing(state, score, client, pos);
+                let old_score = state.score;
+                reached_thing(state.reborrow(), score, client.reborrow(), pos, &config);
+                update_checkpoint(&mut state, old_score, &mut client, &config);
+                // After `reached_thing` so the finish title isn't replaced.
+                update_timer(&mut state, None, &mut client);
+                finish_on_points(&mut state, &mut layer, &mut commands);
             } else {
                 let s = state.generations[0].has_reached_child(*pos);
                 if s > 0 {
-                    reached_thing(state, s, client, pos);
+                    let child = state.generations[0]
+                        .children
+                        .iter()
+                        .filter(|c| c.reached)
+                        .count();
+
+                    let old_score = state.score;
+                    reached_thing(state.reborrow(), s, client.reborrow(), pos, &config);
+                    update_checkpoint(&mut state, old_score, &mut client, &config);
+                    update_timer(&mut state, Some(child), &mut client);
+                    finish_on_points(&mut state, &mut layer, &mut commands);
                 }
             }
         }
     }
 }
 
+/// Continues the course with the branch of the fork the player landed on, and
+/// removes the other branch.
+fn resolve_fork(
+    state: &mut GameState,
+    pos: Position,
+    client: &mut Client,
+    layer: &mut ChunkLayer,
+    commands: &mut Commands,
+) {
+    let Some(branch) = state.fork.as_ref().and_then(|fork| fork.reached(pos)) else {
+        return;
+    };
+    let fork = state.fork.take().unwrap();
+
+    let other = match branch {
+        Branch::Safe => Branch::Hard,
+        Branch::Hard => Branch::Safe,
+    };
+    fork.remove(
+        Some(other),
+        layer,
+        &mut state.alt_block_entities,
+        &mut state.prev_alt_block_states,
+        &mut state.collectible_entities,
+        commands,
+    );
+
+    for gen in fork.branch_generations(branch) {
+        // The branches can overlap, so blocks removed with the other branch
+        // are put back.
+        gen.place(layer);
+        state
+            .recording
+            .generations
+            .push(RecordedGeneration::new(gen));
+        state.generations.push_back(gen.clone());
+    }
+
+    let missing = (AHEAD_GENERATIONS + 1).saturating_sub(state.generations.len());
+    generate_next_blocks(state, layer, missing as u32);
+
+    // Practice runs aren't scored.
+    if branch == Branch::Hard && !state.practice {
+        state.score += HARD_BONUS;
+        client.set_action_bar(
+            format!("{} +{}", branch.name(), HARD_BONUS)
+                .color(Color::GOLD)
+                .bold(),
+        );
+    } else {
+        client.set_action_bar(branch.name().color(Color::GREEN).bold());
+    }
+}
+
+/// Gets the height below which a player has fallen off the course. It is
+/// based on the generation the player is on and the next one, since those are
+/// the only ones they can be on or jumping to. If there are no more
+/// generations before a fork, the fork's branches are the next ones.
+fn get_fall_threshold(state: &GameState) -> f64 {
+    let fork = state
+        .fork
+        .iter()
+        .filter(|_| state.generations.len() < FALL_GENERATIONS)
+        .flat_map(Fork::first_generations);
+
+    state
+        .generations
+        .iter()
+        .take(FALL_GENERATIONS)
+        .chain(fork)
+        .map(|gen| gen.get_lowest_y())
+        .reduce(f64::min)
+        .map_or(f64::NEG_INFINITY, |y| y - FALL_MARGIN)
+}
+
+/// Records a split for the generation the player is on, or for one of its
+/// children. The split and the difference to the player's best run are shown
+/// in the action bar. On finite courses, the run is finished once the finish
+/// is reached.
+fn update_timer(state: &mut GameState, child: Option<usize>, client: &mut Client) {
+    // `generations` only holds the current generation and the ones after it,
+    // while the recording has every generation of the course.
+    let generation = state.recording.generations.len() - state.generations.len();
+    let split = state.timer.split(state.tick, generation, child);
+
+    if child.is_none() && state.generations[0].kind == "finish" {
+        finish_course(state, client);
+        return;
+    }
+
+    let label = match state.theme.length {
+        Some(CourseLength::Segments(segments)) => {
+            format!("Segment {}/{} ", split.label(), segments)
+        }
+        Some(CourseLength::Points(points)) => format!(
+            "Segment {} ({}/{} points) ",
+            split.label(),
+            state.score,
+            points
+        ),
+        None => format!("Split {} ", split.label()),
+    };
+
+    let mut text = label.italic()
+        + timer::format_time(split.ticks)
+            .color(Color::WHITE)
+            .not_italic();
+
+    if let Some(delta) = state.timer.delta(&split) {
+        text = text + " (".color(Color::GRAY) + delta + ")".color(Color::GRAY);
+    }
+
+    client.set_action_bar(text);
+}
+
+/// Shows the time of a finished course, saves it if it is a personal best and
+/// restarts the course.
+fn finish_course(state: &mut GameState, client: &mut Client) {
+    let time = state.timer.finish(state.tick);
+    let prev_best = state
+        .player_data
+        .best_time(&state.theme.name, state.seed, state.difficulty)
+        .map(|best| best.ticks);
+
+    client.set_title("Finished!".color(Color::GOLD).bold());
+    client.set_subtitle(timer::format_time(time.ticks).color(Color::WHITE));
+
+    for line in state.timer.summary(state.tick) {
+        client.send_chat_message(line);
+    }
+
+    // Practice and flagged runs can't set personal bests.
+    if !state.practice && state.flags.is_empty() {
+        let ticks = time.ticks;
+        if state
+            .player_data
+            .submit_time(&state.theme.name, state.seed, state.difficulty, time)
+        {
+            if prev_best.is_some() {
+                client.send_chat_message("New personal best!".color(Color::GREEN).bold());
+            }
+
+            if let Err(e) = state.player_data.save() {
+                println!("Failed to save player data: {}", e);
+            }
+
+            println!(
+                "{} finished {} on seed {} in {}",
+                state.recording.username,
+                state.theme.name,
+                state.seed,
+                timer::format_time(ticks)
+            );
+        }
+    }
+
+    state.pending_restart = Some(state.seed);
+}
+
+/// On courses that are `CourseLength::Points` long, replaces the course ahead
+/// with the finish once the player has enough points.
+fn finish_on_points(state: &mut GameState, layer: &mut ChunkLayer, commands: &mut Commands) {
+    let Some(CourseLength::Points(points)) = state.theme.length else {
+        return;
+    };
+    if state.score < points || state.course.finish_at.is_some() {
+        return;
+    }
+
+    // Only the generation the player is on is kept, and the finish follows
+    // it.
+    let generated = state.recording.generations.len() - state.generations.len();
+    state.course.finish_at = Some(generated as u32);
+    regenerate_next(state, layer, commands);
+}
+
+/// Places a checkpoint if the points the player just got crossed a multiple
+/// of the checkpoint interval.
+fn update_checkpoint(
+    state: &mut GameState,
+    old_score: u32,
+    client: &mut Client,
+    config: &ServerConfig,
+) {
+    let Some(every) = config.failure.checkpoint_interval() else {
+        return;
+    };
+
+    // Practice checkpoints are set by the player.
+    if state.practice {
+        return;
+    }
+
+    if state.score / every > old_score / every {
+        state.checkpoint = Some(Checkpoint::new(state, state.score));
+        client.set_action_bar("Checkpoint reached".color(Color::GREEN).bold());
+    }
+}
+
+/// Gives the player points for reaching part of the course. The points are
+/// multiplied by the player's combo.
 fn reached_thing(
     mut state: Mut<'_, GameState>,
     score: u32,
     mut client: Mut<'_, Client>,
     pos: &Position,
+    config: &ServerConfig,
 ) {
-    if state.stopped_running {
-        state.combo = 0;
-    } else {
-        state.combo += score;
-    }
+    let tick = state.tick;
+    let score = state.combo.add(score, tick, &config.combo);
 
-    state.score += score;
+    // Practice runs aren't scored.
+    if !state.practice {
+        state.score += score;
+    }
 
-    let pitch = 0.9 + ((state.combo as f32) - 1.0) * 0.05;
+    let pitch = 0.9 + ((state.combo.count as f32) - 1.0) * 0.05;
     client.play_sound(
         Sound::BlockNoteBlockBass,
         SoundCategory::Master,
@@ -1554,15 +808,6 @@ fn reached_thing(
         1.0,
         pitch,
     );
-
-    if true
-        || state.score < 50 && state.score % 10 == 0
-        || state.score == 75
-        || state.score >= 100 && state.score % 50 == 0
-    {
-        client.set_title("");
-        client.set_subtitle(state.score.to_string().color(Color::LIGHT_PURPLE).bold());
-    }
 }
 
 fn manage_chunks(mut clients: Query<(&Position, &OldPosition, &mut ChunkLayer), With<Client>>) {
@@ -1594,41 +839,264 @@ fn manage_chunks(mut clients: Query<(&Position, &OldPosition, &mut ChunkLayer),
 fn remove_block(state: &mut GameState, world: &mut ChunkLayer, commands: &mut Commands) {
     let alt_block_entities = &mut state.alt_block_entities;
     let prev_alt_block_states = &mut state.prev_alt_block_states;
+    let collectible_entities = &mut state.collectible_entities;
 
     let removed_block = state.generations.pop_front().unwrap();
-    removed_block.remove(world, alt_block_entities, prev_alt_block_states, commands);
+    removed_block.remove(
+        world,
+        alt_block_entities,
+        prev_alt_block_states,
+        collectible_entities,
+        commands,
+    );
 }
 
-fn generate_next_block(state: &mut GameState, layer: &mut ChunkLayer) {
-    let prev_gen = state.generations.back().unwrap();
+/// Clears the current course and starts a new run on the given seed.
+fn start_run(
+    state: &mut GameState,
+    layer: &mut ChunkLayer,
+    commands: &mut Commands,
+    username: &str,
+    seed: u64,
+    lives: u32,
+) {
+    state.score = 0;
+    state.coins = 0;
+    state.combo = Combo::new(state.tick);
+    state.flags.clear();
+    // Splits are compared against the fastest time on finite courses and
+    // the run with the highest score otherwise.
+    let best = match state.theme.length {
+        Some(_) => state
+            .player_data
+            .best_time(&state.theme.name, seed, state.difficulty)
+            .map(|time| time.splits.clone()),
+        None => RunRecording::load(&state.theme.name, seed, state.difficulty, username)
+            .map(|recording| recording.splits),
+    };
+    state.timer = RunTimer::new(state.tick, best.unwrap_or_default());
+    state.lives = lives;
+    state.checkpoint = None;
+    state.milestones.clear();
+
+    clear_course(state, layer, commands);
+
+    // Everything the generation depends on has to be reset for the seed to
+    // produce the same course.
+    state.seed = seed;
+    state.course = CourseState::new(seed);
+    state.recording = RunRecording::new(username, seed, &state.theme.name, state.difficulty);
+
+    let gen = state.course.first(&state.theme);
+    gen.place(layer);
+    state
+        .recording
+        .generations
+        .push(RecordedGeneration::new(&gen));
+    state.generations.push_back(gen);
+
+    generate_next_blocks(state, layer, AHEAD_GENERATIONS as u32);
+}
 
-    if prev_gen.end_state.get_block_pos().y < MIN_Y {
-        state.target_y = START_POS.y;
-        state.direction = JumpDirection::Up;
-    } else if prev_gen.end_state.get_block_pos().y > MAX_Y {
-        state.target_y = START_POS.y;
-        state.direction = JumpDirection::Down;
-    } else {
-        match state.direction {
-            JumpDirection::Up => {
-                if prev_gen.end_state.get_block_pos().y >= state.target_y {
-                    state.direction = JumpDirection::DoesntMatter;
-                }
-            }
-            JumpDirection::Down => {
-                if prev_gen.end_state.get_block_pos().y <= state.target_y {
-                    state.direction = JumpDirection::DoesntMatter;
-                }
+/// Removes every generation from the world.
+fn clear_course(state: &mut GameState, layer: &mut ChunkLayer, commands: &mut Commands) {
+    for block in &state.generations {
+        block.remove(
+            layer,
+            &mut state.alt_block_entities,
+            &mut state.prev_alt_block_states,
+            &mut state.collectible_entities,
+            commands,
+        );
+    }
+    state.generations.clear();
+
+    if let Some(fork) = state.fork.take() {
+        fork.remove(
+            None,
+            layer,
+            &mut state.alt_block_entities,
+            &mut state.prev_alt_block_states,
+            &mut state.collectible_entities,
+            commands,
+        );
+    }
+}
+
+/// Puts the course back the way it was at the last checkpoint, if there is
+/// one. Returns the position and yaw the player should respawn with, which is
+/// where they set the checkpoint in practice mode, or where they would jump
+/// off of the last generation they reached otherwise.
+fn respawn(state: &mut GameState, layer: &mut ChunkLayer, commands: &mut Commands) -> (DVec3, f32) {
+    if let Some(checkpoint) = state.checkpoint.clone() {
+        clear_course(state, layer, commands);
+
+        let center = checkpoint.generations[0].end_state.get_block_pos();
+        for pos in ChunkView::new(ChunkPos::from(center), VIEW_DIST).iter() {
+            if layer.chunk(pos).is_none() {
+                layer.insert_chunk(pos, UnloadedChunk::new());
             }
-            _ => {}
         }
+
+        for gen in checkpoint.generations {
+            gen.place(layer);
+            state.generations.push_back(gen);
+        }
+
+        if let Some(fork) = checkpoint.fork {
+            fork.place(layer);
+            state.fork = Some(fork);
+        }
+
+        state.score = checkpoint.score;
+        state.coins = checkpoint.coins;
+        state.course = checkpoint.course;
+        state
+            .recording
+            .generations
+            .truncate(checkpoint.recorded_generations);
+        state.timer.splits.truncate(checkpoint.splits);
+    }
+
+    state.combo = Combo::new(state.tick);
+
+    if let Some(spawn) = state.checkpoint.as_ref().and_then(|c| c.spawn) {
+        return spawn;
+    }
+
+    let end_state = &state.generations[0].end_state;
+    let block = end_state.get_block_pos();
+
+    (
+        DVec3::new(
+            block.x as f64 + 0.5,
+            block.y as f64 + 1.0,
+            block.z as f64 + 0.5,
+        ),
+        end_state.yaw.to_degrees(),
+    )
+}
+
+/// Saves the recording of the current run and submits its score to the
+/// leaderboard.
+fn end_run(state: &mut GameState, leaderboard: &mut Leaderboard, commands: &mut Commands) {
+    if let Some(ghost) = state.ghost.take() {
+        ghost.despawn(commands);
+    }
+
+    if state.score == 0 {
+        return;
+    }
+
+    state.recording.score = state.score;
+    state.recording.splits = state.timer.splits.clone();
+
+    let ticks = state.recording.ticks;
+    state.player_data.record_run(state.score, ticks);
+    state.player_data.coins += state.coins as u64;
+
+    if let Err(e) = state.player_data.save() {
+        println!("Failed to save player data: {}", e);
+    }
+
+    if !state.flags.is_empty() {
+        println!(
+            "Not submitting the run of {} on seed {} with a score of {} because it was flagged:",
+            state.recording.username, state.seed, state.score
+        );
+        for flag in &state.flags {
+            println!(
+                "  tick {}: {}: {}",
+                flag.tick,
+                flag.kind.name(),
+                flag.evidence
+            );
+        }
+        return;
+    }
+
+    if let Err(e) = state.recording.save_if_best() {
+        println!("Failed to save recording: {}", e);
     }
 
-    let next_gen = Generator::next_in_generation(state.direction, &state.theme, prev_gen);
+    let improved = leaderboard.submit(LeaderboardEntry {
+        username: state.recording.username.clone(),
+        score: state.score,
+        seed: state.seed,
+        theme: state.theme.name.clone(),
+        difficulty: state.difficulty,
+    });
+
+    if improved {
+        if let Err(e) = leaderboard.save() {
+            println!("Failed to save leaderboard: {}", e);
+        }
+    }
+}
 
-    next_gen.place(layer);
-    state.generations.push_back(next_gen);
+/// Replaces every generation after the one the player is on with new ones.
+fn regenerate_next(state: &mut GameState, layer: &mut ChunkLayer, commands: &mut Commands) {
+    if let Some(fork) = state.fork.take() {
+        fork.remove(
+            None,
+            layer,
+            &mut state.alt_block_entities,
+            &mut state.prev_alt_block_states,
+            &mut state.collectible_entities,
+            commands,
+        );
+    }
+
+    while state.generations.len() > 1 {
+        let gen = state.generations.pop_back().unwrap();
+        gen.remove(
+            layer,
+            &mut state.alt_block_entities,
+            &mut state.prev_alt_block_states,
+            &mut state.collectible_entities,
+            commands,
+        );
+        state.recording.generations.pop();
+    }
 
-    // Combo System
-    state.stopped_running = false;
+    generate_next_blocks(state, layer, AHEAD_GENERATIONS as u32);
+}
+
+/// Adds `count` generations to the end of the course and places them, unless
+/// the course ends or a fork is reached first.
+fn generate_next_blocks(state: &mut GameState, layer: &mut ChunkLayer, count: u32) {
+    // The course continues once the player picks a branch of the fork.
+    if state.fork.is_some() {
+        return;
+    }
+
+    // The first generation is the start, which isn't part of the course.
+    let generated = state.recording.generations.len() as u32 - 1;
+    let recording = &mut state.recording;
+
+    state.fork = course::extend_course(
+        &mut state.course,
+        &state.theme,
+        state.difficulty,
+        &mut state.generations,
+        generated,
+        count,
+        |gen| {
+            // The course continues at the teleport pad's destination, which
+            // can be far away, so its chunks have to be loaded before placing
+            // the blocks.
+            if let Some(destination) = gen.get_teleport_destination() {
+                for pos in ChunkView::new(ChunkPos::from(destination), TELEPORT_VIEW_DIST).iter() {
+                    layer.chunk_entry(pos).or_default();
+                }
+            }
+
+            gen.place(layer);
+            recording.generations.push(RecordedGeneration::new(gen));
+        },
+    );
+
+    if let Some(fork) = &state.fork {
+        fork.place(layer);
+    }
 }
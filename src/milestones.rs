@@ -0,0 +1,139 @@
+use valence::{
+    prelude::*,
+    protocol::{
+        sound::{Sound, SoundCategory},
+        Particle,
+    },
+};
+
+use crate::{
+    config::{Milestone, MilestoneParticles, MilestoneSound, ServerConfig},
+    game_state::GameState,
+    hud::Hud,
+};
+
+impl MilestoneSound {
+    pub fn sound(&self) -> Sound {
+        match self {
+            MilestoneSound::LevelUp => Sound::EntityPlayerLevelup,
+            MilestoneSound::ChallengeComplete => Sound::UiToastChallengeComplete,
+            MilestoneSound::Firework => Sound::EntityFireworkRocketTwinkle,
+            MilestoneSound::Bell => Sound::BlockBellUse,
+            MilestoneSound::Pling => Sound::BlockNoteBlockPling,
+        }
+    }
+}
+
+impl MilestoneParticles {
+    pub fn particle(&self) -> Particle {
+        match self {
+            MilestoneParticles::Firework => Particle::Firework,
+            MilestoneParticles::Totem => Particle::TotemOfUndying,
+            MilestoneParticles::HappyVillager => Particle::HappyVillager,
+            MilestoneParticles::Flame => Particle::Flame,
+        }
+    }
+}
+
+/// Celebrates the milestones players reach. Every milestone is only
+/// celebrated once per run, even if the player falls back below it.
+pub fn celebrate_milestones(
+    mut clients: Query<(&mut Client, &Username, &Position, &mut GameState, &mut Hud)>,
+    config: Res<ServerConfig>,
+) {
+    let mut broadcasts = Vec::new();
+
+    for (mut client, username, pos, mut state, mut hud) in clients.iter_mut() {
+        // The score is lowered when a run ends or a checkpoint is respawned
+        // at, neither of which should be celebrated.
+        if state.score > hud.last_score {
+            let milestones: Vec<Milestone> = config
+                .hud
+                .milestones
+                .reached(state.score)
+                .into_iter()
+                .filter(|m| !state.milestones.contains(&m.score))
+                .collect();
+
+            if milestones.is_empty() && config.hud.score_every_point {
+                client.set_title("");
+                client.set_subtitle(state.score.to_string().color(Color::LIGHT_PURPLE).bold());
+            }
+
+            for milestone in milestones {
+                state.milestones.insert(milestone.score);
+                broadcasts.extend(celebrate(
+                    &milestone,
+                    &mut client,
+                    &username.0,
+                    pos.0,
+                    &mut state,
+                ));
+            }
+        }
+
+        hud.last_score = state.score;
+    }
+
+    for text in broadcasts {
+        for (mut client, ..) in clients.iter_mut() {
+            client.send_chat_message(text.clone());
+        }
+    }
+}
+
+/// Shows a milestone to the player and gives them its unlock. Returns the
+/// message to broadcast, if there is one.
+fn celebrate(
+    milestone: &Milestone,
+    client: &mut Client,
+    username: &str,
+    pos: DVec3,
+    state: &mut GameState,
+) -> Option<Text> {
+    let format = |s: &str| {
+        s.replace("{score}", &milestone.score.to_string())
+            .replace("{player}", username)
+    };
+
+    client.set_title(format(&milestone.title).color(Color::GOLD).bold());
+    client.set_subtitle(
+        format(&milestone.subtitle)
+            .color(Color::LIGHT_PURPLE)
+            .bold(),
+    );
+
+    if let Some(sound) = milestone.sound {
+        client.play_sound(sound.sound(), SoundCategory::Master, pos, 1.0, 1.0);
+    }
+
+    if let Some(particles) = milestone.particles {
+        client.play_particle(
+            &particles.particle(),
+            false,
+            pos + DVec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.5, 1.0, 0.5),
+            0.1,
+            50,
+        );
+    }
+
+    if let Some(unlock) = &milestone.unlock {
+        if !state.player_data.unlocks.contains(unlock) {
+            state.player_data.unlocks.push(unlock.clone());
+
+            if let Err(e) = state.player_data.save() {
+                println!("Failed to save player data: {}", e);
+            }
+
+            client.send_chat_message(
+                "You unlocked ".italic() + unlock.clone().color(Color::GOLD).bold().not_italic(),
+            );
+        }
+    }
+
+    milestone
+        .broadcast
+        .as_ref()
+        .map(|message| format(message).color(Color::GOLD).italic())
+}
@@ -0,0 +1,339 @@
+// Puts generations into the world. This is kept out of the generation
+// library so courses can be generated without a server.
+
+use std::collections::HashMap;
+
+use valence::{
+    entity::{
+        block_display::{self, BlockDisplayEntityBundle},
+        display,
+    },
+    layer::chunk::IntoBlock,
+    prelude::*,
+};
+
+use crate::{
+    alt_block::{AltBlockParams, AltBlockState},
+    generation::{
+        fork::{Branch, Fork},
+        generation::{ChildGeneration, Generation},
+    },
+    utils::*,
+};
+
+/// Places, removes and updates the blocks of a `Generation` in the world.
+pub trait PlaceGeneration {
+    /// Places the blocks in the generation.
+    fn place(&self, world: &mut ChunkLayer);
+
+    /// Removes the blocks and collectibles in the generation.
+    fn remove(
+        &self,
+        world: &mut ChunkLayer,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        collectible_entities: &mut HashMap<BlockPos, Entity>,
+        commands: &mut Commands,
+    );
+
+    /// Updates the alt blocks in the generation.
+    fn update_alt_blocks(
+        &self,
+        params: &AltBlockParams,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        world: &mut ChunkLayer,
+        layer: &EntityLayerId,
+    );
+}
+
+impl PlaceGeneration for Generation {
+    fn place(&self, world: &mut ChunkLayer) {
+        for (pos, block) in &self.blocks {
+            world.set_block(*pos + self.offset.as_ivec3(), *block);
+        }
+
+        for child in &self.children {
+            child.place(world, self.offset);
+        }
+    }
+
+    fn remove(
+        &self,
+        world: &mut ChunkLayer,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        collectible_entities: &mut HashMap<BlockPos, Entity>,
+        commands: &mut Commands,
+    ) {
+        for (pos, _) in &self.blocks {
+            world.set_block(*pos + self.offset.as_ivec3(), BlockState::AIR.into_block());
+        }
+
+        for (pos, _) in &self.alt_blocks {
+            let pos = *pos + self.offset.as_ivec3();
+            if let Some(entity) = alt_block_entities.get_mut(&pos) {
+                if let Some(mut entity) = commands.get_entity(*entity) {
+                    entity.insert(Despawned);
+                }
+
+                alt_block_entities.remove(&pos);
+                prev_alt_block_states.remove(&pos);
+            }
+        }
+
+        for child in &self.children {
+            child.remove(
+                world,
+                alt_block_entities,
+                prev_alt_block_states,
+                commands,
+                self.offset,
+            );
+        }
+
+        for collectible in &self.collectibles {
+            if let Some(entity) = collectible_entities.remove(&collectible.key()) {
+                if let Some(mut entity) = commands.get_entity(entity) {
+                    entity.insert(Despawned);
+                }
+            }
+        }
+    }
+
+    fn update_alt_blocks(
+        &self,
+        params: &AltBlockParams,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        world: &mut ChunkLayer,
+        layer: &EntityLayerId,
+    ) {
+        for (pos, block) in &self.alt_blocks {
+            let block = block.get_block(params);
+            block.set_block(
+                *pos + self.offset.as_ivec3(),
+                alt_block_entities,
+                prev_alt_block_states,
+                commands,
+                world,
+                layer,
+            )
+        }
+
+        for child in &self.children {
+            child.update_alt_blocks(
+                params,
+                alt_block_entities,
+                prev_alt_block_states,
+                commands,
+                world,
+                layer,
+                self.offset,
+            );
+        }
+    }
+}
+
+/// Places, removes and updates the blocks of a `ChildGeneration` in the
+/// world. The positions are relative to the offset of its parent.
+pub trait PlaceChildGeneration {
+    /// Places the blocks in the generation.
+    fn place(&self, world: &mut ChunkLayer, offset: BlockPos);
+
+    /// Removes the blocks in the generation.
+    fn remove(
+        &self,
+        world: &mut ChunkLayer,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        offset: BlockPos,
+    );
+
+    /// Updates the alt blocks in the generation.
+    fn update_alt_blocks(
+        &self,
+        params: &AltBlockParams,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        world: &mut ChunkLayer,
+        layer: &EntityLayerId,
+        offset: BlockPos,
+    );
+}
+
+impl PlaceChildGeneration for ChildGeneration {
+    fn place(&self, world: &mut ChunkLayer, offset: BlockPos) {
+        for (pos, block) in &self.blocks {
+            world.set_block(*pos + offset.as_ivec3(), *block);
+        }
+    }
+
+    fn remove(
+        &self,
+        world: &mut ChunkLayer,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        offset: BlockPos,
+    ) {
+        for (pos, _) in &self.blocks {
+            world.set_block(*pos + offset.as_ivec3(), BlockState::AIR.into_block());
+        }
+
+        for (pos, _) in &self.alt_blocks {
+            let pos = *pos + offset.as_ivec3();
+            if let Some(entity) = alt_block_entities.get_mut(&pos) {
+                if let Some(mut entity) = commands.get_entity(*entity) {
+                    entity.insert(Despawned);
+                }
+
+                alt_block_entities.remove(&pos);
+                prev_alt_block_states.remove(&pos);
+            }
+        }
+    }
+
+    fn update_alt_blocks(
+        &self,
+        params: &AltBlockParams,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        world: &mut ChunkLayer,
+        layer: &EntityLayerId,
+        offset: BlockPos,
+    ) {
+        for (pos, block) in &self.alt_blocks {
+            let block = block.get_block(params);
+            block.set_block(
+                *pos + offset.as_ivec3(),
+                alt_block_entities,
+                prev_alt_block_states,
+                commands,
+                world,
+                layer,
+            )
+        }
+    }
+}
+
+/// Places and removes the branches of a `Fork` in the world.
+pub trait PlaceFork {
+    fn place(&self, world: &mut ChunkLayer);
+
+    /// Removes the blocks of a branch, or of both if `branch` is `None`.
+    fn remove(
+        &self,
+        branch: Option<Branch>,
+        world: &mut ChunkLayer,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        collectible_entities: &mut HashMap<BlockPos, Entity>,
+        commands: &mut Commands,
+    );
+}
+
+impl PlaceFork for Fork {
+    fn place(&self, world: &mut ChunkLayer) {
+        for gen in self.generations() {
+            gen.place(world);
+        }
+    }
+
+    fn remove(
+        &self,
+        branch: Option<Branch>,
+        world: &mut ChunkLayer,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        collectible_entities: &mut HashMap<BlockPos, Entity>,
+        commands: &mut Commands,
+    ) {
+        let generations: Vec<&Generation> = match branch {
+            Some(branch) => self.branch_generations(branch).iter().collect(),
+            None => self.generations().collect(),
+        };
+
+        for gen in generations {
+            gen.remove(
+                world,
+                alt_block_entities,
+                prev_alt_block_states,
+                collectible_entities,
+                commands,
+            );
+        }
+    }
+}
+
+/// Sets an `AltBlockState` in the world.
+pub trait PlaceAltBlockState {
+    /// Sets the block in the world. Small blocks are block displays, which
+    /// are kept track of in `alt_block_entities`.
+    fn set_block(
+        &self,
+        pos: BlockPos,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        world: &mut ChunkLayer,
+        layer: &EntityLayerId,
+    );
+}
+
+impl PlaceAltBlockState for AltBlockState {
+    fn set_block(
+        &self,
+        pos: BlockPos,
+        alt_block_entities: &mut HashMap<BlockPos, Entity>,
+        prev_alt_block_states: &mut HashMap<BlockPos, AltBlockState>,
+        commands: &mut Commands,
+        world: &mut ChunkLayer,
+        layer: &EntityLayerId,
+    ) {
+        if prev_alt_block_states.contains_key(&pos) && *self == prev_alt_block_states[&pos] {
+            return;
+        }
+
+        prev_alt_block_states.insert(pos, *self);
+
+        match self {
+            AltBlockState::Block(block) => {
+                if alt_block_entities.contains_key(&pos) {
+                    if let Some(mut entity) = commands.get_entity(alt_block_entities[&pos]) {
+                        entity.insert(Despawned);
+                    }
+                    alt_block_entities.remove(&pos);
+                }
+                world.set_block(pos, block.into_block());
+            }
+            AltBlockState::SmallBlock(block) => {
+                if alt_block_entities.contains_key(&pos) {
+                    if let Some(mut entity) = commands.get_entity(alt_block_entities[&pos]) {
+                        entity.insert(Despawned);
+                    }
+                }
+
+                world.set_block(pos, BlockState::AIR.into_block());
+
+                let display = BlockDisplayEntityBundle {
+                    position: Position(pos.to_vec3().as_dvec3()),
+                    layer: *layer,
+                    block_display_block_state: block_display::BlockState(*block),
+                    display_scale: display::Scale(Vec3::new(0.5, 0.5, 0.5)),
+                    display_translation: display::Translation(Vec3::new(0.25, 0.25, 0.25)),
+                    ..Default::default()
+                };
+
+                let entity = commands.spawn(display).id();
+
+                alt_block_entities.insert(pos, entity);
+            }
+        }
+    }
+}
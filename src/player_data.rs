@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{generation::difficulty::Difficulty, timer::CourseTime};
+
+pub const PLAYERS_DIR: &str = "data/players";
+/// The longest a Minecraft username can be.
+const MAX_USERNAME_LENGTH: usize = 16;
+
+/// The `PlayerData` struct represents everything that is remembered about a
+/// player between sessions.
+///
+/// Properties:
+///
+/// * `runs`: How many runs the player has finished with a score above zero.
+/// * `best_score`: The highest score the player has reached on any seed.
+/// * `total_score`: The sum of the scores of all of the player's runs.
+/// * `play_ticks`: How many ticks the player has spent in those runs.
+/// * `best_times`: The player's fastest time on every finite course they have
+/// finished, keyed by `course_key`.
+/// * `coins`: How many collectibles the player has picked up.
+/// * `unlocks`: The cosmetics the player has unlocked by reaching milestones.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerData {
+    pub username: String,
+    pub runs: u32,
+    pub best_score: u32,
+    pub total_score: u64,
+    pub play_ticks: u64,
+    pub best_times: HashMap<String, CourseTime>,
+    pub coins: u64,
+    pub unlocks: Vec<String>,
+}
+
+/// Identifies a finite course. The same seed makes a different course with a
+/// different theme or difficulty.
+pub fn course_key(theme: &str, seed: u64, difficulty: Difficulty) -> String {
+    format!("{}:{}:{}", theme, seed, difficulty.name())
+}
+
+/// Whether `name` can be a Minecraft username: 1 to 16 letters, digits or
+/// underscores. Names typed by players have to be checked with this before
+/// they are used in a path.
+pub fn is_valid_username(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_USERNAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl PlayerData {
+    pub fn path(username: &str) -> PathBuf {
+        Path::new(PLAYERS_DIR).join(format!("{}.json", username))
+    }
+
+    /// Loads a player's data from disk. Returns empty data if the player
+    /// hasn't played before.
+    pub fn load(username: &str) -> Self {
+        fs::read_to_string(Self::path(username))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_else(|| Self {
+                username: username.to_owned(),
+                ..Default::default()
+            })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(PLAYERS_DIR)?;
+        fs::write(
+            Self::path(&self.username),
+            serde_json::to_string_pretty(self)?,
+        )
+    }
+
+    pub fn best_time(&self, theme: &str, seed: u64, difficulty: Difficulty) -> Option<&CourseTime> {
+        self.best_times.get(&course_key(theme, seed, difficulty))
+    }
+
+    /// Submits the time of a finished course. Returns true if it is the
+    /// player's new best on that course.
+    pub fn submit_time(
+        &mut self,
+        theme: &str,
+        seed: u64,
+        difficulty: Difficulty,
+        time: CourseTime,
+    ) -> bool {
+        if let Some(best) = self.best_time(theme, seed, difficulty) {
+            if best.ticks <= time.ticks {
+                return false;
+            }
+        }
+
+        self.best_times
+            .insert(course_key(theme, seed, difficulty), time);
+        true
+    }
+
+    pub fn record_run(&mut self, score: u32, ticks: usize) {
+        self.runs += 1;
+        self.best_score = self.best_score.max(score);
+        self.total_score += score as u64;
+        self.play_ticks += ticks as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_usernames() {
+        assert!(is_valid_username("a"));
+        assert!(is_valid_username("Notch"));
+        assert!(is_valid_username("player_123"));
+        assert!(is_valid_username("sixteen_letters_"));
+    }
+
+    #[test]
+    fn invalid_usernames() {
+        assert!(!is_valid_username(""));
+        assert!(!is_valid_username("seventeen_letters"));
+        assert!(!is_valid_username("../leaderboard"));
+        assert!(!is_valid_username("a b"));
+        assert!(!is_valid_username("ümlaut"));
+    }
+}
@@ -0,0 +1,92 @@
+use valence::{interact_item::InteractItemEvent, inventory::HeldItem, prelude::*};
+
+use crate::{anti_cheat::is_on_ground, checkpoint::Checkpoint, game_state::GameState};
+
+/// Sets a checkpoint where the player is standing.
+pub const CHECKPOINT_ITEM: ItemKind = ItemKind::LimeDye;
+/// Replaces the course ahead of the player.
+pub const REGENERATE_ITEM: ItemKind = ItemKind::Clock;
+
+const CHECKPOINT_SLOT: u16 = 36;
+const REGENERATE_SLOT: u16 = 37;
+
+/// Puts the practice items in the player's hotbar.
+pub fn give_items(inventory: &mut Inventory) {
+    inventory.set_slot(CHECKPOINT_SLOT, ItemStack::new(CHECKPOINT_ITEM, 1, None));
+    inventory.set_slot(REGENERATE_SLOT, ItemStack::new(REGENERATE_ITEM, 1, None));
+}
+
+pub fn take_items(inventory: &mut Inventory) {
+    inventory.set_slot(CHECKPOINT_SLOT, ItemStack::EMPTY);
+    inventory.set_slot(REGENERATE_SLOT, ItemStack::EMPTY);
+}
+
+/// Sets a checkpoint at the player's position. The player has to be standing
+/// on a block so they don't respawn in the air.
+pub fn set_checkpoint(
+    state: &mut GameState,
+    client: &mut Client,
+    pos: DVec3,
+    yaw: f32,
+    layer: &ChunkLayer,
+) {
+    if !is_on_ground(pos, layer) {
+        client.send_chat_message(
+            "You have to stand on a block to set a checkpoint.".color(Color::RED),
+        );
+        return;
+    }
+
+    let mut checkpoint = Checkpoint::new(state, state.score);
+    checkpoint.spawn = Some((pos, yaw));
+    state.checkpoint = Some(checkpoint);
+
+    client.set_action_bar("Checkpoint set".color(Color::GREEN).bold());
+}
+
+/// Replaces the course after the generation the player is on.
+pub fn regenerate(
+    state: &mut GameState,
+    client: &mut Client,
+    layer: &mut ChunkLayer,
+    commands: &mut Commands,
+) {
+    crate::regenerate_next(state, layer, commands);
+
+    client.set_action_bar("Course regenerated".color(Color::GREEN).bold());
+}
+
+/// Lets players in practice mode use the practice items.
+pub fn handle_practice_items(
+    mut commands: Commands,
+    mut events: EventReader<InteractItemEvent>,
+    mut clients: Query<(
+        &mut Client,
+        &Inventory,
+        &HeldItem,
+        &Position,
+        &Look,
+        &mut GameState,
+        &mut ChunkLayer,
+    )>,
+) {
+    for event in events.read() {
+        let Ok((mut client, inventory, held_item, pos, look, mut state, mut layer)) =
+            clients.get_mut(event.client)
+        else {
+            continue;
+        };
+
+        if !state.practice {
+            continue;
+        }
+
+        let item = inventory.slot(held_item.slot()).item;
+
+        if item == CHECKPOINT_ITEM {
+            set_checkpoint(&mut state, &mut client, pos.0, look.yaw, &layer);
+        } else if item == REGENERATE_ITEM {
+            regenerate(&mut state, &mut client, &mut layer, &mut commands);
+        }
+    }
+}
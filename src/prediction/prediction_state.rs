@@ -7,7 +7,7 @@ use valence::{
     BlockPos,
 };
 
-use crate::{line::Line3, utils::*};
+use crate::{line::Line3, random, utils::*};
 
 /*
  * Jump: net.minecraft.world.entity.LivingEntity: line ~1950
@@ -58,9 +58,9 @@ impl PredictionState {
             vel,
             yaw,
             color: Vec3::new(
-                rand::thread_rng().gen_range(0f32..1f32),
-                rand::thread_rng().gen_range(0f32..1f32),
-                rand::thread_rng().gen_range(0f32..1f32),
+                random::rng().gen_range(0f32..1f32),
+                random::rng().gen_range(0f32..1f32),
+                random::rng().gen_range(0f32..1f32),
             ),
         }
     }
@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+thread_local! {
+    /// The RNG that generation code draws from while inside `with_rng`.
+    /// Outside of it, `rng()` falls back to `rand::thread_rng()`.
+    static GENERATION_RNG: RefCell<Option<StdRng>> = RefCell::new(None);
+}
+
+/// A handle to the current generation RNG.
+///
+/// Every random choice made while generating a course should go through this
+/// instead of `rand::thread_rng()` so that a course can be reproduced from its
+/// seed.
+pub struct GenerationRng;
+
+impl RngCore for GenerationRng {
+    fn next_u32(&mut self) -> u32 {
+        with_current(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        with_current(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        with_current(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        with_current(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+fn with_current<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    GENERATION_RNG.with(|cell| match cell.borrow_mut().as_mut() {
+        Some(rng) => f(rng),
+        None => f(&mut rand::thread_rng()),
+    })
+}
+
+/// Returns the RNG generation code should use.
+pub fn rng() -> GenerationRng {
+    GenerationRng
+}
+
+/// Creates the RNG for a course with the given seed.
+pub fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
+/// Picks a new random seed for a course.
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+/// Runs `f` with `rng` as the generation RNG, writing the advanced state back
+/// into `rng` afterwards.
+pub fn with_rng<T>(rng: &mut StdRng, f: impl FnOnce() -> T) -> T {
+    let prev = GENERATION_RNG.with(|cell| cell.replace(Some(rng.clone())));
+    let result = f();
+    let used = GENERATION_RNG.with(|cell| cell.replace(prev));
+    *rng = used.expect("Generation RNG was taken while in use");
+    result
+}
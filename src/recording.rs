@@ -0,0 +1,252 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use valence::{
+    entity::{
+        armor_stand::ArmorStandEntityBundle,
+        entity::{CustomName, Flags, NameVisible, NoGravity},
+        HeadYaw,
+    },
+    prelude::*,
+};
+
+use crate::{
+    game_state::GameState,
+    generation::{difficulty::Difficulty, generation::Generation},
+    timer::Split,
+};
+
+pub const RECORDINGS_DIR: &str = "data/recordings";
+/// The most frames a recording keeps, which is 30 minutes. Longer runs are
+/// still timed, but their ghost stops where the frames end.
+pub const MAX_FRAMES: usize = 20 * 60 * 30;
+
+/// The `RunFrame` struct represents where a player was during a single tick of
+/// a run.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RunFrame {
+    pub pos: [f64; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl RunFrame {
+    pub fn new(pos: DVec3, look: &Look) -> Self {
+        Self {
+            pos: pos.to_array(),
+            yaw: look.yaw,
+            pitch: look.pitch,
+        }
+    }
+
+    pub fn pos(&self) -> DVec3 {
+        DVec3::from_array(self.pos)
+    }
+}
+
+/// The `RecordedGeneration` struct represents a generation that was part of a
+/// recorded run.
+///
+/// Properties:
+///
+/// * `kind`: The name of the `GenerationType` that was used.
+/// * `offset`: The offset of the generation.
+/// * `end`: The block the player was expected to jump off of at the end of the
+/// generation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedGeneration {
+    pub kind: String,
+    pub offset: [i32; 3],
+    pub end: [i32; 3],
+}
+
+impl RecordedGeneration {
+    pub fn new(generation: &Generation) -> Self {
+        let end = generation.end_state.get_block_pos();
+        Self {
+            kind: generation.kind.to_owned(),
+            offset: [
+                generation.offset.x,
+                generation.offset.y,
+                generation.offset.z,
+            ],
+            end: [end.x, end.y, end.z],
+        }
+    }
+}
+
+/// The `RunRecording` struct represents everything needed to replay a run.
+///
+/// The course itself isn't stored; it is generated again from `seed`,
+/// `theme` and `difficulty`. `ticks` is how long the run was, and `frames`
+/// holds where the player was during the first `MAX_FRAMES` of them.
+/// `generations` is every generation of the course so far, which splits and
+/// finite courses count and checkpoints roll back. It is saved with the
+/// recording so it can be looked at, but replays don't check it. `splits` are
+/// compared against by later runs on the same seed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunRecording {
+    pub username: String,
+    pub seed: u64,
+    pub theme: String,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    pub score: u32,
+    #[serde(default)]
+    pub ticks: usize,
+    pub frames: Vec<RunFrame>,
+    pub generations: Vec<RecordedGeneration>,
+    #[serde(default)]
+    pub splits: Vec<Split>,
+}
+
+impl RunRecording {
+    pub fn new(username: &str, seed: u64, theme: &str, difficulty: Difficulty) -> Self {
+        Self {
+            username: username.to_owned(),
+            seed,
+            theme: theme.to_owned(),
+            difficulty,
+            ..Default::default()
+        }
+    }
+
+    /// Where the best recording of a player on a course is stored. As with
+    /// `player_data::course_key`, the same seed is a different course with a
+    /// different theme or difficulty.
+    pub fn path(theme: &str, seed: u64, difficulty: Difficulty, username: &str) -> PathBuf {
+        Path::new(RECORDINGS_DIR)
+            .join(theme)
+            .join(seed.to_string())
+            .join(difficulty.name())
+            .join(format!("{}.json", username))
+    }
+
+    pub fn load(theme: &str, seed: u64, difficulty: Difficulty, username: &str) -> Option<Self> {
+        let data = fs::read_to_string(Self::path(theme, seed, difficulty, username)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Saves the recording if it beats the player's previous best on the same
+    /// theme, seed and difficulty. Returns whether it was saved.
+    pub fn save_if_best(&self) -> io::Result<bool> {
+        if let Some(prev) = Self::load(&self.theme, self.seed, self.difficulty, &self.username) {
+            if prev.score >= self.score {
+                return Ok(false);
+            }
+        }
+
+        let path = Self::path(&self.theme, self.seed, self.difficulty, &self.username);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+
+        Ok(true)
+    }
+
+    /// Gets the frame at the given tick of the run. If the run was shorter than
+    /// that, the last frame is returned.
+    pub fn frame(&self, tick: usize) -> Option<&RunFrame> {
+        self.frames.get(tick).or(self.frames.last())
+    }
+}
+
+/// Whose run a ghost is replaying.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GhostSource {
+    PersonalBest,
+    Leaderboard,
+}
+
+/// The `Ghost` struct represents an entity replaying a recorded run next to
+/// the player.
+pub struct Ghost {
+    pub source: GhostSource,
+    pub recording: RunRecording,
+    pub entity: Entity,
+}
+
+impl Ghost {
+    /// Spawns the ghost of a recording. `layer` has to be an entity layer only
+    /// the player can see.
+    pub fn spawn(
+        source: GhostSource,
+        recording: RunRecording,
+        layer: Entity,
+        commands: &mut Commands,
+    ) -> Self {
+        let name = match source {
+            GhostSource::PersonalBest => "Your best run".to_owned(),
+            GhostSource::Leaderboard => format!("{}'s best run", recording.username),
+        };
+
+        let (pos, yaw, pitch) = recording
+            .frame(0)
+            .map_or((DVec3::ZERO, 0.0, 0.0), |f| (f.pos(), f.yaw, f.pitch));
+
+        let entity = commands
+            .spawn(ArmorStandEntityBundle {
+                layer: EntityLayerId(layer),
+                position: Position(pos),
+                look: Look { yaw, pitch },
+                head_yaw: HeadYaw(yaw),
+                entity_no_gravity: NoGravity(true),
+                entity_custom_name: CustomName(Some(name.into())),
+                entity_name_visible: NameVisible(true),
+                entity_flags: {
+                    let mut flags = Flags::default();
+                    flags.set_glowing(true);
+                    flags
+                },
+                ..Default::default()
+            })
+            .id();
+
+        Self {
+            source,
+            recording,
+            entity,
+        }
+    }
+
+    pub fn despawn(&self, commands: &mut Commands) {
+        if let Some(mut entity) = commands.get_entity(self.entity) {
+            entity.insert(Despawned);
+        }
+    }
+}
+
+/// Stores where every player is this tick in their run's recording, until it
+/// has `MAX_FRAMES` frames.
+pub fn record_runs(mut clients: Query<(&Position, &Look, &mut GameState)>) {
+    for (pos, look, mut state) in clients.iter_mut() {
+        let recording = &mut state.recording;
+        recording.ticks += 1;
+        if recording.frames.len() < MAX_FRAMES {
+            recording.frames.push(RunFrame::new(pos.0, look));
+        }
+    }
+}
+
+/// Moves every ghost to where it was at the same tick of its run.
+pub fn update_ghosts(
+    clients: Query<&GameState>,
+    mut ghosts: Query<(&mut Position, &mut Look, &mut HeadYaw), Without<GameState>>,
+) {
+    for state in clients.iter() {
+        if let Some(ghost) = &state.ghost {
+            if let Ok((mut pos, mut look, mut head_yaw)) = ghosts.get_mut(ghost.entity) {
+                if let Some(frame) = ghost.recording.frame(state.recording.ticks) {
+                    pos.0 = frame.pos();
+                    look.yaw = frame.yaw;
+                    look.pitch = frame.pitch;
+                    head_yaw.0 = frame.yaw;
+                }
+            }
+        }
+    }
+}
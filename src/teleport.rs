@@ -0,0 +1,58 @@
+use valence::{
+    entity::Velocity,
+    prelude::*,
+    protocol::{
+        packets::play::EntityVelocityUpdateS2c,
+        sound::{Sound, SoundCategory},
+        VarInt, WritePacket,
+    },
+};
+
+use crate::{anti_cheat::MovementCheck, game_state::GameState};
+
+/// Clients always see themselves as the entity with this id.
+const OWN_ENTITY_ID: i32 = 0;
+
+/// Teleports players standing on the teleport pad of the generation they are
+/// on. Their position on the pad, yaw and velocity are kept.
+pub fn handle_teleports(
+    mut clients: Query<(
+        &mut Client,
+        &mut Position,
+        &mut GameState,
+        &mut MovementCheck,
+    )>,
+) {
+    for (mut client, mut pos, mut state, mut check) in clients.iter_mut() {
+        if let Some(velocity) = state.pending_velocity.take() {
+            // Velocity is in blocks per second, while the prediction works in
+            // blocks per tick.
+            client.write_packet(&EntityVelocityUpdateS2c {
+                entity_id: VarInt(OWN_ENTITY_ID),
+                velocity: Velocity(velocity.as_vec3() * 20.0).to_packet_units(),
+            });
+        }
+
+        let Some(delta) = state
+            .generations
+            .front()
+            .and_then(|gen| gen.get_teleport(pos.0))
+        else {
+            continue;
+        };
+
+        let destination = pos.0 + delta;
+        pos.set(destination);
+        check.teleported(destination);
+        state.prev_pos = destination;
+        state.pending_velocity = Some(state.test_state.vel);
+
+        client.play_sound(
+            Sound::EntityEndermanTeleport,
+            SoundCategory::Master,
+            destination,
+            1.0,
+            1.0,
+        );
+    }
+}
@@ -0,0 +1,1146 @@
+use std::collections::{HashMap, HashSet};
+
+use valence::prelude::*;
+
+use crate::{
+    generation::{
+        block_collection::*,
+        block_grid::{BlockGrid, BlockProperties},
+        generator::GenerationType,
+        generators::*,
+        theme::{CourseLength, GenerationTheme},
+    },
+    weighted_vec,
+    weighted_vec::WeightedVec,
+};
+
+/// The `Themes` resource holds every theme players can pick with `/theme`.
+/// The first one is used for players who just joined.
+#[derive(Resource, Clone, Debug)]
+pub struct Themes {
+    pub themes: Vec<GenerationTheme>,
+}
+
+impl Themes {
+    pub fn get(&self, name: &str) -> Option<&GenerationTheme> {
+        self.themes
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn default_theme(&self) -> &GenerationTheme {
+        &self.themes[0]
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.themes.iter().map(|t| t.name.as_str()).collect()
+    }
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Self {
+            themes: vec![
+                GenerationTheme::new("parkour".to_owned(), block_map(), parkour_types())
+                    .with_collectibles(0.25)
+                    .with_forks(0.1),
+                GenerationTheme::new("classic".to_owned(), block_map(), classic_types())
+                    .with_collectibles(0.25),
+                GenerationTheme::new("timing".to_owned(), block_map(), timing_types()),
+                GenerationTheme::new("sprint".to_owned(), block_map(), classic_types())
+                    .with_length(CourseLength::Segments(30)),
+            ],
+        }
+    }
+}
+
+/// Every generation type, used by the `parkour` theme. The complex presets in
+/// `COMPLEX_PRESETS_DIR` are added to these.
+fn parkour_types() -> WeightedVec<GenerationType> {
+    let mut types = weighted_vec![
+        (GenerationType::Single("jump_block".to_string()), 30.0),
+        (GenerationType::Ramp("ramp_stone".to_string()), 10.0),
+        (GenerationType::Ramp("ramp_wood".to_string()), 10.0),
+        (
+            GenerationType::Indoor {
+                walls: "indoor_walls".to_string(),
+                floor: Some("indoor_floor".to_string()),
+                platforms: "indoor_platforms".to_string(),
+            },
+            2.0
+        ),
+        (
+            GenerationType::Indoor {
+                walls: "indoor_walls".to_string(),
+                floor: Some("water".to_string()),
+                platforms: "indoor_platforms".to_string(),
+            },
+            2.0
+        ),
+        (
+            GenerationType::Indoor {
+                walls: "indoor_walls".to_string(),
+                floor: None,
+                platforms: "indoor_platforms".to_string(),
+            },
+            1.0
+        ),
+        (GenerationType::Cave("cave".to_string()), 5.0),
+        (GenerationType::Teleport("teleport".to_string()), 2.0),
+        (GenerationType::Snake("concrete".to_string()), 10.0),
+        (
+            GenerationType::BlinkBlocks {
+                on: "blink_on".to_string(),
+                off: "blink_off".to_string(),
+                delay: 20,
+                overlap: 5,
+            },
+            10.0
+        ),
+        (
+            GenerationType::SingleCustom(SingleCustomPreset {
+                blocks: BlockGrid::from([
+                    (
+                        BlockPos::new(0, 0, 0),
+                        BlockProperties::new("platform".to_string(), vec![])
+                    ),
+                    (
+                        BlockPos::new(0, 0, 1),
+                        BlockProperties::new("platform".to_string(), vec![])
+                    ),
+                    (
+                        BlockPos::new(0, 0, 2),
+                        BlockProperties::new("platform".to_string(), vec![])
+                    ),
+                    (
+                        BlockPos::new(0, 1, 2),
+                        BlockProperties::new("platform".to_string(), vec![])
+                    ),
+                    (
+                        BlockPos::new(0, 2, 2),
+                        BlockProperties::new("platform".to_string(), vec![])
+                    ),
+                    (
+                        BlockPos::new(0, 0, 3),
+                        BlockProperties::new("platform".to_string(), vec![])
+                    ),
+                    (
+                        BlockPos::new(0, 0, 4),
+                        BlockProperties::new("platform".to_string(), vec![])
+                    ),
+                ]),
+                start_pos: BlockPos::new(0, 0, 0),
+                end_pos: BlockPos::new(0, 0, 4)
+            }),
+            10.0
+        ),
+        (
+            GenerationType::MultiCustom(MultiCustomPreset {
+                presets: HashMap::from([
+                    // TODO: Finish bridge. Also, put everything here in a separate file.
+                    (
+                        "start".to_string(),
+                        SingularMultiCustomPreset {
+                            preset: SingleCustomPreset {
+                                blocks: BlockGrid::from([
+                                    (
+                                        BlockPos::new(0, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                ]),
+                                start_pos: BlockPos::new(0, 0, 0),
+                                end_pos: BlockPos::new(0, 0, 3)
+                            },
+                            nexts: vec!["middle".to_string()],
+                            fixed_offset: Some(BlockPos::new(0, 0, 3)),
+                        }
+                    ),
+                    (
+                        "middle".to_string(),
+                        SingularMultiCustomPreset {
+                            preset: SingleCustomPreset {
+                                blocks: BlockGrid::from([
+                                    (
+                                        BlockPos::new(0, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-2, 1, 0),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                    (
+                                        BlockPos::new(-2, 1, 1),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                    (
+                                        BlockPos::new(-2, 1, 2),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                    (
+                                        BlockPos::new(-2, 1, 3),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                    (
+                                        BlockPos::new(2, 1, 0),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                    (
+                                        BlockPos::new(2, 1, 1),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                    (
+                                        BlockPos::new(2, 1, 2),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                    (
+                                        BlockPos::new(2, 1, 3),
+                                        BlockProperties::new(
+                                            "bridge_wall".to_string(),
+                                            vec![
+                                                (PropName::North, PropValue::Low),
+                                                (PropName::South, PropValue::Low),
+                                                (PropName::Up, PropValue::False)
+                                            ],
+                                        ),
+                                    ),
+                                ]),
+                                start_pos: BlockPos::new(0, 0, 0),
+                                end_pos: BlockPos::new(0, 0, 3)
+                            },
+                            nexts: vec!["middle".to_string()],
+                            fixed_offset: Some(BlockPos::new(0, 0, 3)),
+                        },
+                    ),
+                    (
+                        "end".to_string(),
+                        SingularMultiCustomPreset {
+                            preset: SingleCustomPreset {
+                                blocks: BlockGrid::from([
+                                    (
+                                        BlockPos::new(0, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 0),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 1),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 2),
+                                        BlockProperties::new("bridge_pillar".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 2),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(0, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(-1, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                    (
+                                        BlockPos::new(1, 0, 3),
+                                        BlockProperties::new("bridge_floor".to_string(), vec![]),
+                                    ),
+                                ]),
+                                start_pos: BlockPos::new(0, 0, 0),
+                                end_pos: BlockPos::new(0, 0, 3)
+                            },
+                            nexts: vec!["middle".to_string()],
+                            fixed_offset: Some(BlockPos::new(0, 0, 3)),
+                        },
+                    )
+                ]),
+                start: weighted_vec!["start".to_string()],
+                end: weighted_vec!["end".to_string()],
+                min_length: 2,
+                max_length: 4,
+            }),
+            10.0
+        ),
+        (
+            GenerationType::Island {
+                grass: "island_grass".to_string(),
+                dirt: "island_dirt".to_string(),
+                stone: "island_stone".to_string(),
+                water: "water".to_string(),
+                min_radius: 4,
+                max_radius: 12,
+                min_point_power: 1.0,
+                max_point_power: 1.75,
+            },
+            10.0
+        ),
+        (
+            GenerationType::ComplexCustom(ComplexPreset::new(
+                vec![
+                    ComplexTile {
+                        connection_north: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::South,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(0, 0, 0),
+                                BlockPos::new(0, 0, 1),
+                            ])),
+                            ..Default::default()
+                        }),
+                        connection_south: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::North,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(0, 0, 3),
+                                BlockPos::new(0, 0, 4),
+                            ])),
+                            ..Default::default()
+                        }),
+                        grid: BlockGrid::from([
+                            (
+                                BlockPos::new(0, 0, 0),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 1),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 3),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 4),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                        ]),
+                        ..Default::default()
+                    },
+                    ComplexTile {
+                        connection_north: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::East,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(0, 0, 0),
+                                BlockPos::new(0, 0, 1),
+                            ])),
+                            ..Default::default()
+                        }),
+                        connection_east: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::North,
+                            blocks: Some(HashSet::from([BlockPos::new(2, 0, 2),])),
+                            ..Default::default()
+                        }),
+                        grid: BlockGrid::from([
+                            (
+                                BlockPos::new(0, 0, 0),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 1),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(2, 0, 2),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                        ]),
+                        ..Default::default()
+                    },
+                    ComplexTile {
+                        connection_north: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::South,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(0, 0, 0),
+                                BlockPos::new(0, 0, 1),
+                                BlockPos::new(0, 0, 2),
+                                BlockPos::new(0, 0, 3),
+                                BlockPos::new(0, 0, 4),
+                            ])),
+                            ..Default::default()
+                        }),
+                        connection_south: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::North,
+                            ..Default::default()
+                        }),
+                        connection_east: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::West,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(2, 1, 2),
+                                BlockPos::new(1, 2, 2),
+                                BlockPos::new(1, 2, 3),
+                                BlockPos::new(1, 2, 1),
+                            ])),
+                            can_next: false,
+                            ..Default::default()
+                        }),
+                        connection_west: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::East,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(-1, 1, 2),
+                                BlockPos::new(-2, 2, 2),
+                                BlockPos::new(-2, 2, 1),
+                                BlockPos::new(-2, 2, 3),
+                            ])),
+                            can_next: false,
+                            ..Default::default()
+                        }),
+                        grid: BlockGrid::from([
+                            (
+                                BlockPos::new(0, 0, 0),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 1),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 2),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 3),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 0, 4),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(2, 1, 2),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(1, 2, 2),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(1, 2, 1),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(1, 2, 3),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(-1, 2, 2),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(-1, 2, 1),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(-1, 2, 3),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(-2, 1, 2),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                        ]),
+                        ..Default::default()
+                    },
+                    ComplexTile {
+                        connection_north: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::Up,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(0, 1, 0),
+                                BlockPos::new(0, 2, 1),
+                                BlockPos::new(0, 3, 2),
+                            ])),
+                            can_start: false,
+                            ..Default::default()
+                        }),
+                        connection_up: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::North,
+                            attach_direction: Some(complex_gen::Direction::North),
+                            ..Default::default()
+                        }),
+                        grid: BlockGrid::from([
+                            (
+                                BlockPos::new(0, 1, 0),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 2, 1),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, 3, 2),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                        ]),
+                        ..Default::default()
+                    },
+                    ComplexTile {
+                        connection_north: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::Down,
+                            blocks: Some(HashSet::from([
+                                BlockPos::new(0, 0, 0),
+                                BlockPos::new(0, -1, 1),
+                            ])),
+                            can_start: false,
+                            ..Default::default()
+                        }),
+                        connection_down: Some(Connection {
+                            name: "a".to_string(),
+                            next_direction: complex_gen::Direction::North,
+                            attach_direction: Some(complex_gen::Direction::South),
+                            ..Default::default()
+                        }),
+                        grid: BlockGrid::from([
+                            (
+                                BlockPos::new(0, 0, 0),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                            (
+                                BlockPos::new(0, -1, 1),
+                                BlockProperties::new("concrete".to_string(), vec![])
+                            ),
+                        ]),
+                        ..Default::default()
+                    },
+                    // // a -> b
+                    // ComplexTile {
+                    //     connection_north: Some(Connection {
+                    //         name: "a".to_string(),
+                    //         next_direction: complex_gen::Direction::South,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     connection_south: Some(Connection {
+                    //         name: "b".to_string(),
+                    //         next_direction: complex_gen::Direction::North,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     grid: BlockGrid::from([
+                    //         (
+                    //             BlockPos::new(0, 0, 0),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 1, 1),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 2, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 3, 3),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 4),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //     ]),
+                    //     ..Default::default()
+                    // },
+                    // // b
+                    // ComplexTile {
+                    //     connection_north: Some(Connection {
+                    //         name: "b".to_string(),
+                    //         next_direction: complex_gen::Direction::South,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     connection_south: Some(Connection {
+                    //         name: "b".to_string(),
+                    //         next_direction: complex_gen::Direction::North,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     grid: BlockGrid::from([
+                    //         (
+                    //             BlockPos::new(0, 4, 0),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 1),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 3),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 4),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //     ]),
+                    //     ..Default::default()
+                    // },
+                    // ComplexTile {
+                    //     connection_north: Some(Connection {
+                    //         name: "b".to_string(),
+                    //         next_direction: complex_gen::Direction::East,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     connection_east: Some(Connection {
+                    //         name: "b".to_string(),
+                    //         next_direction: complex_gen::Direction::North,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     grid: BlockGrid::from([
+                    //         (
+                    //             BlockPos::new(0, 4, 0),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 1),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(1, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(2, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //     ]),
+                    //     ..Default::default()
+                    // },
+                    // // a & b junction
+                    // ComplexTile {
+                    //     connection_north: Some(Connection {
+                    //         name: "a".to_string(),
+                    //         next_direction: complex_gen::Direction::South,
+                    //         ..Default::default()
+                    //     }),
+                    //     connection_south: Some(Connection {
+                    //         name: "a".to_string(),
+                    //         next_direction: complex_gen::Direction::North,
+                    //         ..Default::default()
+                    //     }),
+                    //     connection_east: Some(Connection {
+                    //         name: "b".to_string(),
+                    //         next_direction: complex_gen::Direction::West,
+                    //         can_next: false,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     connection_west: Some(Connection {
+                    //         name: "b".to_string(),
+                    //         next_direction: complex_gen::Direction::East,
+                    //         can_next: false,
+                    //         can_start: false,
+                    //         ..Default::default()
+                    //     }),
+                    //     grid: BlockGrid::from([
+                    //         (
+                    //             BlockPos::new(0, 0, 0),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 0, 1),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 0, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 0, 3),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 0, 4),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(2, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(1, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(0, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(-1, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //         (
+                    //             BlockPos::new(-2, 4, 2),
+                    //             BlockProperties::new("platform".to_string(), vec![])
+                    //         ),
+                    //     ]),
+                    //     ..Default::default()
+                    // },
+                ],
+                BlockPos::new(5, 5, 5),
+                BlockPos::new(-5, 0, 0),
+                BlockPos::new(5, 1, 10),
+            )),
+            5.0
+        ),
+    ];
+
+    types.push_all(
+        SingleCustomPreset::load_all()
+            .into_iter()
+            .map(|preset| (GenerationType::SingleCustom(preset), 5.0)),
+    );
+
+    types.push_all(
+        ComplexPreset::load_all()
+            .into_iter()
+            .map(|preset| (GenerationType::ComplexCustom(preset), 5.0)),
+    );
+
+    types
+}
+
+/// Plain jumps and ramps only.
+fn classic_types() -> WeightedVec<GenerationType> {
+    weighted_vec![
+        (GenerationType::Single("jump_block".to_string()), 30.0),
+        (GenerationType::Ramp("ramp_stone".to_string()), 5.0),
+        (GenerationType::Ramp("ramp_wood".to_string()), 5.0),
+    ]
+}
+
+/// Generations that need good timing, with single jumps in between.
+fn timing_types() -> WeightedVec<GenerationType> {
+    weighted_vec![
+        (GenerationType::Single("jump_block".to_string()), 10.0),
+        (GenerationType::Snake("concrete".to_string()), 10.0),
+        (
+            GenerationType::BlinkBlocks {
+                on: "blink_on".to_string(),
+                off: "blink_off".to_string(),
+                delay: 20,
+                overlap: 5,
+            },
+            10.0
+        ),
+        (
+            GenerationType::BlinkBlocks {
+                on: "blink_on".to_string(),
+                off: "blink_off".to_string(),
+                delay: 14,
+                overlap: 3,
+            },
+            5.0
+        ),
+    ]
+}
+
+/// The block collections shared by all themes.
+fn block_map() -> BlockCollectionMap {
+    BlockCollectionMap::from([
+        (
+            "teleport",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::CRYING_OBSIDIAN],
+                uniform: true,
+            }),
+        ),
+        (
+            "finish",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::GOLD_BLOCK],
+                uniform: true,
+            }),
+        ),
+        (
+            "jump_block",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::GRASS_BLOCK,
+                    BlockState::OAK_LOG,
+                    BlockState::BIRCH_LOG,
+                    BlockState::OAK_LEAVES,
+                    BlockState::BIRCH_LEAVES,
+                    BlockState::DIRT,
+                    BlockState::MOSS_BLOCK,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "ramp_stone",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::STONE,
+                    BlockState::COBBLESTONE,
+                    BlockState::MOSSY_COBBLESTONE,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "ramp_stone_slab",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::STONE_SLAB,
+                    BlockState::COBBLESTONE_SLAB,
+                    BlockState::MOSSY_COBBLESTONE_SLAB,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "ramp_wood",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::OAK_PLANKS,
+                    BlockState::SPRUCE_PLANKS,
+                    BlockState::BIRCH_PLANKS,
+                    BlockState::JUNGLE_PLANKS,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "ramp_wood_slab",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::OAK_SLAB,
+                    BlockState::SPRUCE_SLAB,
+                    BlockState::BIRCH_SLAB,
+                    BlockState::JUNGLE_SLAB,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "indoor_walls",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::BRICKS,],
+                uniform: true,
+            }),
+        ),
+        (
+            "indoor_floor",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::COBBLED_DEEPSLATE,],
+                uniform: true,
+            }),
+        ),
+        (
+            "indoor_platforms",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::STONE,],
+                uniform: true,
+            }),
+        ),
+        (
+            "water",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::WATER,],
+                uniform: true,
+            }),
+        ),
+        (
+            "cave",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::STONE,
+                    BlockState::COBBLESTONE,
+                    BlockState::MOSSY_COBBLESTONE,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "island_grass",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::GRASS_BLOCK,],
+                uniform: true,
+            }),
+        ),
+        (
+            "island_dirt",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::DIRT, BlockState::COARSE_DIRT,],
+                uniform: false,
+            }),
+        ),
+        (
+            "island_stone",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::STONE,
+                    BlockState::COBBLESTONE,
+                    BlockState::MOSSY_COBBLESTONE,
+                    BlockState::DEEPSLATE,
+                    BlockState::COBBLED_DEEPSLATE,
+                    BlockState::ANDESITE,
+                    BlockState::DIORITE,
+                    BlockState::GRANITE,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "concrete",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::WHITE_CONCRETE,
+                    BlockState::ORANGE_CONCRETE,
+                    BlockState::MAGENTA_CONCRETE,
+                    BlockState::LIGHT_BLUE_CONCRETE,
+                    BlockState::YELLOW_CONCRETE,
+                    BlockState::LIME_CONCRETE,
+                    BlockState::PINK_CONCRETE,
+                    BlockState::GRAY_CONCRETE,
+                    BlockState::LIGHT_GRAY_CONCRETE,
+                    BlockState::CYAN_CONCRETE,
+                    BlockState::PURPLE_CONCRETE,
+                    BlockState::BLUE_CONCRETE,
+                    BlockState::BROWN_CONCRETE,
+                    BlockState::GREEN_CONCRETE,
+                    BlockState::RED_CONCRETE,
+                    // BlockState::BLACK_CONCRETE, // black has no contrast and is completely invisible at night
+                ],
+                uniform: true,
+            }),
+        ),
+        (
+            "blink_on",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::BLUE_CONCRETE,],
+                uniform: true,
+            }),
+        ),
+        (
+            "blink_off",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::RED_CONCRETE,],
+                uniform: true,
+            }),
+        ),
+        (
+            "platform",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![
+                    BlockState::STONE,
+                    BlockState::COBBLESTONE,
+                    BlockState::MOSSY_COBBLESTONE,
+                    BlockState::OAK_PLANKS,
+                    BlockState::SPRUCE_PLANKS,
+                    BlockState::BIRCH_PLANKS,
+                    BlockState::JUNGLE_PLANKS,
+                ],
+                uniform: false,
+            }),
+        ),
+        (
+            "bridge_side",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::BRICKS],
+                uniform: false,
+            }),
+        ),
+        (
+            "bridge_wall",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::BRICK_WALL],
+                uniform: false,
+            }),
+        ),
+        (
+            "bridge_floor",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::OAK_PLANKS],
+                uniform: false,
+            }),
+        ),
+        (
+            "bridge_pillar",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::STONE_BRICKS],
+                uniform: false,
+            }),
+        ),
+        (
+            "bridge_pillar_stair",
+            BlockCollection(BlockChoice {
+                blocks: weighted_vec![BlockState::STONE_BRICK_STAIRS],
+                uniform: false,
+            }),
+        ),
+    ])
+}
@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use valence::prelude::*;
+
+const TICKS_PER_SECOND: usize = 20;
+
+/// The `Split` struct represents the time a part of the course was reached at.
+///
+/// Properties:
+///
+/// * `generation`: The index of the generation in the course. The start is 0.
+/// * `child`: How many of the generation's children had been reached, if the
+/// split is for a child generation.
+/// * `ticks`: The time from the start of the run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Split {
+    pub generation: usize,
+    pub child: Option<usize>,
+    pub ticks: usize,
+}
+
+impl Split {
+    /// Whether both splits are for the same part of the course.
+    pub fn same_place(&self, other: &Split) -> bool {
+        self.generation == other.generation && self.child == other.child
+    }
+
+    pub fn label(&self) -> String {
+        match self.child {
+            Some(child) => format!("{}.{}", self.generation, child),
+            None => self.generation.to_string(),
+        }
+    }
+}
+
+/// The `CourseTime` struct represents how long a finished run took.
+///
+/// Properties:
+///
+/// * `ticks`: The time from the start to reaching the finish.
+/// * `splits`: The splits of the run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CourseTime {
+    pub ticks: usize,
+    pub splits: Vec<Split>,
+}
+
+/// The `RunTimer` struct keeps track of how long the current run has taken.
+///
+/// Properties:
+///
+/// * `start`: The tick the run started at.
+/// * `splits`: The splits of the run so far.
+/// * `best`: The splits of the player's best run on the same course, to
+/// compare against.
+#[derive(Clone, Debug, Default)]
+pub struct RunTimer {
+    pub start: usize,
+    pub splits: Vec<Split>,
+    pub best: Vec<Split>,
+}
+
+impl RunTimer {
+    pub fn new(tick: usize, best: Vec<Split>) -> Self {
+        Self {
+            start: tick,
+            splits: Vec::new(),
+            best,
+        }
+    }
+
+    pub fn elapsed(&self, tick: usize) -> usize {
+        tick.saturating_sub(self.start)
+    }
+
+    /// Records reaching a part of the course. Returns the split.
+    pub fn split(&mut self, tick: usize, generation: usize, child: Option<usize>) -> Split {
+        let split = Split {
+            generation,
+            child,
+            ticks: self.elapsed(tick),
+        };
+        self.splits.push(split);
+        split
+    }
+
+    pub fn finish(&self, tick: usize) -> CourseTime {
+        CourseTime {
+            ticks: self.elapsed(tick),
+            splits: self.splits.clone(),
+        }
+    }
+
+    /// Gets the split of the best run at the same part of the course.
+    pub fn best_split(&self, split: &Split) -> Option<&Split> {
+        self.best.iter().find(|best| best.same_place(split))
+    }
+
+    /// Formats the difference between a split and the best run's split at
+    /// the same part of the course.
+    pub fn delta(&self, split: &Split) -> Option<Text> {
+        self.best_split(split)
+            .map(|best| format_delta(split.ticks, best.ticks))
+    }
+
+    /// Creates the summary shown at the end of a run: the total time, how it
+    /// compares to the best run, and the segments where the most time was
+    /// gained and lost.
+    pub fn summary(&self, tick: usize) -> Vec<Text> {
+        let mut lines = Vec::new();
+
+        let mut time = "Time: ".italic()
+            + format_time(self.elapsed(tick))
+                .color(Color::GOLD)
+                .bold()
+                .not_italic();
+
+        // The whole run can only be compared up to the last split both runs
+        // reached.
+        if let Some(delta) = self.splits.iter().rev().find_map(|split| self.delta(split)) {
+            time = time + " (".color(Color::GRAY) + delta + ")".color(Color::GRAY);
+        }
+
+        lines.push(time);
+        lines.push("Splits: ".italic() + self.splits.len().to_string().not_italic());
+
+        // How much faster or slower each segment was than in the best run.
+        let segments: Vec<(Split, i64)> = self
+            .splits
+            .windows(2)
+            .filter_map(|pair| {
+                let best_start = self.best_split(&pair[0])?;
+                let best_end = self.best_split(&pair[1])?;
+                let duration = (pair[1].ticks - pair[0].ticks) as i64;
+                let best_duration = best_end.ticks as i64 - best_start.ticks as i64;
+                Some((pair[1], duration - best_duration))
+            })
+            .collect();
+
+        if let Some((split, diff)) = segments.iter().min_by_key(|(_, diff)| *diff) {
+            if *diff < 0 {
+                lines.push(
+                    format!("Most time gained at {}: ", split.label()).italic()
+                        + format_diff(*diff),
+                );
+            }
+        }
+
+        if let Some((split, diff)) = segments.iter().max_by_key(|(_, diff)| *diff) {
+            if *diff > 0 {
+                lines.push(
+                    format!("Most time lost at {}: ", split.label()).italic() + format_diff(*diff),
+                );
+            }
+        }
+
+        lines
+    }
+}
+
+/// Formats a time as `minutes:seconds.hundredths`.
+pub fn format_time(ticks: usize) -> String {
+    let millis = ticks * 1000 / TICKS_PER_SECOND;
+    format!(
+        "{}:{:02}.{:02}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis / 10 % 100
+    )
+}
+
+/// Formats the difference between a time and a personal best. Green if the
+/// time is faster, red if it is slower.
+pub fn format_delta(ticks: usize, best: usize) -> Text {
+    format_diff(ticks as i64 - best as i64)
+}
+
+fn format_diff(diff: i64) -> Text {
+    let seconds = diff.abs() as f64 / TICKS_PER_SECOND as f64;
+
+    if diff <= 0 {
+        format!("-{:.2}", seconds).color(Color::GREEN).not_italic()
+    } else {
+        format!("+{:.2}", seconds).color(Color::RED).not_italic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_time_shows_minutes_seconds_and_hundredths() {
+        assert_eq!(format_time(0), "0:00.00");
+        assert_eq!(format_time(1), "0:00.05");
+        assert_eq!(format_time(59 * TICKS_PER_SECOND + 19), "0:59.95");
+        assert_eq!(format_time(61 * TICKS_PER_SECOND + 10), "1:01.50");
+        assert_eq!(format_time(600 * TICKS_PER_SECOND), "10:00.00");
+    }
+
+    #[test]
+    fn format_delta_is_green_when_faster() {
+        assert_eq!(
+            format_delta(90, 100),
+            "-0.50".color(Color::GREEN).not_italic()
+        );
+        assert_eq!(
+            format_delta(100, 100),
+            "-0.00".color(Color::GREEN).not_italic()
+        );
+    }
+
+    #[test]
+    fn format_delta_is_red_when_slower() {
+        assert_eq!(
+            format_delta(101, 100),
+            "+0.05".color(Color::RED).not_italic()
+        );
+        assert_eq!(
+            format_delta(1300, 100),
+            "+60.00".color(Color::RED).not_italic()
+        );
+    }
+}
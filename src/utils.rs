@@ -10,7 +10,7 @@ use valence::{
     BlockPos,
 };
 
-use crate::{line::Line3, prediction::prediction_state::PredictionState};
+use crate::{line::Line3, prediction::prediction_state::PredictionState, random};
 
 pub const PLAYER_WIDTH: f64 = 0.6;
 pub const PLAYER_HEIGHT: f64 = 1.8;
@@ -150,7 +150,7 @@ pub fn random_yaw() -> f32 {
 
 pub fn random_yaw_dist(f: impl Into<f32>) -> f32 {
     let f = f.into();
-    rand::thread_rng().gen_range(-f..f).to_radians()
+    random::rng().gen_range(-f..f).to_radians()
 }
 
 pub fn get_blocks_between(start: Vec3, end: Vec3) -> Vec<BlockPos> {
@@ -364,7 +364,7 @@ pub fn get_dirs_next_to(dir: BlockPos) -> [BlockPos; 4] {
 }
 
 pub fn random_sign() -> i32 {
-    if rand::thread_rng().gen() {
+    if random::rng().gen() {
         1
     } else {
         -1
@@ -507,8 +507,8 @@ impl JumpDirection {
     pub fn get_y_offset(self) -> i32 {
         match self {
             JumpDirection::Up => 1,
-            JumpDirection::Down => -rand::thread_rng().gen_range(1..=2),
-            JumpDirection::DoesntMatter => rand::thread_rng().gen_range(-1..=1),
+            JumpDirection::Down => -random::rng().gen_range(1..=2),
+            JumpDirection::DoesntMatter => random::rng().gen_range(-1..=1),
         }
     }
 
@@ -516,7 +516,7 @@ impl JumpDirection {
         match self {
             JumpDirection::Up => false,
             JumpDirection::Down => true,
-            JumpDirection::DoesntMatter => rand::thread_rng().gen(),
+            JumpDirection::DoesntMatter => random::rng().gen(),
         }
     }
 
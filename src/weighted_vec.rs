@@ -1,5 +1,7 @@
 use rand::prelude::*;
 
+use crate::random;
+
 /// A weighted vec is a vec of elements with a weight associated with each element.
 /// The weight is used to determine the probability of an element being selected.
 #[derive(Debug, Clone)]
@@ -60,7 +62,7 @@ impl<T> WeightedVec<T> {
 
 impl<T> WeightedVec<T> {
     pub fn get_random(&self) -> Option<&T> {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let total_weight = self.vec.iter().map(|(_, weight)| weight).sum();
 
@@ -77,7 +79,7 @@ impl<T> WeightedVec<T> {
     }
 
     pub fn get_random_index(&self) -> Option<usize> {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let total_weight = self.vec.iter().map(|(_, weight)| weight).sum();
 